@@ -1,9 +1,10 @@
 #![feature(test)]
+#![cfg(feature = "tensorflow")]
 
 extern crate test;
 use test::Bencher;
 
-use tfmicro::{MicroInterpreter, Model, MutableOpResolver};
+use tfmicro::{include_model, MicroInterpreter, Model, MutableOpResolver};
 
 use itertools::Itertools;
 use log::info;
@@ -16,7 +17,7 @@ fn person_detection(b: &mut Bencher) {
 
     // Include trained model and test datasets
     let model =
-        include_bytes!("../examples/models/person_detection_grayscale.tflite");
+        include_model!("../examples/models/person_detection_grayscale.tflite");
     let no_person = include_bytes!(
         "../examples/models/no_person_image_data_grayscale.data"
     );
@@ -25,21 +26,24 @@ fn person_detection(b: &mut Bencher) {
 
     // Map the model into a usable data structure. This doesn't involve
     // any copying or parsing, it's a very lightweight operation.
-    let model = Model::from_buffer(&model[..]).unwrap();
+    let model = Model::from_buffer(model).unwrap();
 
     // Create memory area for input, output and intermediate arrays
     const TENSOR_ARENA_SIZE: usize = 93 * 1024;
     let mut tensor_arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
 
-    let micro_op_resolver = MutableOpResolver::empty()
+    let micro_op_resolver = MutableOpResolver::<3>::empty()
         .depthwise_conv_2d()
         .conv_2d()
         .average_pool_2d();
 
     // Build an interpreter to run the model with
-    let mut interpreter =
-        MicroInterpreter::new(&model, micro_op_resolver, &mut tensor_arena[..])
-            .unwrap();
+    let mut interpreter = MicroInterpreter::new(
+        &model,
+        &micro_op_resolver,
+        &mut tensor_arena[..],
+    )
+    .unwrap();
 
     info!("Created setup");
 
@@ -1,7 +1,14 @@
 //! Build
+//!
+//! TensorFlow Lite for Microcontrollers is only built with the `tensorflow`
+//! feature. Without it, only the pure Rust parts of the crate are built, and
+//! nothing needs to be done here.
 
+#[cfg(feature = "tensorflow")]
 #[macro_use]
 extern crate error_chain;
+
+#[cfg(feature = "tensorflow")]
 error_chain! {
     foreign_links {
         Io(::std::io::Error);
@@ -10,16 +17,24 @@ error_chain! {
     }
 }
 
+#[cfg(feature = "tensorflow")]
 use glob::glob;
 
+#[cfg(feature = "tensorflow")]
 use std::env;
+#[cfg(feature = "tensorflow")]
 use std::path::{Path, PathBuf};
+#[cfg(feature = "tensorflow")]
 use std::process::Command;
+#[cfg(feature = "tensorflow")]
 use std::time::Instant;
 
+#[cfg(feature = "tensorflow")]
 use std::borrow::Borrow;
+#[cfg(feature = "tensorflow")]
 use std::ffi::OsStr;
 
+#[cfg(feature = "tensorflow")]
 fn run_command_or_fail<P, S>(dir: &str, cmd: P, args: &[S])
 where
     P: AsRef<Path>,
@@ -53,6 +68,7 @@ where
     }
 }
 
+#[cfg(feature = "tensorflow")]
 fn check_submodules_or_checkout_from_git() {
     if !Path::new("submodules/tensorflow/LICENSE").exists() {
         eprintln!("Setting up submodules");
@@ -65,22 +81,27 @@ fn check_submodules_or_checkout_from_git() {
     }
 }
 
+#[cfg(feature = "tensorflow")]
 fn manifest_dir() -> PathBuf {
     PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap())
 }
 
+#[cfg(feature = "tensorflow")]
 fn submodules() -> PathBuf {
     manifest_dir().join("submodules")
 }
 
+#[cfg(feature = "tensorflow")]
 fn flatbuffers_include_dir() -> PathBuf {
     submodules().join("tensorflow/tensorflow/lite/micro/tools/make/downloads/flatbuffers/include")
 }
 
+#[cfg(feature = "tensorflow")]
 fn is_cross_compiling() -> Result<bool> {
     Ok(env::var("TARGET")? != env::var("HOST")?)
 }
 
+#[cfg(feature = "tensorflow")]
 fn get_command_result(command: &mut Command) -> Result<String> {
     command
         .output()
@@ -95,6 +116,7 @@ fn get_command_result(command: &mut Command) -> Result<String> {
 }
 
 /// Move tensorflow source to $OUT_DIR
+#[cfg(feature = "tensorflow")]
 fn prepare_tensorflow_source() -> PathBuf {
     println!("Moving tensorflow micro source");
     let start = Instant::now();
@@ -126,6 +148,7 @@ fn prepare_tensorflow_source() -> PathBuf {
 
 /// Return a Vec of all *.cc files in `path`, excluding those that have a
 /// name containing 'test.cc'
+#[cfg(feature = "tensorflow")]
 fn get_files_glob(path: PathBuf) -> Vec<String> {
     let mut paths: Vec<String> = vec![];
 
@@ -143,6 +166,7 @@ fn get_files_glob(path: PathBuf) -> Vec<String> {
         .collect()
 }
 
+#[cfg(feature = "tensorflow")]
 trait CompilationBuilder {
     fn flag(&mut self, s: &str) -> &mut Self;
     fn define(&mut self, var: &str, val: Option<&str>) -> &mut Self;
@@ -186,6 +210,21 @@ trait CompilationBuilder {
                 .flag("-Wunused-function")
         };
 
+        // Instrument the C++ code with the same sanitizers as the Rust code,
+        // so that errors inside tensorflow are caught when fuzzing
+        let build = if cfg!(feature = "sanitize") {
+            let sanitizers = env::var("CARGO_CFG_SANITIZE").expect(
+                "The sanitize feature requires a Rust sanitizer, \
+                 for example `cargo fuzz run` or -Zsanitizer=address",
+            );
+            build
+                .flag(&format!("-fsanitize={}", sanitizers))
+                .flag("-fno-omit-frame-pointer")
+                .flag("-g")
+        } else {
+            build
+        };
+
         if target.starts_with("thumb") {
             // unaligned accesses are usually a poor idea on ARM cortex-m
             build.flag("-mno-unaligned-access")
@@ -194,6 +233,7 @@ trait CompilationBuilder {
         }
     }
 }
+#[cfg(feature = "tensorflow")]
 impl CompilationBuilder for cpp_build::Config {
     fn flag(&mut self, s: &str) -> &mut Self {
         self.flag(s)
@@ -202,6 +242,7 @@ impl CompilationBuilder for cpp_build::Config {
         self.define(var, val)
     }
 }
+#[cfg(feature = "tensorflow")]
 impl CompilationBuilder for cc::Build {
     fn flag(&mut self, s: &str) -> &mut Self {
         self.flag(s)
@@ -211,11 +252,14 @@ impl CompilationBuilder for cc::Build {
     }
 }
 
+#[cfg(feature = "tensorflow")]
 fn cc_tensorflow_library() {
     let tflite = prepare_tensorflow_source();
     let out_dir = env::var("OUT_DIR").unwrap();
     let tf_lib_name =
         Path::new(&out_dir).join("libtensorflow-microlite.a".to_string());
+    let frontend_lib_name =
+        Path::new(&out_dir).join("libtensorflow-microfrontend.a".to_string());
 
     if is_cross_compiling().unwrap() {
         // Find include directory used by the crosscompiler for libm
@@ -234,7 +278,10 @@ fn cc_tensorflow_library() {
         println!("cargo:rustc-link-lib=static=m");
     }
 
-    if !tf_lib_name.exists() || cfg!(feature = "build") {
+    if !tf_lib_name.exists()
+        || !frontend_lib_name.exists()
+        || cfg!(feature = "build")
+    {
         println!("Building tensorflow micro");
         let target = env::var("TARGET").unwrap_or_else(|_| "".to_string());
         let tfmicro_mdir = tflite.join("lite/micro/tools/make/");
@@ -257,9 +304,6 @@ fn cc_tensorflow_library() {
             .files(get_files_glob(
                 tflite.join("lite/micro/memory_planner/*.cc"),
             ))
-            .files(get_files_glob(
-                tflite.join("lite/experimental/microfrontend/lib/*.c"),
-            ))
             .file(tflite.join("lite/c/common.c"))
             .file(tflite.join("lite/core/api/error_reporter.cc"))
             .file(tflite.join("lite/core/api/flatbuffer_conversions.cc"))
@@ -283,19 +327,33 @@ fn cc_tensorflow_library() {
                 .include(cmsis.join("CMSIS/Core/Include"));
         }
 
+        // Compile
+        builder_ref.compile("tensorflow-microlite");
+
         // micro frontend
-        builder_ref
+        //
+        // This is built separately, so that calls to malloc and free can be
+        // redirected to the allocator in src/frontend/allocator.rs
+        cc::Build::new()
+            .cpp(true)
+            .tensorflow_build_setup()
+            .cpp_link_stdlib(None)
+            .define("malloc", Some("tfmicro_frontend_malloc"))
+            .define("free", Some("tfmicro_frontend_free"))
+            //
+            .include(tflite.parent().unwrap())
             .include(tfmicro_mdir.join("downloads/kissfft"))
             .include(tfmicro_mdir.join("downloads/kissfft/tools"))
             .include(tflite.join("lite/experimental/microfrontend/lib"))
+            .files(get_files_glob(
+                tflite.join("lite/experimental/microfrontend/lib/*.c"),
+            ))
             .files(get_files_glob(
                 tflite.join("lite/experimental/microfrontend/lib/*.cc"),
             ))
             .file(tfmicro_mdir.join("downloads/kissfft/kiss_fft.c"))
-            .file(tfmicro_mdir.join("downloads/kissfft/tools/kiss_fftr.c"));
-
-        // Compile
-        builder_ref.compile("tensorflow-microlite");
+            .file(tfmicro_mdir.join("downloads/kissfft/tools/kiss_fftr.c"))
+            .compile("tensorflow-microfrontend");
 
         println!(
             "Building tensorflow micro from source took {:?}",
@@ -305,11 +363,13 @@ fn cc_tensorflow_library() {
         println!("Didn't rebuild tensorflow micro, using {:?}", tf_lib_name);
 
         println!("cargo:rustc-link-lib=static=tensorflow-microlite");
+        println!("cargo:rustc-link-lib=static=tensorflow-microfrontend");
         println!("cargo:rustc-link-search=native={}", out_dir);
     }
 }
 
 /// Configure bindgen for cross-compiling
+#[cfg(feature = "tensorflow")]
 fn bindgen_cross_builder() -> Result<bindgen::Builder> {
     let builder = bindgen::Builder::default().clang_arg("--verbose");
 
@@ -369,6 +429,7 @@ fn bindgen_cross_builder() -> Result<bindgen::Builder> {
 
 /// This generates "tflite_types.rs" containing structs and enums which are
 /// inter-operable with rust
+#[cfg(feature = "tensorflow")]
 fn bindgen_tflite_types() {
     use bindgen::*;
 
@@ -400,9 +461,8 @@ fn bindgen_tflite_types() {
             .opaque_type("tflite::Model")
             .whitelist_type("tflite::MicroInterpreter")
             .opaque_type("tflite::MicroInterpreter")
-            .whitelist_type("tflite::ops::micro::AllOpsResolver")
-            .opaque_type("tflite::ops::micro::AllOpsResolver")
             .whitelist_type("TfLiteTensor")
+            .whitelist_type("TfLiteRegistration")
             .whitelist_type("FrontendState")
             .whitelist_type("FrontendConfig")
             .whitelist_type("FrontendOutput")
@@ -441,6 +501,7 @@ fn bindgen_tflite_types() {
     }
 }
 
+#[cfg(feature = "tensorflow")]
 fn build_inline_cpp() {
     let submodules = submodules();
 
@@ -458,9 +519,15 @@ fn build_inline_cpp() {
     println!("Building inline cpp took {:?}", start.elapsed());
 }
 
+#[cfg(feature = "tensorflow")]
 fn main() {
     check_submodules_or_checkout_from_git();
     bindgen_tflite_types();
     build_inline_cpp();
     cc_tensorflow_library();
 }
+
+#[cfg(not(feature = "tensorflow"))]
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+}
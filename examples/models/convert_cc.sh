@@ -0,0 +1,50 @@
+#!/bin/sh
+# Regenerate the example models and data from the sources in the
+# TensorFlow submodule. Run from the root of the repository
+set -e
+
+cargo build --features std --bin c_array
+C_ARRAY=target/debug/c_array
+
+MICRO=submodules/tensorflow/tensorflow/lite/micro
+PERSON=$MICRO/tools/make/downloads/person_model_grayscale
+OUT=examples/models
+
+# hello_world
+$C_ARRAY to-bin $MICRO/examples/hello_world/model.cc $OUT/hello_world.tflite
+
+# magic_wand
+$C_ARRAY to-bin $MICRO/examples/magic_wand/magic_wand_model_data.cc \
+    $OUT/magic_wand.tflite
+$C_ARRAY to-bin --format f32be \
+    $MICRO/examples/magic_wand/ring_micro_features_data.cc \
+    $OUT/ring_micro_f9643d42_nohash_4.data
+$C_ARRAY to-bin --format f32be \
+    $MICRO/examples/magic_wand/slope_micro_features_data.cc \
+    $OUT/slope_micro_f2e59fea_nohash_1.data
+
+# micro_speech
+$C_ARRAY to-bin $MICRO/examples/micro_speech/micro_features/model.cc \
+    $OUT/micro_speech.tflite
+$C_ARRAY to-bin --format u8 \
+    $MICRO/examples/micro_speech/micro_features/no_micro_features_data.cc \
+    $OUT/no_micro_f9643d42_nohash_4.data
+$C_ARRAY to-bin --format u8 \
+    $MICRO/examples/micro_speech/micro_features/yes_micro_features_data.cc \
+    $OUT/yes_micro_f2e59fea_nohash_1.data
+
+# micro_speech audio samples
+$C_ARRAY to-bin --format i16le \
+    $MICRO/examples/micro_speech/yes_1000ms_sample_data.cc \
+    $OUT/yes_1000ms_sample.data
+$C_ARRAY to-bin --format i16le \
+    $MICRO/examples/micro_speech/no_1000ms_sample_data.cc \
+    $OUT/no_1000ms_sample.data
+
+# person_detection, grayscale
+$C_ARRAY to-bin $PERSON/person_detect_model_data.cc \
+    $OUT/person_detection_grayscale.tflite
+$C_ARRAY to-bin $PERSON/person_image_data.cc \
+    $OUT/person_image_data_grayscale.data
+$C_ARRAY to-bin $PERSON/no_person_image_data.cc \
+    $OUT/no_person_image_data_grayscale.data
@@ -14,7 +14,7 @@ use cortex_m_rt::entry;
 use stm32f0xx_hal::{prelude::*, stm32};
 
 extern crate tfmicro;
-use tfmicro::{MicroInterpreter, Model, MutableOpResolver};
+use tfmicro::{include_model, MicroInterpreter, Model, MutableOpResolver};
 
 #[entry]
 fn main() -> ! {
@@ -30,13 +30,13 @@ fn main() -> ! {
         gpioa.pa1.into_push_pull_output(cs)
     });
 
-    let model = include_bytes!("../../models/micro_speech.tflite");
+    let model = include_model!("../../models/micro_speech.tflite");
     let no = include_bytes!("../../models/no_micro_f9643d42_nohash_4.data");
     let yes = include_bytes!("../../models/yes_micro_f2e59fea_nohash_1.data");
 
     // Map the model into a usable data structure. This doesn't involve
     // any copying or parsing, it's a very lightweight operation.
-    let model = Model::from_buffer(&model[..]).unwrap();
+    let model = Model::from_buffer(model).unwrap();
 
     // Create an area of memory to use for input, output, and
     // intermediate arrays.
@@ -44,15 +44,18 @@ fn main() -> ! {
     let mut tensor_arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
 
     // Pull in all needed operation implementations
-    let micro_op_resolver = MutableOpResolver::empty()
+    let micro_op_resolver = MutableOpResolver::<3>::empty()
         .depthwise_conv_2d()
         .fully_connected()
         .softmax();
 
     // Build an interpreter to run the model with
-    let mut interpreter =
-        MicroInterpreter::new(&model, micro_op_resolver, &mut tensor_arena[..])
-            .unwrap();
+    let mut interpreter = MicroInterpreter::new(
+        &model,
+        &micro_op_resolver,
+        &mut tensor_arena[..],
+    )
+    .unwrap();
 
     // Check properties of the input sensor
     assert_eq!([1, 49, 40, 1], interpreter.input_info(0).dims);
@@ -0,0 +1,19 @@
+//! Parse and verify arbitrary bytes as a model container
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use tfmicro::container::Container;
+
+/// Public key of the Ed25519 secret key `[7; 32]`
+const KEY: [u8; 32] = [
+    0xea, 0x4a, 0x6c, 0x63, 0xe2, 0x9c, 0x52, 0x0a, 0xbe, 0xf5, 0x50, 0x7b,
+    0x13, 0x2e, 0xc5, 0xf9, 0x95, 0x47, 0x76, 0xae, 0xbe, 0xbe, 0x7b, 0x92,
+    0x42, 0x1e, 0xea, 0x69, 0x14, 0x46, 0xd2, 0x2c,
+];
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(container) = Container::parse(data) {
+        let _ = container.check();
+        let _ = container.verify(&KEY);
+    }
+});
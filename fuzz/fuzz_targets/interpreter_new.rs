@@ -0,0 +1,27 @@
+//! Build an interpreter with every operator for mutated models, and run it
+//! if the model is accepted
+//!
+//! The input is copied into an aligned buffer first, so that it reaches the
+//! verifier and the interpreter rather than being rejected as misaligned
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use tfmicro::{AllOpResolver, MicroInterpreter, OwnedModel};
+
+const TENSOR_ARENA_SIZE: usize = 128 * 1024;
+
+fuzz_target!(|data: &[u8]| {
+    let model = match OwnedModel::from_bytes(data) {
+        Ok(model) => model,
+        Err(_) => return,
+    };
+
+    let resolver = AllOpResolver::new();
+    let mut arena = vec![0u8; TENSOR_ARENA_SIZE];
+
+    if let Ok(mut interpreter) =
+        MicroInterpreter::new(&model, &resolver, &mut arena[..])
+    {
+        let _ = interpreter.invoke();
+    }
+});
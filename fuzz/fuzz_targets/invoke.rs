@@ -0,0 +1,51 @@
+//! Run the example models with arbitrary input data
+//!
+//! The first byte selects the model, and the rest fill each input tensor,
+//! repeated as needed. Every model must run for any input.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use tfmicro::{include_model, AllOpResolver, MicroInterpreter, Model};
+
+/// Models, and the tensor arena size they are tested with
+static MODELS: [(&[u8], usize); 3] = [
+    (
+        include_model!("../../examples/models/hello_world.tflite"),
+        4 * 1024,
+    ),
+    (
+        include_model!("../../examples/models/micro_speech.tflite"),
+        10 * 1024,
+    ),
+    (
+        include_model!("../../examples/models/magic_wand.tflite"),
+        60 * 1024,
+    ),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let (&selector, input) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let (model, arena_size) = MODELS[selector as usize % MODELS.len()];
+
+    let model = Model::from_buffer(model).unwrap();
+    let resolver = AllOpResolver::new();
+    let mut arena = vec![0u8; arena_size];
+    let mut interpreter =
+        MicroInterpreter::new(model, &resolver, &mut arena[..]).unwrap();
+
+    for n in 0..interpreter.inputs_len() {
+        let tensor = interpreter.input_tensor(n).as_bytes_mut();
+        for (byte, &value) in tensor.iter_mut().zip(input.iter().cycle()) {
+            *byte = value;
+        }
+    }
+
+    interpreter.invoke().unwrap();
+
+    for n in 0..interpreter.outputs_len() {
+        interpreter.output(n).as_bytes();
+    }
+});
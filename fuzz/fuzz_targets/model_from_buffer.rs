@@ -0,0 +1,39 @@
+//! Verify arbitrary bytes as a model, with both the TensorFlow verifier and
+//! the pure Rust schema reader
+//!
+//! The input is copied into an aligned buffer first, as TensorFlow reads
+//! the model in place. Otherwise most inputs would be rejected as
+//! misaligned before reaching the verifier
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use tfmicro::{schema, OwnedModel};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(model) = OwnedModel::from_bytes(data) {
+        model.version();
+        model.operators_len();
+        model.tensors_len();
+        model.operator_codes().count();
+    }
+
+    if let Ok(model) = schema::Model::from_buffer(data) {
+        for subgraph in model.subgraphs() {
+            for tensor in subgraph.tensors() {
+                tensor.name();
+                tensor.elements();
+                if let Some(quantization) = tensor.quantization() {
+                    quantization.scale().iter().count();
+                    quantization.zero_point().iter().count();
+                }
+            }
+            for operator in subgraph.operators() {
+                operator.inputs().iter().count();
+                operator.custom_options();
+            }
+        }
+        for metadata in model.metadata() {
+            model.metadata_buffer(metadata.name());
+        }
+    }
+});
@@ -0,0 +1,167 @@
+//! Convert between C array sources and binary files
+//!
+//! ```text
+//! c_array to-bin [--format FORMAT] INPUT OUTPUT
+//! c_array to-c [--name NAME] INPUT OUTPUT
+//! c_array to-rust [--name NAME] INPUT OUTPUT
+//! ```
+//!
+//! `to-bin` reads the first array in the C or C++ source `INPUT`, such as
+//! the models and test data in the TensorFlow Micro examples, and writes
+//! its values to `OUTPUT`. The format of the values follows the C type of
+//! the array, unless `--format` is one of `u8`, `i8`, `i16le`, `i32le`,
+//! `f32le` or `f32be`.
+//!
+//! `to-c` and `to-rust` embed the binary file `INPUT` in a C++ array or a
+//! Rust `static`, aligned to 16 bytes. The default `NAME` is derived from
+//! the name of `INPUT`.
+
+use std::convert::TryFrom;
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::process;
+
+use tfmicro::c_array::{self, Format};
+
+const USAGE: &str = "usage: c_array to-bin [--format FORMAT] INPUT OUTPUT
+       c_array to-c [--name NAME] INPUT OUTPUT
+       c_array to-rust [--name NAME] INPUT OUTPUT";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    ToBin,
+    ToC,
+    ToRust,
+}
+
+struct Args {
+    command: Command,
+    format: Option<Format>,
+    name: Option<String>,
+    input: String,
+    output: String,
+}
+
+fn parse_format(format: &str) -> Result<Format, Box<dyn Error>> {
+    match format {
+        "u8" => Ok(Format::U8),
+        "i8" => Ok(Format::I8),
+        "i16le" => Ok(Format::I16Le),
+        "i32le" => Ok(Format::I32Le),
+        "f32le" => Ok(Format::F32Le),
+        "f32be" => Ok(Format::F32Be),
+        _ => Err(format!("unknown format `{}`", format).into()),
+    }
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+
+    let command = match args.next().as_deref() {
+        Some("to-bin") => Command::ToBin,
+        Some("to-c") => Command::ToC,
+        Some("to-rust") => Command::ToRust,
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        _ => return Err(USAGE.into()),
+    };
+
+    let mut format = None;
+    let mut name = None;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" if command == Command::ToBin => {
+                format = Some(parse_format(&args.next().ok_or(USAGE)?)?);
+            }
+            "--name" if command != Command::ToBin => {
+                name = Some(args.next().ok_or(USAGE)?);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    match <[String; 2]>::try_from(paths) {
+        Ok([input, output]) => Ok(Args {
+            command,
+            format,
+            name,
+            input,
+            output,
+        }),
+        Err(_) => Err(USAGE.into()),
+    }
+}
+
+/// Derive an identifier from the file name of `path`
+fn default_name(path: &Path, command: Command) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let stem: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    match command {
+        Command::ToRust => stem.to_ascii_uppercase(),
+        _ => format!("g_{}", stem),
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = parse_args()?;
+    let input = Path::new(&args.input);
+
+    if args.command == Command::ToBin {
+        let array = c_array::parse_c_array(&fs::read_to_string(input)?)?;
+        let bytes = match args.format {
+            Some(format) => format.encode_all(&array.values)?,
+            None => array.to_bytes()?,
+        };
+        fs::write(&args.output, &bytes)?;
+
+        eprintln!(
+            "Wrote {} values of `{}` as {} bytes",
+            array.values.len(),
+            array.name,
+            bytes.len()
+        );
+        return Ok(());
+    }
+
+    let name = args
+        .name
+        .clone()
+        .unwrap_or_else(|| default_name(input, args.command));
+    if !c_array::is_identifier(&name) {
+        return Err(format!("`{}` is not a valid identifier", name).into());
+    }
+
+    let bytes = fs::read(input)?;
+    let writer = BufWriter::new(File::create(&args.output)?);
+    match args.command {
+        Command::ToC => c_array::write_c_array(writer, &name, &bytes)?,
+        _ => c_array::write_rust_static(writer, &name, &bytes)?,
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("c_array: {}", e);
+        process::exit(1);
+    }
+}
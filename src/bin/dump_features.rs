@@ -0,0 +1,218 @@
+//! Dump the output of the audio frontend as CSV or `.npy`, for comparison
+//! against the Python training pipeline
+//!
+//! ```text
+//! dump_features [--scaled] [--sample-rate HZ] [FRONTEND OPTIONS] INPUT OUTPUT
+//! ```
+//!
+//! `INPUT` is a 16-bit PCM `.wav` file, or a raw little-endian PCM file
+//! sampled at `--sample-rate` (default 16000). Audio is resampled to the
+//! sample rate of the frontend.
+//!
+//! The frontend uses the configuration of the micro_speech example, unless
+//! it is changed with these options, which set the `FrontendConfig` field
+//! of the same name:
+//!
+//! ```text
+//! --frontend-sample-rate HZ     --window-size-ms MS
+//! --window-step-size-ms MS      --num-channels N
+//! --lower-band-limit HZ         --upper-band-limit HZ
+//! --output-scale-shift N        --smoothing-bits N
+//! --even-smoothing X            --odd-smoothing X
+//! --min-signal-remaining X      --no-pcan
+//! --pcan-strength X             --pcan-offset X
+//! --pcan-gain-bits N            --no-log
+//! --log-scale-shift N
+//! ```
+//!
+//! `OUTPUT` is written as `.npy` if it has that extension, and CSV
+//! otherwise. Each row contains the features of one window. By default the
+//! raw `generate_micro_features` output is written; with `--scaled` the
+//! features are scaled to `u8` as for the model input.
+
+use std::convert::TryFrom;
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+
+use tfmicro::wav::Wav;
+use tfmicro::{csv, npy, FeatureGenerator, Frontend, FrontendConfig};
+
+/// Sample rate of the default frontend configuration
+const SAMPLE_RATE: u32 = 16_000;
+
+const USAGE: &str = "usage: dump_features [--scaled] [--sample-rate HZ] \
+                     [FRONTEND OPTIONS] INPUT OUTPUT
+
+Frontend options, which set the FrontendConfig field of the same name:
+    --frontend-sample-rate HZ     --window-size-ms MS
+    --window-step-size-ms MS      --num-channels N
+    --lower-band-limit HZ         --upper-band-limit HZ
+    --output-scale-shift N        --smoothing-bits N
+    --even-smoothing X            --odd-smoothing X
+    --min-signal-remaining X      --no-pcan
+    --pcan-strength X             --pcan-offset X
+    --pcan-gain-bits N            --no-log
+    --log-scale-shift N";
+
+struct Args {
+    scaled: bool,
+    sample_rate: u32,
+    config: FrontendConfig,
+    frontend_sample_rate: u32,
+    input: String,
+    output: String,
+}
+
+/// Parse the value of an option from the next argument
+fn value<T>(
+    args: &mut impl Iterator<Item = String>,
+) -> Result<T, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Error + 'static,
+{
+    Ok(args.next().ok_or(USAGE)?.parse()?)
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut scaled = false;
+    let mut sample_rate = SAMPLE_RATE;
+    let mut config = FrontendConfig::new();
+    let mut frontend_sample_rate = SAMPLE_RATE;
+    let mut paths = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let a = &mut args;
+        config = match arg.as_str() {
+            "--scaled" => {
+                scaled = true;
+                config
+            }
+            "--sample-rate" => {
+                sample_rate = value(a)?;
+                config
+            }
+            "--frontend-sample-rate" => {
+                frontend_sample_rate = value(a)?;
+                config
+            }
+            "--window-size-ms" => config.window_size_ms(value(a)?),
+            "--window-step-size-ms" => config.window_step_size_ms(value(a)?),
+            "--num-channels" => config.num_channels(value(a)?),
+            "--lower-band-limit" => config.lower_band_limit(value(a)?),
+            "--upper-band-limit" => config.upper_band_limit(value(a)?),
+            "--output-scale-shift" => config.output_scale_shift(value(a)?),
+            "--smoothing-bits" => config.smoothing_bits(value(a)?),
+            "--even-smoothing" => config.even_smoothing(value(a)?),
+            "--odd-smoothing" => config.odd_smoothing(value(a)?),
+            "--min-signal-remaining" => config.min_signal_remaining(value(a)?),
+            "--no-pcan" => config.enable_pcan(false),
+            "--pcan-strength" => config.pcan_strength(value(a)?),
+            "--pcan-offset" => config.pcan_offset(value(a)?),
+            "--pcan-gain-bits" => config.pcan_gain_bits(value(a)?),
+            "--no-log" => config.enable_log(false),
+            "--log-scale-shift" => config.log_scale_shift(value(a)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("--") => {
+                return Err(format!("unknown option {}\n{}", arg, USAGE).into())
+            }
+            _ => {
+                paths.push(arg);
+                config
+            }
+        };
+    }
+
+    match <[String; 2]>::try_from(paths) {
+        Ok([input, output]) => Ok(Args {
+            scaled,
+            sample_rate,
+            config: config.sample_rate(frontend_sample_rate),
+            frontend_sample_rate,
+            input,
+            output,
+        }),
+        Err(_) => Err(USAGE.into()),
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = parse_args()?;
+
+    let input = Path::new(&args.input);
+    let audio = if input.extension().and_then(|e| e.to_str()) == Some("wav") {
+        Wav::open(input)?
+    } else {
+        Wav::from_raw_pcm(&fs::read(input)?, args.sample_rate)
+    };
+    let samples = audio.resample(args.frontend_sample_rate);
+
+    let frontend_error = |e| format!("frontend error: {:?}", e);
+    let mut frontend =
+        Frontend::with_config(&args.config).map_err(frontend_error)?;
+    let channels = frontend.num_channels();
+
+    // Run every window through the frontend
+    let mut raw: Vec<u16> = Vec::new();
+    let mut scaled: Vec<u8> = Vec::new();
+    let mut remaining = &samples[..];
+    while !remaining.is_empty() {
+        let num_samples_read = if args.scaled {
+            let mut slice = vec![0; channels];
+            let (n, complete) = frontend.next_slice(remaining, &mut slice);
+            if complete {
+                scaled.extend(slice);
+            }
+            n
+        } else {
+            let (n, output) = frontend.process_samples(remaining);
+            if let Some(output) = output {
+                raw.extend(output);
+            }
+            n
+        };
+
+        if num_samples_read == 0 {
+            break;
+        }
+        remaining = &remaining[num_samples_read..];
+    }
+
+    let output = Path::new(&args.output);
+    let writer = BufWriter::new(File::create(output)?);
+    let npy = output.extension().and_then(|e| e.to_str()) == Some("npy");
+
+    match (npy, args.scaled) {
+        (true, true) => npy::write_npy(
+            writer,
+            &[scaled.len() / channels, channels],
+            &scaled,
+        ),
+        (true, false) => {
+            npy::write_npy(writer, &[raw.len() / channels, channels], &raw)
+        }
+        (false, true) => csv::write_csv(writer, channels, &scaled),
+        (false, false) => csv::write_csv(writer, channels, &raw),
+    }?;
+
+    let windows = (raw.len() + scaled.len()) / channels;
+    eprintln!("Wrote {} windows of {} features", windows, channels);
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("dump_features: {}", e);
+        process::exit(1);
+    }
+}
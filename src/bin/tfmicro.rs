@@ -0,0 +1,634 @@
+//! Run a TensorFlow Lite model from the command line
+//!
+//! ```text
+//! tfmicro info MODEL
+//! tfmicro run [--arena-size BYTES] [--real] [-i INPUT]... [-o OUTPUT]... MODEL
+//! tfmicro eval [--arena-size BYTES] [--real] [--top-k K] [--classes FILE]
+//!              [--header] MODEL DIR LABELS
+//! ```
+//!
+//! `info` prints a summary of the model, the operators it requires, its
+//! input and output tensors and the size of tensor arena it needs.
+//!
+//! `run` fills the input tensors from the `INPUT` files in order, invokes
+//! the model and writes the output tensors to the `OUTPUT` files in order.
+//! Output tensors without a file are printed. Files are read and written
+//! as `.npy` or CSV if they have that extension, and as the raw bytes of
+//! the tensor otherwise. Input values are converted to the element type of
+//! the tensor. With `--real`, input values are quantized, and output values
+//! dequantized, using the quantization parameters of the tensor.
+//!
+//! `eval` measures the accuracy of a classification model. Each line of the
+//! CSV file `LABELS` is `PATH,LABEL`, where `PATH` is an input file
+//! relative to `DIR`. Labels are class indices, or names listed one per
+//! line in the `--classes` file in the order of the model's output. With
+//! `--header`, the first line of `LABELS` is a header and is skipped. Every
+//! input is run through the same interpreter, and the top-1 and top-`K`
+//! (default 3) accuracy, confusion matrix, precision and recall are
+//! printed.
+//!
+//! Unless `--arena-size` is given, the smallest tensor arena that the
+//! model can be allocated in is found automatically, by bisection. Errors
+//! reported by TensorFlow are printed to stderr, except while the arena
+//! size is being found.
+
+use std::env;
+use std::error::Error;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::path::Path;
+use std::process;
+
+use log::LevelFilter;
+
+use tfmicro::eval::{self, Evaluation};
+use tfmicro::{
+    csv, npy, AllOpResolver, ElementType, MicroInterpreter, Model,
+    OwnedInterpreter, OwnedModel, Tensor,
+};
+
+const USAGE: &str = "usage: tfmicro info MODEL
+       tfmicro run [--arena-size BYTES] [--real] [-i INPUT]... [-o OUTPUT]... MODEL
+       tfmicro eval [--arena-size BYTES] [--real] [--top-k K] [--classes FILE]
+           [--header] MODEL DIR LABELS";
+
+/// Smallest tensor arena that is tried
+const MIN_ARENA_SIZE: usize = 1024;
+
+/// Largest tensor arena that is tried
+const MAX_ARENA_SIZE: usize = 256 * 1024 * 1024;
+
+/// Alignment of the tensor arena used by TensorFlow
+const ARENA_ALIGNMENT: usize = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Info,
+    Run,
+    Eval,
+}
+
+struct Args {
+    command: Command,
+    model: String,
+    arena_size: Option<usize>,
+    real: bool,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    top_k: usize,
+    classes: Option<String>,
+    header: bool,
+    /// Paths following the model
+    paths: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+
+    let command = match args.next().as_deref() {
+        Some("info") => Command::Info,
+        Some("run") => Command::Run,
+        Some("eval") => Command::Eval,
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        _ => return Err(USAGE.into()),
+    };
+
+    let mut arena_size = None;
+    let mut real = false;
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    let mut top_k = 3;
+    let mut classes = None;
+    let mut header = false;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--arena-size" => {
+                arena_size = Some(args.next().ok_or(USAGE)?.parse()?);
+            }
+            "--real" => real = true,
+            "-i" | "--input" => inputs.push(args.next().ok_or(USAGE)?),
+            "-o" | "--output" => outputs.push(args.next().ok_or(USAGE)?),
+            "--top-k" => top_k = args.next().ok_or(USAGE)?.parse()?,
+            "--classes" => classes = Some(args.next().ok_or(USAGE)?),
+            "--header" => header = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let expected = match command {
+        Command::Eval => 3,
+        _ => 1,
+    };
+    if paths.len() != expected || top_k == 0 {
+        return Err(USAGE.into());
+    }
+    let model = paths.remove(0);
+
+    Ok(Args {
+        command,
+        model,
+        arena_size,
+        real,
+        inputs,
+        outputs,
+        top_k,
+        classes,
+        header,
+        paths,
+    })
+}
+
+/// Read the model in `path`
+fn read_model(path: &str) -> Result<OwnedModel, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+
+    OwnedModel::from_bytes(&bytes)
+        .map_err(|e| format!("invalid model: {:?}", e).into())
+}
+
+/// Create an interpreter for the model given in `args`
+fn load(args: &Args) -> Result<OwnedInterpreter, Box<dyn Error>> {
+    let model = read_model(&args.model)?;
+
+    let resolver = AllOpResolver::new();
+    let arena_size = match args.arena_size {
+        Some(arena_size) => arena_size,
+        None => find_arena_size(&model, &resolver)?,
+    };
+
+    OwnedInterpreter::new(model, resolver, arena_size)
+        .map_err(|e| format!("cannot create interpreter: {:?}", e).into())
+}
+
+/// Returns a tensor arena size that `model` can be allocated in
+///
+/// This is the smallest size for which creating the interpreter succeeds,
+/// rather than the bytes it reports as used, as allocation needs more room
+/// than is used in the end, for example whilst planning the tensors.
+///
+/// Error reports from TensorFlow are silenced while probing, as most
+/// attempts are expected to fail.
+fn find_arena_size(
+    model: &Model,
+    resolver: &AllOpResolver,
+) -> Result<usize, Box<dyn Error>> {
+    log::set_max_level(LevelFilter::Off);
+    let size = smallest_arena(|size| {
+        let mut arena = vec![0; size];
+        MicroInterpreter::new(model, resolver, &mut arena[..]).map(|_| ())
+    });
+    log::set_max_level(LevelFilter::Info);
+
+    let size =
+        size.map_err(|e| format!("cannot create interpreter: {:?}", e))?;
+
+    // Leave room to align the start of the arena, as a `Vec` is not
+    // aligned to 16 bytes
+    Ok(size + ARENA_ALIGNMENT)
+}
+
+/// Returns the smallest tensor arena size for which `allocate` succeeds
+///
+/// The arena is doubled until allocation succeeds, and then bisected
+/// between the largest size that failed and the smallest that succeeded.
+/// Every size tried is a multiple of the arena alignment.
+fn smallest_arena<F>(mut allocate: F) -> Result<usize, tfmicro::Error>
+where
+    F: FnMut(usize) -> Result<(), tfmicro::Error>,
+{
+    let mut failed = 0;
+    let mut size = MIN_ARENA_SIZE;
+    loop {
+        match allocate(size) {
+            Ok(()) => break,
+            Err(_) if size < MAX_ARENA_SIZE => {
+                failed = size;
+                size *= 2;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    // To the alignment of the arena, which is the granularity of
+    // TensorFlow's allocations
+    while size - failed > ARENA_ALIGNMENT {
+        let mid = ((failed + size) / 2) & !(ARENA_ALIGNMENT - 1);
+        match allocate(mid) {
+            Ok(()) => size = mid,
+            Err(_) => failed = mid,
+        }
+    }
+
+    Ok(size)
+}
+
+fn print_tensor_info(kind: &str, n: usize, tensor: &Tensor) {
+    let info = tensor.info();
+
+    print!(
+        "  {} {}: {} {:?} {:?}",
+        kind, n, info.name, info.element_type, info.dims
+    );
+    if let Some(q) = info.quantization {
+        print!(" scale={} zero_point={}", q.scale, q.zero_point);
+    }
+    println!();
+}
+
+fn info(args: &Args) -> Result<(), Box<dyn Error>> {
+    let model = read_model(&args.model)?;
+
+    println!("Model: {}", args.model);
+    println!("  schema version: {}", model.version());
+    println!("  subgraphs: {}", model.subgraphs_len());
+    println!("  operators: {}", model.operators_len());
+    println!("  tensors: {}", model.tensors_len());
+
+    println!("Required operators:");
+    for op in model.operator_codes() {
+        let op = op.map_err(|e| format!("invalid operator: {:?}", e))?;
+        let kind = if op.custom { " (custom)" } else { "" };
+        println!("  {} v{}{}", op.name, op.version, kind);
+    }
+
+    let resolver = AllOpResolver::new();
+    let mut findings = model.validate(&resolver).peekable();
+    if findings.peek().is_some() {
+        println!("Problems:");
+        for finding in findings {
+            println!("  {}", finding);
+        }
+    }
+
+    let arena_size = match args.arena_size {
+        Some(arena_size) => arena_size,
+        None => find_arena_size(&model, &resolver)?,
+    };
+    let mut arena = vec![0; arena_size];
+    let mut interpreter =
+        MicroInterpreter::new(&model, &resolver, &mut arena[..])
+            .map_err(|e| format!("cannot create interpreter: {:?}", e))?;
+
+    println!("Tensors:");
+    for n in 0..interpreter.inputs_len() {
+        print_tensor_info("input", n, interpreter.input_tensor(n));
+    }
+    for n in 0..interpreter.outputs_len() {
+        print_tensor_info("output", n, interpreter.output(n));
+    }
+
+    // The bytes used once the tensors are allocated are fewer than the
+    // arena size needed to allocate them
+    println!("Tensor arena:");
+    println!("  size: {} bytes", arena_size);
+    println!("  used: {} bytes", interpreter.arena_used_bytes());
+
+    Ok(())
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|e| e.to_str())
+}
+
+/// Read the values in `path` into `tensor`
+fn read_input(
+    path: &Path,
+    tensor: &mut Tensor,
+    real: bool,
+) -> Result<(), Box<dyn Error>> {
+    let values = match extension(path) {
+        Some("npy") => npy::read_npy(BufReader::new(File::open(path)?))?.data,
+        Some("csv") => csv::read_csv(BufReader::new(File::open(path)?))?,
+        _ => {
+            let bytes = fs::read(path)?;
+            let data = tensor.as_bytes_mut();
+            if bytes.len() != data.len() {
+                return Err(format!(
+                    "{}: {} bytes, but the tensor has {} bytes",
+                    path.display(),
+                    bytes.len(),
+                    data.len()
+                )
+                .into());
+            }
+            data.copy_from_slice(&bytes);
+            return Ok(());
+        }
+    };
+
+    let (element_type, len, quantization) = {
+        let info = tensor.info();
+        let len = info.dims.iter().product::<i32>() as usize;
+        (info.element_type, len, info.quantization)
+    };
+    if values.len() != len {
+        return Err(format!(
+            "{}: {} values, but the tensor has {} elements",
+            path.display(),
+            values.len(),
+            len
+        )
+        .into());
+    }
+
+    // Quantize real values
+    let values: Vec<f64> = match quantization {
+        Some(q) if real && element_type != ElementType::Float32 => values
+            .iter()
+            .map(|x| (x / q.scale as f64).round() + q.zero_point as f64)
+            .collect(),
+        _ => values,
+    };
+
+    let out_of_range = |x: &f64, min: f64, max: f64| {
+        x.round() < min || x.round() > max || x.is_nan()
+    };
+    let range_error = || {
+        format!(
+            "{}: values out of range for {:?} tensor",
+            path.display(),
+            element_type
+        )
+    };
+
+    match element_type {
+        ElementType::Float32 => {
+            for (t, x) in tensor.as_data_mut::<f32>().iter_mut().zip(values) {
+                *t = x as f32;
+            }
+        }
+        ElementType::UInt8 => {
+            if values.iter().any(|x| out_of_range(x, 0., 255.)) {
+                return Err(range_error().into());
+            }
+            for (t, x) in tensor.as_data_mut::<u8>().iter_mut().zip(values) {
+                *t = x.round() as u8;
+            }
+        }
+        ElementType::Int8 => {
+            if values.iter().any(|x| out_of_range(x, -128., 127.)) {
+                return Err(range_error().into());
+            }
+            for (t, x) in tensor.as_data_mut::<i8>().iter_mut().zip(values) {
+                *t = x.round() as i8;
+            }
+        }
+        ElementType::Int32 => {
+            let (min, max) = (i32::MIN as f64, i32::MAX as f64);
+            if values.iter().any(|x| out_of_range(x, min, max)) {
+                return Err(range_error().into());
+            }
+            for (t, x) in tensor.as_data_mut::<i32>().iter_mut().zip(values) {
+                *t = x.round() as i32;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write `data` with dimensions `shape` to `path`, or print it
+fn write_values<T: npy::NpyElement + Display>(
+    path: Option<&Path>,
+    shape: &[usize],
+    data: &[T],
+) -> Result<(), Box<dyn Error>> {
+    let columns = shape.last().copied().unwrap_or(1).max(1);
+
+    match path {
+        Some(path) => {
+            let writer = BufWriter::new(File::create(path)?);
+            match extension(path) {
+                Some("npy") => npy::write_npy(writer, shape, data)?,
+                _ => csv::write_csv(writer, columns, data)?,
+            }
+        }
+        None => csv::write_csv(std::io::stdout(), columns, data)?,
+    }
+
+    Ok(())
+}
+
+/// Write `tensor` to `path`, or print it
+fn write_output(
+    path: Option<&Path>,
+    tensor: &Tensor,
+    real: bool,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = path {
+        if !matches!(extension(path), Some("npy") | Some("csv")) {
+            fs::write(path, tensor.as_bytes())?;
+            return Ok(());
+        }
+    }
+
+    let info = tensor.info();
+    let shape: Vec<usize> = info.dims.iter().map(|&d| d as usize).collect();
+
+    // Dequantize to real values
+    if let Some(q) = info.quantization.filter(|_| real) {
+        let dequantize = |x: i32| q.scale * (x - q.zero_point) as f32;
+        let data: Vec<f32> = match info.element_type {
+            ElementType::Float32 => tensor.as_data::<f32>().to_vec(),
+            ElementType::UInt8 => tensor
+                .as_data::<u8>()
+                .iter()
+                .map(|&x| dequantize(x as i32))
+                .collect(),
+            ElementType::Int8 => tensor
+                .as_data::<i8>()
+                .iter()
+                .map(|&x| dequantize(x as i32))
+                .collect(),
+            ElementType::Int32 => tensor
+                .as_data::<i32>()
+                .iter()
+                .map(|&x| dequantize(x))
+                .collect(),
+        };
+        return write_values(path, &shape, &data);
+    }
+
+    match info.element_type {
+        ElementType::Float32 => {
+            write_values(path, &shape, tensor.as_data::<f32>())
+        }
+        ElementType::UInt8 => {
+            write_values(path, &shape, tensor.as_data::<u8>())
+        }
+        ElementType::Int8 => write_values(path, &shape, tensor.as_data::<i8>()),
+        ElementType::Int32 => {
+            write_values(path, &shape, tensor.as_data::<i32>())
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let mut interpreter = load(args)?;
+
+    if args.inputs.len() != interpreter.inputs_len() {
+        return Err(format!(
+            "the model has {} inputs, but {} were given",
+            interpreter.inputs_len(),
+            args.inputs.len()
+        )
+        .into());
+    }
+    if args.outputs.len() > interpreter.outputs_len() {
+        return Err(format!(
+            "the model has {} outputs, but {} were given",
+            interpreter.outputs_len(),
+            args.outputs.len()
+        )
+        .into());
+    }
+
+    for (n, input) in args.inputs.iter().enumerate() {
+        read_input(Path::new(input), interpreter.input_tensor(n), args.real)?;
+    }
+
+    interpreter
+        .invoke()
+        .map_err(|e| format!("invoke failed: {:?}", e))?;
+
+    for n in 0..interpreter.outputs_len() {
+        let tensor = interpreter.output(n);
+        match args.outputs.get(n) {
+            Some(output) => {
+                write_output(Some(Path::new(output)), tensor, args.real)?
+            }
+            None => {
+                println!("output {}: {}", n, tensor.info().name);
+                write_output(None, tensor, args.real)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the values of `tensor` as `f64`
+fn scores(tensor: &Tensor) -> Vec<f64> {
+    match tensor.element_type() {
+        Some(ElementType::Float32) => {
+            tensor.as_data::<f32>().iter().map(|&x| x as f64).collect()
+        }
+        Some(ElementType::UInt8) => {
+            tensor.as_data::<u8>().iter().map(|&x| x as f64).collect()
+        }
+        Some(ElementType::Int8) => {
+            tensor.as_data::<i8>().iter().map(|&x| x as f64).collect()
+        }
+        Some(ElementType::Int32) => {
+            tensor.as_data::<i32>().iter().map(|&x| x as f64).collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+fn evaluate(args: &Args) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(&args.paths[0]);
+    let names = match &args.classes {
+        Some(classes) => BufReader::new(File::open(classes)?)
+            .lines()
+            .map(|line| line.map(|name| name.trim().to_string()))
+            .filter(|name| !matches!(name, Ok(name) if name.is_empty()))
+            .collect::<io::Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    let samples = eval::read_labels(
+        BufReader::new(File::open(&args.paths[1])?),
+        &names,
+        args.header,
+    )?;
+
+    let mut interpreter = load(args)?;
+
+    if interpreter.inputs_len() != 1 {
+        return Err(format!(
+            "the model has {} inputs, but only 1 is supported",
+            interpreter.inputs_len()
+        )
+        .into());
+    }
+    let classes = scores(interpreter.output(0)).len();
+    if classes == 0 {
+        return Err("the model has no output classes".into());
+    }
+    if !names.is_empty() && names.len() != classes {
+        return Err(format!(
+            "the model has {} classes, but {} names were given",
+            classes,
+            names.len()
+        )
+        .into());
+    }
+
+    let mut evaluation = Evaluation::new(classes, args.top_k.min(classes));
+    for sample in &samples {
+        if sample.class >= classes {
+            return Err(format!(
+                "{}: class {} is out of range",
+                sample.path, sample.class
+            )
+            .into());
+        }
+
+        let path = dir.join(&sample.path);
+        read_input(&path, interpreter.input_tensor(0), args.real)?;
+        interpreter.invoke().map_err(|e| {
+            format!("{}: invoke failed: {:?}", path.display(), e)
+        })?;
+
+        evaluation.record(sample.class, &scores(interpreter.output(0)));
+    }
+
+    evaluation.write_report(io::stdout(), &names)?;
+
+    Ok(())
+}
+
+/// Prints the error reports from TensorFlow, which are logged at the `info`
+/// level
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("tensorflow: {}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn main() {
+    if log::set_logger(&Logger).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+
+    let result = parse_args().and_then(|args| match args.command {
+        Command::Info => info(&args),
+        Command::Run => run(&args),
+        Command::Eval => evaluate(&args),
+    });
+
+    if let Err(e) = result {
+        eprintln!("tfmicro: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,438 @@
+//! Helpers for build scripts
+//!
+//! [`generate_resolver`] reads a `.tflite` model on the host and writes
+//! Rust source for an op resolver that contains only the operators the
+//! model uses, so that the kernels for every other operator can be dropped
+//! by the linker.
+//!
+//! Requires the `std` feature. This module does not need TensorFlow, so
+//! it can be used from a build script without building TensorFlow for the
+//! host as well as the target:
+//!
+//! ```text
+//! [build-dependencies]
+//! tfmicro = { version = "0.1.0", default-features = false, features = ["std"] }
+//! ```
+//!
+//! In `build.rs`:
+//!
+//! ```no_run
+//! use std::env;
+//! use std::path::Path;
+//!
+//! let out_dir = env::var("OUT_DIR").unwrap();
+//! let out = Path::new(&out_dir).join("resolver.rs");
+//!
+//! tfmicro::build::generate_resolver("model.tflite", &out).unwrap();
+//! ```
+//!
+//! Then in the firmware
+//!
+//! ```text
+//! include!(concat!(env!("OUT_DIR"), "/resolver.rs"));
+//!
+//! let resolver = resolver();
+//! let mut arena = [0u8; ARENA_SIZE];
+//! let interpreter = MicroInterpreter::new(&model, &resolver, &mut arena[..])?;
+//! ```
+
+use std::env;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::Path;
+
+mod planner;
+
+use crate::schema::{self, Model};
+use planner::Buffer;
+
+/// Calls `$m!` with the builtin operators as a slice of (code, method)
+/// tuples
+macro_rules! operator_table {
+    ($(($method:ident, $name:ident, $code:literal),)*) => {
+        &[$(($code, stringify!($method)),)*]
+    };
+}
+
+/// Builtin operator codes from the TensorFlow Lite schema, and the
+/// [`MutableOpResolver`](crate::MutableOpResolver) method that adds each of
+/// them
+const OPERATORS: &[(i32, &str)] = builtin_operators!(operator_table);
+
+/// Builtin operator code of `CONV_2D`
+const CONV_2D: i32 = 3;
+
+/// Builtin operator code of `DEPTHWISE_CONV_2D`
+const DEPTHWISE_CONV_2D: i32 = 4;
+
+/// Sizes in bytes of what TensorFlow allocates in the tensor arena besides
+/// the tensor data, for a target with pointers of `ptr` bytes
+///
+/// The structures are from tensorflow/lite/c/common.h, and are allocated
+/// by tensorflow/lite/micro/micro_allocator.cc at the pinned revision.
+struct Overheads {
+    /// `TfLiteTensor`, for every tensor in the model
+    tensor: usize,
+    /// `TfLiteAffineQuantization`, for every quantized tensor. The scales
+    /// are read from the model, but the zero points are copied into a
+    /// `TfLiteIntArray` of `4 + 4 * channels` bytes
+    quantization: usize,
+    /// `NodeAndRegistration`, a `TfLiteNode` and a pointer to its
+    /// `TfLiteRegistration`, for every operator
+    node: usize,
+    /// The builtin options of an operator, the largest of which is
+    /// `TfLiteDepthwiseConvParams` with 7 `int`s
+    builtin_data: usize,
+    /// The `AllocationInfo` kept for every tensor whilst planning
+    allocation_info: usize,
+    /// The `GreedyMemoryPlanner` scratch for every planned buffer, given
+    /// by its `per_buffer_size()`
+    planner_buffer: usize,
+    /// The `RustOpResolver` and `tflite::MicroInterpreter` that
+    /// `MicroInterpreter::new` places at the start of the arena, rounded up
+    /// to 16 bytes
+    arena_header: usize,
+}
+
+impl Overheads {
+    fn new(ptr: usize) -> Self {
+        // Rounds `size` up to a multiple of the pointer size
+        let padded = |size: usize| (size + ptr - 1) & !(ptr - 1);
+
+        Self {
+            // type, data, dims, params, allocation_type, bytes, allocation,
+            // name, delegate, buffer_handle, data_is_stale and is_variable,
+            // quantization, sparsity, dims_signature
+            tensor: padded(4)
+                + 2 * ptr
+                + 8
+                + padded(4)
+                + 3 * ptr
+                + ptr
+                + padded(4 + 1 + 1)
+                + padded(4)
+                + 3 * ptr,
+            // scale, zero_point, quantized_dimension
+            quantization: padded(2 * ptr + 4),
+            // inputs, outputs, intermediates, temporaries, user_data,
+            // builtin_data, custom_initial_data, custom_initial_data_size,
+            // delegate, and the registration
+            node: 7 * ptr + padded(4) + ptr + ptr,
+            builtin_data: 7 * 4,
+            // bytes, output_ptr, first_created, last_used, needs_allocating
+            allocation_info: padded(2 * ptr + 4 + 4 + 1),
+            // BufferRequirements, ListEntry and three int arrays
+            planner_buffer: 3 * 4 + 3 * 4 + 3 * 4,
+            // The resolver's vtable, registrations and len, then the
+            // interpreter's TfLiteContext of 18 pointers, an int and a bool,
+            // its node_and_registrations, model, op resolver, error
+            // reporter, allocator and subgraph, a ContextHelper of 3
+            // pointers, tensors_allocated and initialization_status
+            arena_header: planner::align(
+                3 * ptr
+                    + 18 * ptr
+                    + padded(4)
+                    + padded(1)
+                    + 9 * ptr
+                    + padded(1 + 4),
+            ),
+        }
+    }
+
+    /// Overheads for the target of the build script that is running, or
+    /// otherwise the host
+    fn target() -> Self {
+        let bits = env::var("CARGO_CFG_TARGET_POINTER_WIDTH")
+            .ok()
+            .and_then(|bits| bits.parse::<usize>().ok())
+            .unwrap_or(8 * mem::size_of::<usize>());
+
+        Self::new(bits / 8)
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// The operators and estimated arena size of a model
+struct Summary {
+    /// `MutableOpResolver` methods for the operators used, in the order
+    /// they appear in the model
+    methods: Vec<&'static str>,
+    /// Estimated tensor arena size, in bytes
+    arena_size: usize,
+}
+
+fn summarize(model: &[u8]) -> io::Result<Summary> {
+    let model = Model::from_buffer(model)
+        .map_err(|_| invalid_data("not a valid .tflite model".into()))?;
+
+    // Operators
+    let mut methods = Vec::new();
+    for opcode in model.operator_codes() {
+        if opcode.is_custom() {
+            return Err(invalid_data(format!(
+                "custom operator `{}` is not supported",
+                opcode.name()
+            )));
+        }
+
+        let code = opcode.builtin_code();
+        let (_, method) =
+            OPERATORS.iter().find(|(c, _)| *c == code).ok_or_else(|| {
+                invalid_data(format!(
+                    "builtin operator {} is not supported by tfmicro",
+                    opcode.name()
+                ))
+            })?;
+        if !methods.contains(method) {
+            methods.push(*method);
+        }
+    }
+
+    let subgraphs = model.subgraphs();
+    let subgraph = match subgraphs.get(0) {
+        Some(subgraph) if subgraphs.len() == 1 => subgraph,
+        _ => {
+            return Err(invalid_data(format!(
+                "model has {} subgraphs, but only 1 is supported",
+                subgraphs.len()
+            )))
+        }
+    };
+    let tensors = subgraph.tensors();
+    let operators = subgraph.operators();
+    let last = operators.len().saturating_sub(1);
+
+    // Lifetime of each tensor, as the first and last operators that use it
+    let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; tensors.len()];
+    let mut used = |tensor: i32, op: usize| {
+        // Optional inputs are -1
+        if let Some(lifetime) = lifetimes.get_mut(tensor as usize) {
+            *lifetime = Some(match *lifetime {
+                Some((first, last)) => (first.min(op), last.max(op)),
+                None => (op, op),
+            });
+        }
+    };
+    for tensor in subgraph.inputs() {
+        used(tensor, 0);
+    }
+    for tensor in subgraph.outputs() {
+        used(tensor, last);
+    }
+    for (n, operator) in operators.iter().enumerate() {
+        for tensor in operator.inputs().iter().chain(operator.outputs()) {
+            used(tensor, n);
+        }
+    }
+
+    let overheads = Overheads::target();
+    let mut persistent = overheads.arena_header
+        + tensors.len() * overheads.tensor
+        + operators.len() * (overheads.node + overheads.builtin_data);
+
+    // Tensors without constant data are allocated in the arena. Variable
+    // tensors persist for the whole of every invocation
+    let mut planned = Vec::new();
+    for (tensor, lifetime) in tensors.iter().zip(lifetimes) {
+        if let Some(quantization) = tensor.quantization() {
+            let channels = quantization.zero_point().len();
+            if channels > 0 && !quantization.scale().is_empty() {
+                persistent += overheads.quantization + 4 + 4 * channels;
+            }
+        }
+
+        let buffer = model.buffers().get(tensor.buffer() as usize);
+        if matches!(buffer, Some(buffer) if !buffer.data().is_empty()) {
+            continue;
+        }
+
+        let element_size = tensor
+            .tensor_type()
+            .and_then(schema::TensorType::size)
+            .ok_or_else(|| {
+                invalid_data("tensor has an unsupported element type".into())
+            })?;
+        let elements = tensor.elements().ok_or_else(|| {
+            invalid_data("dynamic shapes are not supported".into())
+        })?;
+        let size = elements * element_size;
+
+        match lifetime {
+            _ if tensor.is_variable() => persistent += planner::align(size),
+            Some((first_used, last_used)) => planned.push(Buffer {
+                size,
+                first_used,
+                last_used,
+            }),
+            None => {}
+        }
+    }
+
+    // Convolutions keep a multiplier and shift for each output channel
+    for operator in operators {
+        let code = model
+            .operator_codes()
+            .get(operator.opcode_index() as usize)
+            .map(|opcode| opcode.builtin_code());
+        if code != Some(CONV_2D) && code != Some(DEPTHWISE_CONV_2D) {
+            continue;
+        }
+
+        let channels = operator
+            .outputs()
+            .get(0)
+            .and_then(|output| tensors.get(output as usize))
+            .and_then(|output| output.shape().iter().last())
+            .unwrap_or(0);
+        persistent += 2 * 4 * channels.max(0) as usize;
+    }
+
+    // The planner's scratch space is only needed whilst planning, and is
+    // then reused for the tensor data
+    let planning = tensors.len() * overheads.allocation_info
+        + planned.len() * overheads.planner_buffer;
+    let arena_size = planner::plan(&planned).max(planning) + persistent;
+
+    Ok(Summary {
+        methods,
+        arena_size: planner::align(arena_size),
+    })
+}
+
+/// Returns Rust source for an op resolver for `model`, as described for
+/// [`generate_resolver`]
+///
+/// # Errors
+///
+/// Returns an `io::Error` of kind `InvalidData` if `model` is not a valid
+/// `.tflite` model, or it uses operators that are not supported.
+pub fn resolver_source(model: &[u8]) -> io::Result<String> {
+    let summary = summarize(model)?;
+
+    let mut source = String::new();
+    source.push_str(
+        "// Generated by tfmicro::build::generate_resolver. Do not edit\n\n",
+    );
+    source.push_str(&format!(
+        "/// Number of operators used by the model\n\
+         pub const OPERATORS_LEN: usize = {};\n\n",
+        summary.methods.len()
+    ));
+    source.push_str(&format!(
+        "/// Estimated tensor arena size for the model, in bytes\n\
+         pub const ARENA_SIZE: usize = {};\n\n",
+        summary.arena_size
+    ));
+    source.push_str(
+        "/// Returns an op resolver containing the operators used by the model\n\
+         pub fn resolver() -> tfmicro::MutableOpResolver<OPERATORS_LEN> {\n\
+         \x20   tfmicro::MutableOpResolver::empty()",
+    );
+    for method in &summary.methods {
+        source.push_str(&format!("\n        .{}()", method));
+    }
+    source.push_str("\n}\n");
+
+    Ok(source)
+}
+
+/// Read the `.tflite` model at `model`, and write Rust source for an op
+/// resolver to `out`
+///
+/// The source contains
+///
+/// * `fn resolver() -> MutableOpResolver<OPERATORS_LEN>`, which returns a
+///   resolver containing only the operators used by the model
+/// * `const ARENA_SIZE: usize`, an estimate of the tensor arena size
+///   needed by the model
+///
+/// The arena size is estimated by planning the model's tensors in the same
+/// way as TensorFlow, plus the structures that TensorFlow allocates for
+/// each tensor and operator on the target. Kernels may allocate more than
+/// expected, for example for scratch buffers. The exact size is reported
+/// by `tfmicro info`, see DEVELOP.md.
+///
+/// Cargo is told to run the build script again if the model changes.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the model cannot be read or `out` cannot be
+/// written. The error kind is `InvalidData` if `model` is not a valid
+/// `.tflite` model, or it uses operators that are not supported.
+pub fn generate_resolver<P: AsRef<Path>, Q: AsRef<Path>>(
+    model: P,
+    out: Q,
+) -> io::Result<()> {
+    let model = model.as_ref();
+    println!("cargo:rerun-if-changed={}", model.display());
+
+    let source = resolver_source(&fs::read(model)?)?;
+    fs::write(out, source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn micro_speech_resolver() {
+        let model = include_bytes!("../../examples/models/micro_speech.tflite");
+        let source = resolver_source(&model[..]).unwrap();
+
+        assert!(source.contains("pub const OPERATORS_LEN: usize = 3;"));
+        assert!(source.contains(
+            "tfmicro::MutableOpResolver::empty()\n        \
+             .depthwise_conv_2d()\n        \
+             .fully_connected()\n        \
+             .softmax()\n}\n"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "tensorflow")]
+    fn arena_size_is_sufficient() {
+        use crate::{include_model, AllOpResolver, MicroInterpreter};
+
+        let models: [&[u8]; 4] = [
+            include_model!("../../examples/models/hello_world.tflite"),
+            include_model!("../../examples/models/micro_speech.tflite"),
+            include_model!("../../examples/models/magic_wand.tflite"),
+            include_model!(
+                "../../examples/models/person_detection_grayscale.tflite"
+            ),
+        ];
+        let resolver = AllOpResolver::new();
+
+        for &model in models.iter() {
+            let arena_size = summarize(model).unwrap().arena_size;
+            assert_eq!(arena_size % 16, 0);
+
+            let model = crate::Model::from_buffer(model).unwrap();
+            let mut arena = vec![0u8; arena_size];
+            let interpreter =
+                MicroInterpreter::new(&model, &resolver, &mut arena[..]);
+
+            assert!(interpreter.is_ok(), "{} bytes", arena_size);
+        }
+    }
+
+    #[test]
+    fn overheads() {
+        // TfLiteTensor and NodeAndRegistration on 32 and 64-bit targets
+        assert_eq!(Overheads::new(4).tensor, 64);
+        assert_eq!(Overheads::new(8).tensor, 112);
+        assert_eq!(Overheads::new(4).node, 40);
+        assert_eq!(Overheads::new(8).node, 80);
+    }
+
+    #[test]
+    fn invalid_model() {
+        let error = resolver_source(b"not a model").unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,116 @@
+//! Estimating the size of tensor arena a model needs
+//!
+//! Follows the approach of the TensorFlow Micro `GreedyMemoryPlanner`.
+//! Buffers are placed in order of decreasing size, each at the lowest
+//! offset that does not overlap a buffer that is in use at the same time.
+
+use std::cmp::Reverse;
+
+/// A buffer that must be allocated in the arena
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Buffer {
+    /// Size in bytes
+    pub size: usize,
+    /// Index of the first operator that uses the buffer
+    pub first_used: usize,
+    /// Index of the last operator that uses the buffer
+    pub last_used: usize,
+}
+
+/// Alignment of each buffer in the arena
+pub(crate) const ALIGNMENT: usize = 16;
+
+pub(crate) fn align(size: usize) -> usize {
+    (size + ALIGNMENT - 1) & !(ALIGNMENT - 1)
+}
+
+/// Returns the number of bytes needed to hold `buffers`
+pub(crate) fn plan(buffers: &[Buffer]) -> usize {
+    let mut order: Vec<&Buffer> = buffers.iter().collect();
+    order.sort_by_key(|buffer| Reverse(buffer.size));
+
+    // (offset, buffer) of each buffer placed so far
+    let mut placed: Vec<(usize, &Buffer)> = Vec::new();
+    let mut total = 0;
+
+    for buffer in order {
+        let size = align(buffer.size);
+
+        // Buffers in use at the same time as this one, by offset
+        let mut overlapping: Vec<(usize, usize)> = placed
+            .iter()
+            .filter(|(_, other)| {
+                other.first_used <= buffer.last_used
+                    && buffer.first_used <= other.last_used
+            })
+            .map(|&(offset, other)| (offset, align(other.size)))
+            .collect();
+        overlapping.sort();
+
+        // Find the first gap that is large enough
+        let mut offset = 0;
+        for (other_offset, other_size) in overlapping {
+            if other_offset >= offset + size {
+                break;
+            }
+            offset = offset.max(other_offset + other_size);
+        }
+
+        placed.push((offset, buffer));
+        total = total.max(offset + size);
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(size: usize, first_used: usize, last_used: usize) -> Buffer {
+        Buffer {
+            size,
+            first_used,
+            last_used,
+        }
+    }
+
+    #[test]
+    fn disjoint_lifetimes_share_memory() {
+        let buffers = [buffer(100, 0, 0), buffer(200, 1, 1), buffer(50, 2, 2)];
+
+        assert_eq!(plan(&buffers), 208);
+    }
+
+    #[test]
+    fn overlapping_lifetimes() {
+        // A chain of operators, where each output is the next input
+        let buffers = [
+            buffer(64, 0, 0),
+            buffer(32, 0, 1),
+            buffer(64, 1, 2),
+            buffer(16, 2, 2),
+        ];
+
+        assert_eq!(plan(&buffers), 96);
+    }
+
+    #[test]
+    fn fills_gaps() {
+        let buffers = [
+            buffer(100, 0, 2),
+            buffer(100, 0, 0),
+            buffer(200, 1, 2),
+            buffer(90, 0, 0),
+        ];
+
+        // The 90 byte buffer fits in the space later used by the 200 byte
+        // buffer
+        assert_eq!(plan(&buffers), 112 + 208);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(plan(&[]), 0);
+    }
+}
@@ -0,0 +1,93 @@
+//! The builtin operators supported by tfmicro
+//!
+//! This is the only list of them. The
+//! [`MutableOpResolver`](crate::MutableOpResolver) methods,
+//! [`AllOpResolver`](crate::AllOpResolver) and the operators known to
+//! `build::generate_resolver` are generated from it.
+
+/// Calls `$m!` with each builtin operator supported by tfmicro, in the order
+/// they are added in lite/micro/kernels/all_ops_resolver.cc
+///
+/// Each operator is given as the `MutableOpResolver` method that adds it,
+/// and its name and code in the TensorFlow Lite schema.
+macro_rules! builtin_operators {
+    ($m:ident) => {
+        $m! {
+            (fully_connected, FULLY_CONNECTED, 9),
+            (max_pool_2d, MAX_POOL_2D, 17),
+            (softmax, SOFTMAX, 25),
+            (logistic, LOGISTIC, 14),
+            (svdf, SVDF, 27),
+            (conv_2d, CONV_2D, 3),
+            (concatenation, CONCATENATION, 2),
+            (depthwise_conv_2d, DEPTHWISE_CONV_2D, 4),
+            (average_pool_2d, AVERAGE_POOL_2D, 1),
+            (abs, ABS, 101),
+            (sin, SIN, 66),
+            (cos, COS, 108),
+            (log, LOG, 73),
+            (sqrt, SQRT, 75),
+            (rsqrt, RSQRT, 76),
+            (square, SQUARE, 92),
+            (prelu, PRELU, 54),
+            (floor, FLOOR, 8),
+            (maximum, MAXIMUM, 55),
+            (minimum, MINIMUM, 57),
+            (arg_max, ARG_MAX, 56),
+            (arg_min, ARG_MIN, 79),
+            (logical_or, LOGICAL_OR, 84),
+            (logical_and, LOGICAL_AND, 86),
+            (logical_not, LOGICAL_NOT, 87),
+            (reshape, RESHAPE, 22),
+            (equal, EQUAL, 71),
+            (not_equal, NOT_EQUAL, 72),
+            (greater, GREATER, 61),
+            (greater_equal, GREATER_EQUAL, 62),
+            (less, LESS, 58),
+            (less_equal, LESS_EQUAL, 63),
+            (ceil, CEIL, 104),
+            (round, ROUND, 116),
+            (strided_slice, STRIDED_SLICE, 45),
+            (pack, PACK, 83),
+            (pad, PAD, 34),
+            (padv2, PADV2, 60),
+            (split, SPLIT, 49),
+            (unpack, UNPACK, 88),
+            (neg, NEG, 59),
+            (add, ADD, 0),
+            (mul, MUL, 18),
+            (sub, SUB, 41),
+            (quantize, QUANTIZE, 114),
+            (dequantize, DEQUANTIZE, 6),
+            (relu, RELU, 19),
+            (relu6, RELU6, 21),
+            (mean, MEAN, 40),
+            (resize_nearest_neighbor, RESIZE_NEAREST_NEIGHBOR, 97),
+            (l2_normalization, L2_NORMALIZATION, 11),
+            (tanh, TANH, 28),
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::builtin_operator_name;
+
+    macro_rules! check_names {
+        ($(($method:ident, $name:ident, $code:literal),)*) => {
+            $(
+                assert_eq!(
+                    builtin_operator_name($code),
+                    Some(stringify!($name)),
+                    "{}",
+                    stringify!($method)
+                );
+            )*
+        };
+    }
+
+    #[test]
+    fn codes_match_schema() {
+        builtin_operators!(check_names);
+    }
+}
@@ -0,0 +1,447 @@
+//! Converting between C array sources and binary files
+//!
+//! The TensorFlow Micro examples embed models and test data in C++ sources
+//! such as
+//!
+//! ```text
+//! const unsigned char g_model[] DATA_ALIGN_ATTRIBUTE = {
+//!     0x1c, 0x00, 0x00, 0x00, 0x54, 0x46, 0x4c, 0x33, ...
+//! };
+//! const int g_model_len = 2512;
+//! ```
+//!
+//! [`parse_c_array`] reads such an array, and [`Format`] encodes its values
+//! as the bytes of a `.tflite` or `.data` file. In the other direction,
+//! [`write_c_array`] and [`write_rust_static`] embed a binary file as an
+//! aligned array.
+//!
+//! Requires the `std` feature.
+
+use std::io::{self, Write};
+
+/// Alignment of the arrays written by [`write_c_array`] and
+/// [`write_rust_static`], which is suitable for TensorFlow models
+pub const ALIGNMENT: usize = 16;
+
+/// How the values of an array are stored in a binary file
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    /// Unsigned bytes
+    U8,
+    /// Signed bytes
+    I8,
+    /// Little-endian 16-bit signed integers
+    I16Le,
+    /// Little-endian 32-bit signed integers
+    I32Le,
+    /// Little-endian 32-bit floats
+    F32Le,
+    /// Big-endian 32-bit floats
+    F32Be,
+}
+
+impl Format {
+    /// The format for arrays of the C type `c_type`
+    ///
+    /// Floats are big-endian, as in the `.data` files in `examples/models`.
+    /// Returns `None` if the type is not recognised.
+    pub fn from_c_type(c_type: &str) -> Option<Self> {
+        let words: Vec<&str> = c_type
+            .split_whitespace()
+            .filter(|&w| w != "const" && w != "static")
+            .collect();
+
+        match words.as_slice() {
+            ["unsigned", "char"] | ["uint8_t"] => Some(Format::U8),
+            ["signed", "char"] | ["char"] | ["int8_t"] => Some(Format::I8),
+            ["short"] | ["int16_t"] => Some(Format::I16Le),
+            ["int"] | ["int32_t"] => Some(Format::I32Le),
+            ["float"] => Some(Format::F32Be),
+            _ => None,
+        }
+    }
+
+    /// Append `value` in this format to `bytes`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` of kind `InvalidData` if `value` cannot be
+    /// represented in this format.
+    fn encode(self, value: f64, bytes: &mut Vec<u8>) -> io::Result<()> {
+        let integer = |min: f64, max: f64| {
+            if value.fract() == 0. && value >= min && value <= max {
+                Ok(value as i64)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("value {} is out of range for {:?}", value, self),
+                ))
+            }
+        };
+
+        match self {
+            Format::U8 => bytes.push(integer(0., 255.)? as u8),
+            // Hex literals for signed bytes are often written unsigned
+            Format::I8 => bytes.push(integer(-128., 255.)? as u8),
+            Format::I16Le => bytes.extend_from_slice(
+                &(integer(i16::MIN as f64, i16::MAX as f64)? as i16)
+                    .to_le_bytes(),
+            ),
+            Format::I32Le => bytes.extend_from_slice(
+                &(integer(i32::MIN as f64, i32::MAX as f64)? as i32)
+                    .to_le_bytes(),
+            ),
+            Format::F32Le => {
+                bytes.extend_from_slice(&(value as f32).to_le_bytes())
+            }
+            Format::F32Be => {
+                bytes.extend_from_slice(&(value as f32).to_be_bytes())
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Encode `values` in this format
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` of kind `InvalidData` if any value cannot be
+    /// represented in this format.
+    pub fn encode_all(self, values: &[f64]) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for &value in values {
+            self.encode(value, &mut bytes)?;
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// An array parsed from C source
+#[derive(Clone, PartialEq, Debug)]
+pub struct CArray {
+    /// Name of the array
+    pub name: String,
+    /// C type of the elements, for example `unsigned char`
+    pub c_type: String,
+    /// Values of the elements
+    pub values: Vec<f64>,
+}
+
+impl CArray {
+    /// Encode the values of this array in the format for its C type
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` of kind `InvalidData` if the C type is not
+    /// recognised, or any value cannot be represented in that format.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let format = Format::from_c_type(&self.c_type).ok_or_else(|| {
+            invalid_data(format!("unsupported array type `{}`", self.c_type))
+        })?;
+
+        format.encode_all(&self.values)
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Replace comments in `source` with spaces
+fn strip_comments(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(start) = rest.find('/') {
+        output.push_str(&rest[..start]);
+        let comment = &rest[start..];
+
+        let end = if comment.starts_with("//") {
+            comment.find('\n').unwrap_or(comment.len())
+        } else if comment.starts_with("/*") {
+            comment.find("*/").map_or(comment.len(), |end| end + 2)
+        } else {
+            output.push('/');
+            rest = &comment[1..];
+            continue;
+        };
+
+        output.push(' ');
+        rest = &comment[end..];
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// Characters of C integer literal suffixes such as `u` and `UL`
+const INTEGER_SUFFIX: &[char] = &['u', 'U', 'l', 'L'];
+
+/// Parse a C integer or floating point literal, with an optional sign
+fn parse_literal(literal: &str) -> Option<f64> {
+    let (negative, literal) = match literal.strip_prefix('-') {
+        Some(literal) => (true, literal.trim_start()),
+        None => (false, literal.trim_start_matches('+').trim_start()),
+    };
+
+    let value = if let Some(hex) = literal
+        .strip_prefix("0x")
+        .or_else(|| literal.strip_prefix("0X"))
+    {
+        let hex = hex.trim_end_matches(INTEGER_SUFFIX);
+        u64::from_str_radix(hex, 16).ok()? as f64
+    } else if literal.contains(&['.', 'e', 'E'][..]) {
+        literal.trim_end_matches(&['f', 'F'][..]).parse().ok()?
+    } else {
+        let decimal = literal.trim_end_matches(INTEGER_SUFFIX);
+        decimal.parse::<u64>().ok()? as f64
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+/// Parse the first array definition in the C or C++ source `source`
+///
+/// Comments and attributes such as `DATA_ALIGN_ATTRIBUTE` are ignored.
+/// Values may be decimal or hexadecimal integers, or floats.
+///
+/// # Errors
+///
+/// Returns an `io::Error` of kind `InvalidData` if no array definition is
+/// found, or a value cannot be parsed.
+pub fn parse_c_array(source: &str) -> io::Result<CArray> {
+    let source = strip_comments(source);
+
+    // The declaration is `type name[...] attributes = {`
+    let open = source
+        .find('[')
+        .ok_or_else(|| invalid_data("no array found".into()))?;
+    let declaration = source[..open]
+        .rsplit(&[';', '}', '\n'][..])
+        .next()
+        .unwrap_or("");
+    let mut words: Vec<&str> = declaration.split_whitespace().collect();
+    let name = words
+        .pop()
+        .ok_or_else(|| invalid_data("array has no name".into()))?
+        .to_string();
+    let c_type = words
+        .iter()
+        .filter(|&&w| {
+            !matches!(w, "const" | "static" | "extern")
+                && !w.starts_with("alignas")
+        })
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let body_start = source[open..]
+        .find('{')
+        .map(|i| open + i + 1)
+        .ok_or_else(|| invalid_data(format!("`{}` has no values", name)))?;
+    let body_len = source[body_start..]
+        .find('}')
+        .ok_or_else(|| invalid_data(format!("`{}` is not terminated", name)))?;
+
+    let values = source[body_start..body_start + body_len]
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            parse_literal(v).ok_or_else(|| {
+                invalid_data(format!("invalid value `{}` in `{}`", v, name))
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    Ok(CArray {
+        name,
+        c_type,
+        values,
+    })
+}
+
+/// Write `bytes` to `writer` as a C++ `unsigned char` array called `name`,
+/// aligned to [`ALIGNMENT`] bytes, followed by its length as `name_len`
+///
+/// # Errors
+///
+/// Returns any error from `writer`.
+pub fn write_c_array<W: Write>(
+    mut writer: W,
+    name: &str,
+    bytes: &[u8],
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "alignas({}) const unsigned char {}[] = {{",
+        ALIGNMENT, name
+    )?;
+    write_bytes(&mut writer, bytes, "  ")?;
+    writeln!(writer, "}};")?;
+    writeln!(writer, "const unsigned int {}_len = {};", name, bytes.len())
+}
+
+/// Write `bytes` to `writer` as a Rust `static` byte slice called `name`,
+/// aligned to [`ALIGNMENT`] bytes
+///
+/// The output can be used with `include!`.
+///
+/// # Errors
+///
+/// Returns any error from `writer`.
+pub fn write_rust_static<W: Write>(
+    mut writer: W,
+    name: &str,
+    bytes: &[u8],
+) -> io::Result<()> {
+    writeln!(writer, "pub static {}: &[u8] = {{", name)?;
+    writeln!(writer, "    #[repr(C, align({}))]", ALIGNMENT)?;
+    writeln!(writer, "    struct Aligned<T>(T);")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "    static DATA: Aligned<[u8; {}]> = Aligned([",
+        bytes.len()
+    )?;
+    write_bytes(&mut writer, bytes, "        ")?;
+    writeln!(writer, "    ]);")?;
+    writeln!(writer, "    &DATA.0")?;
+    writeln!(writer, "}};")
+}
+
+/// Write `bytes` as hex literals, 12 to a line
+fn write_bytes<W: Write>(
+    writer: &mut W,
+    bytes: &[u8],
+    indent: &str,
+) -> io::Result<()> {
+    for line in bytes.chunks(12) {
+        let line: Vec<String> =
+            line.iter().map(|b| format!("0x{:02x},", b)).collect();
+        writeln!(writer, "{}{}", indent, line.join(" "))?;
+    }
+
+    Ok(())
+}
+
+/// Returns whether `name` can be used as a C or Rust identifier
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_model_array() {
+        let source = "
+            // Automatically created from a TensorFlow Lite flatbuffer
+            #include \"model.h\"
+
+            const unsigned char g_model[] DATA_ALIGN_ATTRIBUTE = {
+                0x1c, 0x00, 0x00, 0x00, 0x54, 0x46, 0x4c, 0x33, /* TFL3 */
+                0xFF};
+            const int g_model_len = 9;
+        ";
+
+        let array = parse_c_array(source).unwrap();
+        assert_eq!(array.name, "g_model");
+        assert_eq!(array.c_type, "unsigned char");
+        assert_eq!(array.to_bytes().unwrap(), b"\x1c\0\0\0TFL3\xff");
+    }
+
+    #[test]
+    fn parse_float_array() {
+        let source = "const float g_ring_micro_f9643d42_nohash_4_data[] = {
+            -0.5, 1.25f, 3,
+            1e-2, -7.0e+1 };";
+
+        let array = parse_c_array(source).unwrap();
+        assert_eq!(array.c_type, "float");
+        assert_eq!(array.values, [-0.5, 1.25, 3., 0.01, -70.]);
+
+        let bytes = array.to_bytes().unwrap();
+        assert_eq!(&bytes[..4], (-0.5f32).to_be_bytes());
+    }
+
+    #[test]
+    fn parse_decimal_array() {
+        let source = "const int16_t g_yes_1000ms_sample_data[16000] = {\n\
+                      -5, 12, -32768, 32767,\n};";
+
+        let array = parse_c_array(source).unwrap();
+        assert_eq!(
+            array.to_bytes().unwrap(),
+            [0xfb, 0xff, 12, 0, 0x00, 0x80, 0xff, 0x7f]
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        let source = "const uint8_t data[] = { 1, 256 };";
+        let error = parse_c_array(source).unwrap().to_bytes().unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(Format::I16Le.encode_all(&[0.5]).is_err());
+    }
+
+    #[test]
+    fn invalid_value() {
+        let source = "const uint8_t data[] = { 1, x };";
+        let error = parse_c_array(source).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn c_array_round_trip() {
+        let bytes: Vec<u8> = (0..30).collect();
+
+        let mut source = Vec::new();
+        write_c_array(&mut source, "g_data", &bytes).unwrap();
+        let source = String::from_utf8(source).unwrap();
+
+        assert!(source.starts_with("alignas(16) const unsigned char g_data[]"));
+        assert!(source.ends_with("const unsigned int g_data_len = 30;\n"));
+
+        let array = parse_c_array(&source).unwrap();
+        assert_eq!(array.name, "g_data");
+        assert_eq!(array.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn rust_static() {
+        let mut source = Vec::new();
+        write_rust_static(&mut source, "MODEL", &[1, 2, 255]).unwrap();
+
+        assert_eq!(
+            String::from_utf8(source).unwrap(),
+            "pub static MODEL: &[u8] = {
+    #[repr(C, align(16))]
+    struct Aligned<T>(T);
+
+    static DATA: Aligned<[u8; 3]> = Aligned([
+        0x01, 0x02, 0xff,
+    ]);
+    &DATA.0
+};
+"
+        );
+    }
+
+    #[test]
+    fn identifiers() {
+        assert!(is_identifier("g_model"));
+        assert!(is_identifier("_MODEL2"));
+        assert!(!is_identifier("2model"));
+        assert!(!is_identifier("model-data"));
+        assert!(!is_identifier(""));
+    }
+}
@@ -0,0 +1,489 @@
+//! Integrity-checked and signed model containers
+//!
+//! Models that are updated over the air can be wrapped in a container,
+//! which is checked before the model is passed to TensorFlow. A corrupted,
+//! truncated or unsigned model is rejected without the flatbuffer verifier
+//! ever reading it.
+//!
+//! A container is a 128 byte header followed by the `.tflite` model. All
+//! fields are little-endian.
+//!
+//! | Offset | Size | Field                                          |
+//! |--------|------|------------------------------------------------|
+//! | 0      | 4    | Magic, `TFMC`                                  |
+//! | 4      | 2    | Format version, currently 1                    |
+//! | 6      | 2    | Flags, see [`FLAG_SHA256`] and [`FLAG_SIGNED`] |
+//! | 8      | 4    | Length of the model in bytes                   |
+//! | 12     | 4    | CRC-32 (IEEE) of the model                     |
+//! | 16     | 32   | SHA-256 of the model                           |
+//! | 48     | 64   | Ed25519 signature of bytes 0 to 48             |
+//! | 112    | 16   | Reserved, zero                                 |
+//!
+//! The signature covers the length and digest of the model, rather than
+//! the model itself. The header length is a multiple of
+//! [`Model::ALIGNMENT`](crate::Model::ALIGNMENT), so a model within an
+//! aligned container is itself aligned. Bytes after the model are ignored,
+//! so that a container can be read from a padded flash partition.
+//!
+//! The CRC-32 is always checked. Checking the SHA-256 digest requires the
+//! `sha256` feature, and checking the signature requires the `ed25519`
+//! feature.
+//!
+//! ```
+//! use tfmicro::container::{self, Container};
+//!
+//! let model = include_bytes!("../examples/models/hello_world.tflite");
+//! # #[cfg(any(feature = "std", feature = "alloc"))]
+//! # {
+//! let bytes = container::wrap(&model[..]);
+//!
+//! let container = Container::parse(&bytes).unwrap();
+//! container.check().unwrap();
+//! assert_eq!(container.model(), &model[..]);
+//! # }
+//! ```
+
+use core::convert::TryInto;
+
+use crate::Error;
+
+/// Magic at the start of every container
+pub const MAGIC: [u8; 4] = *b"TFMC";
+
+/// Format version written by this crate
+pub const VERSION: u16 = 1;
+
+/// Length of the header in bytes
+pub const HEADER_LEN: usize = 128;
+
+/// The SHA-256 field holds a digest of the model
+pub const FLAG_SHA256: u16 = 1 << 0;
+
+/// The signature field holds an Ed25519 signature. Requires
+/// [`FLAG_SHA256`]
+pub const FLAG_SIGNED: u16 = 1 << 1;
+
+/// Length of the part of the header that is signed
+#[cfg(feature = "ed25519")]
+const SIGNED_LEN: usize = 48;
+
+/// CRC-32 lookup table, for the reflected IEEE polynomial
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+/// CRC-32 of `bytes`, as used by zlib and Ethernet
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &b| {
+        CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// A model container, parsed from a buffer
+///
+/// Parsing only reads the header. Call [`check`](Container::check) or
+/// [`verify`](Container::verify) before using the model.
+#[derive(Clone, Copy, Debug)]
+pub struct Container<'a> {
+    header: &'a [u8; HEADER_LEN],
+    model: &'a [u8],
+}
+
+impl<'a> Container<'a> {
+    /// Parse the header of the container in `bytes`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidContainer` if the magic or version are not
+    /// recognised, or if `bytes` is shorter than the model length given in
+    /// the header
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        let header: &[u8; HEADER_LEN] = bytes
+            .get(..HEADER_LEN)
+            .and_then(|header| header.try_into().ok())
+            .ok_or(Error::InvalidContainer)?;
+        if header[0..4] != MAGIC {
+            return Err(Error::InvalidContainer);
+        }
+
+        let container = Self { header, model: &[] };
+        if container.version() != VERSION {
+            return Err(Error::InvalidContainer);
+        }
+
+        let model = HEADER_LEN
+            .checked_add(container.model_len())
+            .and_then(|end| bytes.get(HEADER_LEN..end))
+            .ok_or(Error::InvalidContainer)?;
+
+        Ok(Self { model, ..container })
+    }
+
+    fn u16(&self, pos: usize) -> u16 {
+        u16::from_le_bytes([self.header[pos], self.header[pos + 1]])
+    }
+
+    fn u32(&self, pos: usize) -> u32 {
+        u32::from_le_bytes(self.header[pos..pos + 4].try_into().unwrap())
+    }
+
+    /// Format version
+    pub fn version(&self) -> u16 {
+        self.u16(4)
+    }
+
+    /// Flags
+    pub fn flags(&self) -> u16 {
+        self.u16(6)
+    }
+
+    /// Length of the model in bytes
+    pub fn model_len(&self) -> usize {
+        self.u32(8) as usize
+    }
+
+    /// CRC-32 of the model, as given in the header
+    pub fn crc32(&self) -> u32 {
+        self.u32(12)
+    }
+
+    /// SHA-256 of the model, as given in the header, if present
+    pub fn sha256(&self) -> Option<&'a [u8; 32]> {
+        if self.flags() & FLAG_SHA256 == 0 {
+            return None;
+        }
+        self.header[16..48].try_into().ok()
+    }
+
+    /// Ed25519 signature, as given in the header, if present
+    pub fn signature(&self) -> Option<&'a [u8; 64]> {
+        if self.flags() & FLAG_SIGNED == 0 {
+            return None;
+        }
+        self.header[48..112].try_into().ok()
+    }
+
+    /// The model. This has not been checked against the header unless
+    /// [`check`](Container::check) or [`verify`](Container::verify) returned
+    /// `Ok`
+    pub fn model(&self) -> &'a [u8] {
+        self.model
+    }
+
+    /// Check the CRC-32 of the model, and its SHA-256 digest if present
+    /// and the `sha256` feature is enabled
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ChecksumMismatch` if the model does not match the
+    /// header
+    pub fn check(&self) -> Result<(), Error> {
+        if crc32(self.model) != self.crc32() {
+            return Err(Error::ChecksumMismatch);
+        }
+
+        #[cfg(feature = "sha256")]
+        {
+            use sha2::{Digest, Sha256};
+
+            if let Some(sha256) = self.sha256() {
+                if Sha256::digest(self.model)[..] != sha256[..] {
+                    return Err(Error::ChecksumMismatch);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the model as for [`check`](Container::check), and that it was
+    /// signed by the Ed25519 public key `key`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ChecksumMismatch` if the model does not match the
+    /// header.
+    ///
+    /// Returns `Error::InvalidSignature` if the container is not signed,
+    /// the signature does not match, or `key` is not a valid public key
+    #[cfg(feature = "ed25519")]
+    pub fn verify(&self, key: &[u8; 32]) -> Result<(), Error> {
+        use core::convert::TryFrom;
+        use ed25519_dalek::{PublicKey, Signature};
+
+        // The signature only covers the digest, so it must be present
+        let signature = match (self.sha256(), self.signature()) {
+            (Some(_), Some(signature)) => {
+                Signature::try_from(&signature[..])
+                    .map_err(|_| Error::InvalidSignature)?
+            }
+            _ => return Err(Error::InvalidSignature),
+        };
+        let key =
+            PublicKey::from_bytes(key).map_err(|_| Error::InvalidSignature)?;
+        key.verify_strict(&self.header[..SIGNED_LEN], &signature)
+            .map_err(|_| Error::InvalidSignature)?;
+
+        self.check()
+    }
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+mod write {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    /// Header for `model`, without a signature
+    fn header(model: &[u8], flags: u16) -> [u8; HEADER_LEN] {
+        let len: u32 = model.len().try_into().expect("model is too large");
+
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(&MAGIC);
+        header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        header[6..8].copy_from_slice(&flags.to_le_bytes());
+        header[8..12].copy_from_slice(&len.to_le_bytes());
+        header[12..16].copy_from_slice(&crc32(model).to_le_bytes());
+
+        #[cfg(feature = "sha256")]
+        {
+            use sha2::{Digest, Sha256};
+
+            if flags & FLAG_SHA256 != 0 {
+                header[16..48].copy_from_slice(&Sha256::digest(model));
+            }
+        }
+
+        header
+    }
+
+    fn concat(header: &[u8; HEADER_LEN], model: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + model.len());
+        bytes.extend_from_slice(header);
+        bytes.extend_from_slice(model);
+        bytes
+    }
+
+    /// Wrap `model` in an unsigned container. The SHA-256 digest is included
+    /// with the `sha256` feature
+    ///
+    /// # Panics
+    ///
+    /// Panics if the model is 4GiB or larger
+    pub fn wrap(model: &[u8]) -> Vec<u8> {
+        let flags = if cfg!(feature = "sha256") {
+            FLAG_SHA256
+        } else {
+            0
+        };
+
+        concat(&header(model, flags), model)
+    }
+
+    /// Wrap `model` in a container signed with the Ed25519 secret key
+    /// `secret_key`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidSignature` if `secret_key` is not a valid
+    /// secret key
+    ///
+    /// # Panics
+    ///
+    /// Panics if the model is 4GiB or larger
+    #[cfg(feature = "ed25519")]
+    pub fn wrap_signed(
+        model: &[u8],
+        secret_key: &[u8; 32],
+    ) -> Result<Vec<u8>, Error> {
+        use ed25519_dalek::{ExpandedSecretKey, PublicKey, SecretKey};
+
+        let secret_key = SecretKey::from_bytes(secret_key)
+            .map_err(|_| Error::InvalidSignature)?;
+        let public_key = PublicKey::from(&secret_key);
+
+        let mut header = header(model, FLAG_SHA256 | FLAG_SIGNED);
+        let signature = ExpandedSecretKey::from(&secret_key)
+            .sign(&header[..SIGNED_LEN], &public_key);
+        header[48..112].copy_from_slice(&signature.to_bytes());
+
+        Ok(concat(&header, model))
+    }
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+pub use write::wrap;
+#[cfg(all(feature = "ed25519", any(feature = "std", feature = "alloc")))]
+pub use write::wrap_signed;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let mut header = [0; HEADER_LEN];
+        assert_eq!(
+            Container::parse(&header).unwrap_err(),
+            Error::InvalidContainer
+        );
+
+        // Version 2 is not supported
+        header[0..4].copy_from_slice(&MAGIC);
+        header[4] = 2;
+        assert_eq!(
+            Container::parse(&header).unwrap_err(),
+            Error::InvalidContainer
+        );
+
+        // Empty model
+        header[4] = 1;
+        let container = Container::parse(&header).unwrap();
+        assert_eq!(container.model(), &[]);
+        assert_eq!(container.sha256(), None);
+        container.check().unwrap();
+
+        assert_eq!(
+            Container::parse(&header[..64]).unwrap_err(),
+            Error::InvalidContainer
+        );
+    }
+
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    mod write {
+        extern crate alloc;
+
+        use super::*;
+
+        const MODEL: &[u8] =
+            include_bytes!("../examples/models/hello_world.tflite");
+
+        #[test]
+        fn wrap_and_parse() {
+            let mut bytes = wrap(MODEL);
+            assert_eq!(bytes.len(), HEADER_LEN + MODEL.len());
+
+            // Padding after the model is ignored
+            bytes.extend_from_slice(&[0xff; 64]);
+            let container = Container::parse(&bytes).unwrap();
+            assert_eq!(container.version(), VERSION);
+            assert_eq!(container.model(), MODEL);
+            assert_eq!(container.signature(), None);
+            container.check().unwrap();
+        }
+
+        #[test]
+        fn truncated() {
+            let bytes = wrap(MODEL);
+
+            assert_eq!(
+                Container::parse(&bytes[..bytes.len() - 1]).unwrap_err(),
+                Error::InvalidContainer
+            );
+        }
+
+        #[test]
+        fn corrupted() {
+            let mut bytes = wrap(MODEL);
+            bytes[HEADER_LEN + 100] ^= 1;
+
+            let container = Container::parse(&bytes).unwrap();
+            assert_eq!(container.check().unwrap_err(), Error::ChecksumMismatch);
+        }
+
+        #[cfg(feature = "sha256")]
+        #[test]
+        fn sha256_mismatch() {
+            // The CRC-32 still matches, but the digest does not
+            let mut bytes = wrap(MODEL);
+            bytes[16] ^= 1;
+
+            let container = Container::parse(&bytes).unwrap();
+            assert_eq!(container.check().unwrap_err(), Error::ChecksumMismatch);
+        }
+
+        #[cfg(feature = "ed25519")]
+        mod signed {
+            use super::*;
+            use alloc::vec;
+            use ed25519_dalek::{PublicKey, SecretKey};
+
+            const SECRET_KEY: [u8; 32] = [7; 32];
+
+            fn public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+                let secret_key = SecretKey::from_bytes(secret_key).unwrap();
+                PublicKey::from(&secret_key).to_bytes()
+            }
+
+            #[test]
+            fn verify() {
+                let bytes = wrap_signed(MODEL, &SECRET_KEY).unwrap();
+                let container = Container::parse(&bytes).unwrap();
+
+                container.verify(&public_key(&SECRET_KEY)).unwrap();
+                assert_eq!(
+                    container.verify(&public_key(&[8; 32])).unwrap_err(),
+                    Error::InvalidSignature
+                );
+            }
+
+            #[test]
+            fn unsigned() {
+                let bytes = wrap(MODEL);
+                let container = Container::parse(&bytes).unwrap();
+
+                assert_eq!(
+                    container.verify(&public_key(&SECRET_KEY)).unwrap_err(),
+                    Error::InvalidSignature
+                );
+            }
+
+            #[test]
+            fn tampered() {
+                let key = public_key(&SECRET_KEY);
+
+                // A modified model with a matching CRC-32 and digest is
+                // still rejected, as the digest is signed
+                let model = vec![0u8; 64];
+                let mut bytes = wrap_signed(MODEL, &SECRET_KEY).unwrap();
+                let forged = wrap(&model);
+                bytes.truncate(HEADER_LEN);
+                bytes[8..48].copy_from_slice(&forged[8..48]);
+                bytes.extend_from_slice(&model);
+
+                let container = Container::parse(&bytes).unwrap();
+                container.check().unwrap();
+                assert_eq!(
+                    container.verify(&key).unwrap_err(),
+                    Error::InvalidSignature
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Reading and writing arrays as comma-separated values
+//!
+//! Requires the `std` feature.
+
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+
+/// Write `data` to `writer` as CSV, with `columns` values on each line
+///
+/// # Errors
+///
+/// Returns an `io::Error` of kind `InvalidInput` if `columns` is zero or
+/// the length of `data` is not a multiple of `columns`, or any error from
+/// `writer`.
+pub fn write_csv<W: Write, T: Display>(
+    mut writer: W,
+    columns: usize,
+    data: &[T],
+) -> io::Result<()> {
+    if columns == 0 || !data.chunks_exact(columns).remainder().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "data length is not a multiple of the number of columns",
+        ));
+    }
+
+    for row in data.chunks_exact(columns) {
+        for (i, x) in row.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            write!(writer, "{}", x)?;
+        }
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// Read all values from CSV in `reader`, in row-major order
+///
+/// Values may be separated by commas or whitespace. Blank lines and lines
+/// starting with `#` are ignored.
+///
+/// # Errors
+///
+/// Returns an `io::Error` of kind `InvalidData` if a value cannot be parsed
+/// as a number, or any error from `reader`.
+pub fn read_csv<R: BufRead>(reader: R) -> io::Result<Vec<f64>> {
+    let mut data = Vec::new();
+
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        for value in line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|v| !v.is_empty())
+        {
+            let x = value.parse().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid value `{}` on line {}", value, n + 1),
+                )
+            })?;
+            data.push(x);
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv() {
+        let mut output = Vec::new();
+        write_csv(&mut output, 3, &[1, 2, 3, 4, 5, 6]).unwrap();
+
+        assert_eq!(output, b"1,2,3\n4,5,6\n");
+    }
+
+    #[test]
+    fn csv_read() {
+        let input = "# comment\n1,2.5,-3\n\n4 5\t6\n";
+
+        assert_eq!(
+            read_csv(input.as_bytes()).unwrap(),
+            [1., 2.5, -3., 4., 5., 6.]
+        );
+    }
+
+    #[test]
+    fn csv_read_invalid() {
+        let error = read_csv("1,x\n".as_bytes()).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn csv_columns_mismatch() {
+        let error = write_csv(Vec::new(), 4, &[0u8; 6]).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,450 @@
+//! Measuring the accuracy of a classification model on a labelled dataset
+//!
+//! An [`Evaluation`] records the scores that the model gave each sample,
+//! and reports top-1 and top-k accuracy, a confusion matrix and the
+//! precision and recall of each class. The `tfmicro eval` command runs a
+//! directory of samples through a model with it, see DEVELOP.md.
+//!
+//! Requires the `std` feature.
+//!
+//! ```
+//! # use tfmicro::eval::Evaluation;
+//! let mut evaluation = Evaluation::new(3, 2);
+//!
+//! evaluation.record(0, &[0.9, 0.05, 0.05]);
+//! evaluation.record(1, &[0.6, 0.3, 0.1]);
+//!
+//! assert_eq!(evaluation.accuracy(), 0.5);
+//! assert_eq!(evaluation.top_k_accuracy(), 1.);
+//! assert_eq!(evaluation.confusion().count(1, 0), 1);
+//! ```
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// Counts of the predicted class for each actual class
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfusionMatrix {
+    classes: usize,
+    counts: Vec<usize>,
+}
+
+impl ConfusionMatrix {
+    /// Create an empty matrix for `classes` classes
+    pub fn new(classes: usize) -> Self {
+        Self {
+            classes,
+            counts: vec![0; classes * classes],
+        }
+    }
+
+    /// Returns the number of classes
+    pub fn classes(&self) -> usize {
+        self.classes
+    }
+
+    /// Record a sample of class `actual` that was predicted as `predicted`
+    ///
+    /// # Panics
+    ///
+    /// Panics if either class is out of range.
+    pub fn record(&mut self, actual: usize, predicted: usize) {
+        assert!(actual < self.classes && predicted < self.classes);
+
+        self.counts[actual * self.classes + predicted] += 1;
+    }
+
+    /// Returns the number of samples of class `actual` that were predicted
+    /// as `predicted`
+    pub fn count(&self, actual: usize, predicted: usize) -> usize {
+        self.counts[actual * self.classes + predicted]
+    }
+
+    /// Returns the total number of samples
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the number of samples that were predicted correctly
+    pub fn correct(&self) -> usize {
+        (0..self.classes).map(|c| self.count(c, c)).sum()
+    }
+
+    /// Returns the fraction of predictions of `class` that were correct, or
+    /// `None` if `class` was never predicted
+    pub fn precision(&self, class: usize) -> Option<f64> {
+        let predicted: usize = (0..self.classes)
+            .map(|actual| self.count(actual, class))
+            .sum();
+
+        ratio(self.count(class, class), predicted)
+    }
+
+    /// Returns the fraction of samples of `class` that were predicted
+    /// correctly, or `None` if there were no samples of `class`
+    pub fn recall(&self, class: usize) -> Option<f64> {
+        let actual: usize = (0..self.classes)
+            .map(|predicted| self.count(class, predicted))
+            .sum();
+
+        ratio(self.count(class, class), actual)
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> Option<f64> {
+    if denominator == 0 {
+        None
+    } else {
+        Some(numerator as f64 / denominator as f64)
+    }
+}
+
+/// Accuracy statistics for a classification model
+#[derive(Clone, PartialEq, Debug)]
+pub struct Evaluation {
+    confusion: ConfusionMatrix,
+    k: usize,
+    top_k_correct: usize,
+}
+
+impl Evaluation {
+    /// Create an evaluation of a model with `classes` output classes, which
+    /// also measures top-`k` accuracy
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is zero.
+    pub fn new(classes: usize, k: usize) -> Self {
+        assert!(k > 0, "k must be at least 1");
+
+        Self {
+            confusion: ConfusionMatrix::new(classes),
+            k,
+            top_k_correct: 0,
+        }
+    }
+
+    /// Record the `scores` that the model gave to a sample of class
+    /// `actual`. The predicted class is the one with the highest score.
+    /// Ties are broken in favour of the lowest class
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `scores` is not the number of classes, or
+    /// `actual` is out of range.
+    pub fn record<T: Copy + Into<f64>>(&mut self, actual: usize, scores: &[T]) {
+        assert_eq!(scores.len(), self.confusion.classes());
+
+        let score = |class: usize| -> f64 { scores[class].into() };
+        let predicted = (0..scores.len())
+            .fold(None, |best: Option<usize>, class| match best {
+                Some(best) if score(best) >= score(class) => Some(best),
+                _ => Some(class),
+            })
+            .unwrap_or(0);
+        self.confusion.record(actual, predicted);
+
+        // Classes that scored strictly higher, or equal and lower
+        let rank = (0..scores.len())
+            .filter(|&class| {
+                score(class) > score(actual)
+                    || (score(class) == score(actual) && class < actual)
+            })
+            .count();
+        if rank < self.k {
+            self.top_k_correct += 1;
+        }
+    }
+
+    /// Returns the confusion matrix
+    pub fn confusion(&self) -> &ConfusionMatrix {
+        &self.confusion
+    }
+
+    /// Returns the `k` of top-k accuracy
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the fraction of samples where the highest score was the
+    /// actual class
+    pub fn accuracy(&self) -> f64 {
+        ratio(self.confusion.correct(), self.confusion.total()).unwrap_or(0.)
+    }
+
+    /// Returns the fraction of samples where the actual class was among the
+    /// `k` highest scores
+    pub fn top_k_accuracy(&self) -> f64 {
+        ratio(self.top_k_correct, self.confusion.total()).unwrap_or(0.)
+    }
+
+    /// Write a report of the accuracy, confusion matrix, precision and
+    /// recall to `writer`. Classes are labelled with `names`, or their index
+    /// if there are too few names
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from `writer`.
+    pub fn write_report<W: Write>(
+        &self,
+        mut writer: W,
+        names: &[String],
+    ) -> io::Result<()> {
+        write!(
+            writer,
+            "{}",
+            Report {
+                evaluation: self,
+                names
+            }
+        )
+    }
+}
+
+/// Formats an [`Evaluation`] with class names
+struct Report<'a> {
+    evaluation: &'a Evaluation,
+    names: &'a [String],
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let evaluation = self.evaluation;
+        let confusion = &evaluation.confusion;
+        let classes = confusion.classes();
+        let names: Vec<String> = (0..classes)
+            .map(|c| {
+                self.names.get(c).cloned().unwrap_or_else(|| c.to_string())
+            })
+            .collect();
+        let width = names.iter().map(String::len).max().unwrap_or(0).max(6);
+        let percent = |x: Option<f64>| match x {
+            Some(x) => format!("{:.1}%", 100. * x),
+            None => "-".into(),
+        };
+
+        writeln!(f, "Samples: {}", confusion.total())?;
+        writeln!(
+            f,
+            "Top-1 accuracy: {}",
+            percent(Some(evaluation.accuracy()))
+        )?;
+        writeln!(
+            f,
+            "Top-{} accuracy: {}",
+            evaluation.k,
+            percent(Some(evaluation.top_k_accuracy()))
+        )?;
+
+        writeln!(f)?;
+        writeln!(f, "Confusion matrix (rows are actual, columns predicted):")?;
+        write!(f, "{:>w$}", "", w = width)?;
+        for name in &names {
+            write!(f, " {:>w$}", name, w = width)?;
+        }
+        writeln!(f)?;
+        for (actual, name) in names.iter().enumerate() {
+            write!(f, "{:>w$}", name, w = width)?;
+            for predicted in 0..classes {
+                let count = confusion.count(actual, predicted);
+                write!(f, " {:>w$}", count, w = width)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>w$} {:>9} {:>9}",
+            "class",
+            "precision",
+            "recall",
+            w = width
+        )?;
+        for (class, name) in names.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>w$} {:>9} {:>9}",
+                name,
+                percent(confusion.precision(class)),
+                percent(confusion.recall(class)),
+                w = width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A sample listed in a labels file
+#[derive(Clone, PartialEq, Debug)]
+pub struct LabelledSample {
+    /// Path of the sample, relative to the dataset directory
+    pub path: String,
+    /// Class of the sample
+    pub class: usize,
+}
+
+/// Read a labels file from `reader`
+///
+/// Each line is `path,label`, where the label is an index into `names` or
+/// one of the `names`. If `header` is true, the first line is a header
+/// such as `path,label`, and is skipped. Blank lines and lines starting
+/// with `#` are ignored.
+///
+/// # Errors
+///
+/// Returns an `io::Error` of kind `InvalidData` if a line does not contain
+/// a valid label, or any error from `reader`.
+pub fn read_labels<R: BufRead>(
+    reader: R,
+    names: &[String],
+    header: bool,
+) -> io::Result<Vec<LabelledSample>> {
+    let mut samples = Vec::new();
+
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        if header && n == 0 {
+            continue;
+        }
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (path, label) = match line.rfind(',') {
+            Some(comma) => (line[..comma].trim(), line[comma + 1..].trim()),
+            None => (line, ""),
+        };
+        let class = names
+            .iter()
+            .position(|name| name == label)
+            .or_else(|| label.parse().ok())
+            .filter(|&class| names.is_empty() || class < names.len());
+
+        match class {
+            Some(class) => samples.push(LabelledSample {
+                path: path.into(),
+                class,
+            }),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid label `{}` on line {}", label, n + 1),
+                ))
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confusion_matrix() {
+        let mut confusion = ConfusionMatrix::new(3);
+        for &(actual, predicted) in
+            &[(0, 0), (0, 0), (0, 1), (1, 1), (1, 0), (2, 2)]
+        {
+            confusion.record(actual, predicted);
+        }
+
+        assert_eq!(confusion.total(), 6);
+        assert_eq!(confusion.correct(), 4);
+        assert_eq!(confusion.count(0, 1), 1);
+        assert_eq!(confusion.precision(0), Some(2. / 3.));
+        assert_eq!(confusion.recall(0), Some(2. / 3.));
+        assert_eq!(confusion.precision(2), Some(1.));
+        assert_eq!(confusion.recall(1), Some(0.5));
+    }
+
+    #[test]
+    fn precision_without_predictions() {
+        let mut confusion = ConfusionMatrix::new(2);
+        confusion.record(1, 0);
+
+        assert_eq!(confusion.precision(1), None);
+        assert_eq!(confusion.recall(0), None);
+        assert_eq!(confusion.recall(1), Some(0.));
+    }
+
+    #[test]
+    fn top_k() {
+        let mut evaluation = Evaluation::new(4, 2);
+
+        evaluation.record(0, &[10u8, 20, 5, 0]); // Second
+        evaluation.record(2, &[10u8, 20, 5, 0]); // Third
+        evaluation.record(3, &[0u8, 0, 0, 0]); // Tie, last
+        evaluation.record(1, &[1u8, 3, 2, 0]); // First
+
+        assert_eq!(evaluation.accuracy(), 0.25);
+        assert_eq!(evaluation.top_k_accuracy(), 0.5);
+        assert_eq!(evaluation.confusion().count(3, 0), 1);
+    }
+
+    #[test]
+    fn empty_evaluation() {
+        let evaluation = Evaluation::new(2, 1);
+
+        assert_eq!(evaluation.accuracy(), 0.);
+        assert_eq!(evaluation.top_k_accuracy(), 0.);
+    }
+
+    #[test]
+    fn report() {
+        let mut evaluation = Evaluation::new(2, 1);
+        evaluation.record(0, &[0.75f32, 0.25]);
+        evaluation.record(1, &[0.75f32, 0.25]);
+
+        let mut report = Vec::new();
+        evaluation
+            .write_report(&mut report, &["yes".into()])
+            .unwrap();
+        let report = String::from_utf8(report).unwrap();
+
+        assert!(report.contains("Top-1 accuracy: 50.0%"));
+        assert!(report.contains("\n   yes      1      0\n"));
+        assert!(report.contains("\n     1      1      0\n"));
+        assert!(report.contains("\n   yes     50.0%    100.0%\n"));
+        assert!(report.contains("\n     1         -      0.0%\n"));
+    }
+
+    #[test]
+    fn labels() {
+        let names: Vec<String> = vec!["silence".into(), "yes".into()];
+        let input = "file,label\n\
+                     # comment\n\
+                     a.npy, yes\n\
+                     b,c.npy,0\n\
+                     \n";
+
+        let samples = read_labels(input.as_bytes(), &names, true).unwrap();
+        assert_eq!(
+            samples,
+            [
+                LabelledSample {
+                    path: "a.npy".into(),
+                    class: 1
+                },
+                LabelledSample {
+                    path: "b,c.npy".into(),
+                    class: 0
+                },
+            ]
+        );
+
+        // Without a header, an invalid first line is an error
+        let error = read_labels("a.npy,2\n".as_bytes(), &names, false);
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let error = read_labels(input.as_bytes(), &names, false);
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // Only the first line is skipped as a header
+        let error = read_labels("a.npy,0\nb.npy,2\n".as_bytes(), &names, true);
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
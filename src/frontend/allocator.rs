@@ -0,0 +1,253 @@
+//! Memory allocation for the audio frontend
+//!
+//! The frontend library calls `malloc` from `FrontendPopulateState`, and
+//! `free` from `FrontendFreeStateContents`. When the library is built these
+//! are renamed to `tfmicro_frontend_malloc` and `tfmicro_frontend_free`
+//! (see build.rs), which are implemented here.
+//!
+//! Whilst a caller-provided buffer is active, allocations are taken from
+//! that buffer. Otherwise they are passed to the global allocator if the
+//! `std` or `alloc` feature is enabled, or to the C library `malloc` if
+//! not. With the `no-malloc` feature there is no fallback, and allocations
+//! outside a buffer fail.
+//!
+//! Only one frontend state can be allocated at a time. With `std`, other
+//! threads wait for it. Without `std` there is nothing to wait on, and an
+//! interrupt could not wait for the code it interrupted, so allocating
+//! fails instead.
+
+use core::cell::UnsafeCell;
+use core::mem::size_of;
+use core::ptr;
+#[cfg(not(feature = "std"))]
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Alignment of each allocation, as for malloc
+const ALIGN: usize = 2 * size_of::<usize>();
+
+/// A bump allocator over a caller-provided buffer
+struct Buffer {
+    start: *mut u8,
+    len: usize,
+    used: usize,
+}
+
+impl Buffer {
+    fn allocate(&mut self, size: usize) -> *mut u8 {
+        let address = self.start as usize + self.used;
+        let padding = (ALIGN - address % ALIGN) % ALIGN;
+
+        match self
+            .used
+            .checked_add(padding)
+            .and_then(|o| o.checked_add(size))
+        {
+            Some(end) if end <= self.len => {
+                let allocation = unsafe { self.start.add(self.used + padding) };
+                self.used = end;
+                allocation
+            }
+            _ => ptr::null_mut(),
+        }
+    }
+}
+
+struct Current(UnsafeCell<Option<Buffer>>);
+
+// Only accessed whilst a `Lock` is held
+unsafe impl Sync for Current {}
+
+static CURRENT: Current = Current(UnsafeCell::new(None));
+
+// Start and end addresses of the active buffer, or zero. These are read by
+// `tfmicro_frontend_free`, which is also called when a frontend is dropped,
+// without the lock
+static BUFFER_START: AtomicUsize = AtomicUsize::new(0);
+static BUFFER_END: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "std")]
+static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// Set whilst the lock is held
+#[cfg(not(feature = "std"))]
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Exclusive access to `CURRENT`, released when dropped
+struct Lock {
+    #[cfg(feature = "std")]
+    _guard: std::sync::MutexGuard<'static, ()>,
+}
+
+impl Lock {
+    /// Take the lock, waiting for it with `std`. Returns `None` without
+    /// `std` if it is already held
+    fn take() -> Option<Self> {
+        #[cfg(feature = "std")]
+        let lock = Self {
+            _guard: LOCK.lock().unwrap_or_else(|e| e.into_inner()),
+        };
+        #[cfg(not(feature = "std"))]
+        let lock = {
+            LOCKED
+                .compare_exchange(
+                    false,
+                    true,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .ok()?;
+            Self {}
+        };
+
+        Some(lock)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Drop for Lock {
+    fn drop(&mut self) {
+        LOCKED.store(false, Ordering::Release);
+    }
+}
+
+/// Run `f`, with allocations passed to the global allocator
+///
+/// Returns `None` if another frontend state is being allocated, see the
+/// module documentation
+pub(crate) fn with_global_allocator<R>(f: impl FnOnce() -> R) -> Option<R> {
+    let _lock = Lock::take()?;
+
+    Some(f())
+}
+
+/// Run `f`, with allocations taken from `buffer`
+///
+/// Returns the result of `f` and the number of bytes of `buffer` used, or
+/// `None` if another frontend state is being allocated
+pub(crate) fn with_buffer<R>(
+    buffer: &mut [u8],
+    f: impl FnOnce() -> R,
+) -> Option<(R, usize)> {
+    let _lock = Lock::take()?;
+
+    let start = buffer.as_mut_ptr();
+    let current = CURRENT.0.get();
+    unsafe {
+        *current = Some(Buffer {
+            start,
+            len: buffer.len(),
+            used: 0,
+        });
+    }
+    BUFFER_START.store(start as usize, Ordering::Release);
+    BUFFER_END.store(start as usize + buffer.len(), Ordering::Release);
+
+    let result = f();
+
+    BUFFER_START.store(0, Ordering::Release);
+    BUFFER_END.store(0, Ordering::Release);
+    let used = unsafe { (*current).take().map_or(0, |b| b.used) };
+    Some((result, used))
+}
+
+/// Called by the frontend library whilst a `Lock` is held
+#[no_mangle]
+pub extern "C" fn tfmicro_frontend_malloc(size: usize) -> *mut cty::c_void {
+    let current = unsafe { &mut *CURRENT.0.get() };
+
+    let allocation = match current {
+        Some(buffer) => buffer.allocate(size),
+        None => global::allocate(size),
+    };
+
+    allocation as *mut cty::c_void
+}
+
+/// Called by the frontend library, with or without a `Lock`
+#[no_mangle]
+pub extern "C" fn tfmicro_frontend_free(p: *mut cty::c_void) {
+    let p = p as usize;
+
+    if p == 0 {
+        return;
+    }
+    // Memory from a caller-provided buffer is never freed individually. A
+    // buffer in use by another thread never contains memory from the
+    // global allocator, so it does not matter if it changes meanwhile
+    let start = BUFFER_START.load(Ordering::Acquire);
+    let end = BUFFER_END.load(Ordering::Acquire);
+    if (start..end).contains(&p) {
+        return;
+    }
+
+    unsafe { global::deallocate(p as *mut u8) }
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+mod global {
+    extern crate alloc;
+
+    use super::ALIGN;
+    use alloc::alloc::{alloc, dealloc, Layout};
+    use core::ptr;
+
+    // Each allocation is prefixed with its size, so that it can be
+    // deallocated with the same layout
+    pub fn allocate(size: usize) -> *mut u8 {
+        let layout = match size
+            .checked_add(ALIGN)
+            .map(|total| Layout::from_size_align(total, ALIGN))
+        {
+            Some(Ok(layout)) => layout,
+            _ => return ptr::null_mut(),
+        };
+
+        unsafe {
+            let p = alloc(layout);
+            if p.is_null() {
+                return p;
+            }
+            (p as *mut usize).write(size);
+            p.add(ALIGN)
+        }
+    }
+
+    pub unsafe fn deallocate(p: *mut u8) {
+        let p = p.sub(ALIGN);
+        let size = (p as *const usize).read();
+
+        dealloc(p, Layout::from_size_align_unchecked(size + ALIGN, ALIGN));
+    }
+}
+
+#[cfg(not(any(feature = "std", feature = "alloc", feature = "no-malloc")))]
+mod global {
+    extern "C" {
+        fn malloc(size: usize) -> *mut cty::c_void;
+        fn free(p: *mut cty::c_void);
+    }
+
+    pub fn allocate(size: usize) -> *mut u8 {
+        unsafe { malloc(size) as *mut u8 }
+    }
+
+    pub unsafe fn deallocate(p: *mut u8) {
+        free(p as *mut cty::c_void)
+    }
+}
+
+#[cfg(all(
+    not(any(feature = "std", feature = "alloc")),
+    feature = "no-malloc"
+))]
+mod global {
+    use core::ptr;
+
+    // No allocator is available
+    pub fn allocate(_size: usize) -> *mut u8 {
+        ptr::null_mut()
+    }
+
+    pub unsafe fn deallocate(_p: *mut u8) {}
+}
@@ -0,0 +1,296 @@
+//! Streaming feature extraction into a model input tensor
+//!
+//! Audio arrives in chunks of arbitrary length, whereas the model expects a
+//! spectrogram of the most recent `SLICES` windows. A
+//! [`FeatureProvider`](crate::FeatureProvider) runs each chunk through a
+//! feature generator, and keeps the resulting slices in a ring buffer that
+//! can be written directly into the input tensor.
+
+use crate::tensor::{ElemTypeOf, Tensor};
+use crate::Error;
+
+use super::Frontend;
+
+/// A source of feature slices, generated from 16-bit PCM audio
+pub trait FeatureGenerator {
+    /// The element type of each feature, matching the model input tensor
+    type Feature: ElemTypeOf + Copy + Default;
+
+    /// Returns the number of features generated for each window
+    fn slice_len(&self) -> usize;
+
+    /// Process samples from `samples`, until either a window is complete or
+    /// `samples` is exhausted. When a window is complete, its features are
+    /// written to `slice`, which has length
+    /// [`slice_len`](FeatureGenerator::slice_len).
+    ///
+    /// Returns the number of samples read, and whether `slice` was written
+    fn next_slice(
+        &mut self,
+        samples: &[i16],
+        slice: &mut [Self::Feature],
+    ) -> (usize, bool);
+
+    /// Reset the generator to its initial state
+    fn reset(&mut self);
+}
+
+impl<'a> FeatureGenerator for Frontend<'a> {
+    type Feature = u8;
+
+    fn slice_len(&self) -> usize {
+        self.num_channels()
+    }
+
+    fn next_slice(
+        &mut self,
+        samples: &[i16],
+        slice: &mut [u8],
+    ) -> (usize, bool) {
+        let (num_samples_read, output) = self.process_samples(samples);
+
+        match output {
+            Some(output) => {
+                for (s, &x) in slice.iter_mut().zip(output) {
+                    *s = scale_feature(x);
+                }
+                (num_samples_read, true)
+            }
+            None => (num_samples_read, false),
+        }
+    }
+
+    fn reset(&mut self) {
+        Frontend::reset(self)
+    }
+}
+
+/// Scale a raw frontend output to the range used in the training pipeline.
+/// See
+/// https://github.com/tensorflow/tensorflow/blob/master/tensorflow/lite/micro/examples/micro_speech/micro_features/micro_features_generator.cc
+fn scale_feature(x: u16) -> u8 {
+    let scaled = ((x as i32 * 2550) + 3328) / 6656;
+
+    scaled.clamp(0, 255) as u8
+}
+
+/// Maintains a spectrogram of the most recent `SLICES` feature slices, each
+/// of `CHANNELS` features, generated from streaming audio
+///
+/// ```no_run
+/// # use tfmicro::{FeatureProvider, Frontend};
+/// let frontend = Frontend::new().unwrap();
+/// let mut provider = FeatureProvider::<_, 49, 40>::new(frontend);
+///
+/// let chunk = [0i16; 512];
+/// let new_slices = provider.push_samples(&chunk);
+/// ```
+pub struct FeatureProvider<G, const SLICES: usize, const CHANNELS: usize>
+where
+    G: FeatureGenerator,
+{
+    generator: G,
+    slices: [[G::Feature; CHANNELS]; SLICES],
+    // Index of the oldest slice
+    head: usize,
+}
+
+impl<G, const SLICES: usize, const CHANNELS: usize>
+    FeatureProvider<G, SLICES, CHANNELS>
+where
+    G: FeatureGenerator,
+{
+    /// Create a new FeatureProvider, with every feature initially zero
+    ///
+    /// # Panics
+    ///
+    /// Panics if the generator does not produce `CHANNELS` features per
+    /// slice.
+    pub fn new(generator: G) -> Self {
+        assert_eq!(
+            generator.slice_len(),
+            CHANNELS,
+            "Feature generator slice length does not match CHANNELS"
+        );
+
+        Self {
+            generator,
+            slices: [[Default::default(); CHANNELS]; SLICES],
+            head: 0,
+        }
+    }
+
+    /// Run all of `samples` through the feature generator, replacing the
+    /// oldest slices with any new slices generated. Samples that do not
+    /// complete a window are retained by the generator for the next call.
+    ///
+    /// Returns the number of new slices
+    pub fn push_samples(&mut self, mut samples: &[i16]) -> usize {
+        let mut new_slices = 0;
+
+        while !samples.is_empty() {
+            let slice = &mut self.slices[self.head];
+            let (num_samples_read, complete) =
+                self.generator.next_slice(samples, &mut slice[..]);
+
+            if complete {
+                self.head = (self.head + 1) % SLICES;
+                new_slices += 1;
+            }
+            if num_samples_read == 0 {
+                break;
+            }
+            samples = &samples[num_samples_read..];
+        }
+
+        new_slices
+    }
+
+    /// Returns an iterator over the slices, from oldest to newest
+    pub fn slices(&self) -> impl Iterator<Item = &[G::Feature; CHANNELS]> {
+        let (newer, older) = self.slices.split_at(self.head);
+
+        older.iter().chain(newer.iter())
+    }
+
+    /// Writes the spectrogram into `tensor`, oldest slice first
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InputDataLenMismatch` if the length of `tensor` is
+    /// not `SLICES * CHANNELS`.
+    ///
+    /// Returns `Error::ElementTypeMismatch` if the element type of `tensor`
+    /// does not match the features.
+    pub fn write_to(&self, tensor: &mut Tensor) -> Result<(), Error> {
+        if tensor.element_type() != Some(G::Feature::elem_type_of()) {
+            return Err(Error::ElementTypeMismatch);
+        }
+
+        let data = tensor.as_data_mut::<G::Feature>();
+        if data.len() != SLICES * CHANNELS {
+            return Err(Error::InputDataLenMismatch);
+        }
+
+        for (chunk, slice) in data.chunks_exact_mut(CHANNELS).zip(self.slices())
+        {
+            chunk.copy_from_slice(&slice[..]);
+        }
+
+        Ok(())
+    }
+
+    /// Returns a reference to the feature generator
+    pub fn generator(&self) -> &G {
+        &self.generator
+    }
+
+    /// Reset the feature generator, and set every feature to zero
+    pub fn reset(&mut self) {
+        self.generator.reset();
+        self.slices = [[Default::default(); CHANNELS]; SLICES];
+        self.head = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Generates a slice of constant features every `step` samples
+    struct Counter {
+        step: usize,
+        pending: usize,
+        count: u8,
+    }
+
+    impl FeatureGenerator for Counter {
+        type Feature = u8;
+
+        fn slice_len(&self) -> usize {
+            2
+        }
+
+        fn next_slice(
+            &mut self,
+            samples: &[i16],
+            slice: &mut [u8],
+        ) -> (usize, bool) {
+            let needed = self.step - self.pending;
+
+            if samples.len() < needed {
+                self.pending += samples.len();
+                (samples.len(), false)
+            } else {
+                self.pending = 0;
+                self.count += 1;
+                slice.iter_mut().for_each(|s| *s = self.count);
+                (needed, true)
+            }
+        }
+
+        fn reset(&mut self) {
+            self.pending = 0;
+            self.count = 0;
+        }
+    }
+
+    fn counter() -> Counter {
+        Counter {
+            step: 10,
+            pending: 0,
+            count: 0,
+        }
+    }
+
+    #[test]
+    fn scale() {
+        assert_eq!(scale_feature(0), 0);
+        assert_eq!(scale_feature(100), 38);
+        assert_eq!(scale_feature(u16::MAX), 255);
+    }
+
+    #[test]
+    fn push_samples() {
+        let mut provider = FeatureProvider::<_, 3, 2>::new(counter());
+
+        assert_eq!(provider.push_samples(&[0; 5]), 0);
+        assert_eq!(provider.push_samples(&[0; 5]), 1);
+        assert_eq!(provider.push_samples(&[0; 25]), 2);
+
+        assert!(provider.slices().eq([[0, 0], [1, 1], [2, 2]].iter()));
+
+        // Completes a fourth slice, which replaces the oldest
+        assert_eq!(provider.push_samples(&[0; 5]), 1);
+
+        assert!(provider.slices().eq([[1, 1], [2, 2], [3, 3]].iter()));
+    }
+
+    #[test]
+    fn reset() {
+        let mut provider = FeatureProvider::<_, 3, 2>::new(counter());
+
+        provider.push_samples(&[0; 25]);
+        provider.reset();
+
+        assert!(provider.slices().all(|s| s == &[0, 0]));
+        assert_eq!(provider.push_samples(&[0; 5]), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn channels_mismatch() {
+        let _ = FeatureProvider::<_, 3, 4>::new(counter());
+    }
+
+    #[test]
+    fn frontend_provider() {
+        let frontend = Frontend::new().unwrap();
+        let mut provider = FeatureProvider::<_, 49, 40>::new(frontend);
+
+        // The first window is 30ms, and each subsequent window 20ms
+        assert_eq!(provider.push_samples(&[0; 479]), 0);
+        assert_eq!(provider.push_samples(&[0; 1]), 1);
+        assert_eq!(provider.push_samples(&[0; 640]), 2);
+    }
+}
@@ -0,0 +1,651 @@
+//! Bindings for the audio "frontend" library for feature generation
+//!
+//! This API is in the 'experimental' directory in tensorflow. Therefore
+//! these bindings should also be considered to be experimental.
+//!
+//! See https://github.com/tensorflow/tensorflow/tree/master/tensorflow/lite/experimental/microfrontend/lib
+//!
+//! # Usage
+//!
+//! The default configuration matches the micro_speech example: 30ms windows
+//! every 20ms, 40 channels and a 16kHz sample rate. Other models can use a
+//! [`FrontendConfig`](crate::FrontendConfig) to change any parameter.
+//!
+//! ```
+//! # use tfmicro::{Frontend, FrontendConfig};
+//! let config = FrontendConfig::new()
+//!     .sample_rate(8_000)
+//!     .upper_band_limit(3_800.0)
+//!     .num_channels(49);
+//!
+//! let frontend = Frontend::with_config(&config).unwrap();
+//! ```
+//!
+//! To feed a model from streaming audio, wrap the frontend in a
+//! [`FeatureProvider`](crate::FeatureProvider).
+
+#![allow(non_snake_case)]
+
+use core::slice;
+
+use crate::bindings;
+use crate::{Error, FrontendConfigError};
+use managed::ManagedSlice;
+
+mod allocator;
+mod feature_provider;
+
+pub use feature_provider::{FeatureGenerator, FeatureProvider};
+
+cpp! {{
+    #include "tensorflow/lite/experimental/microfrontend/lib/frontend.h"
+    #include "tensorflow/lite/experimental/microfrontend/lib/frontend_util.h"
+}}
+
+/// Configuration for the audio [`Frontend`](crate::Frontend)
+///
+/// Mirrors each field of the `FrontendConfig` struct in tensorflow, plus
+/// the sample rate of the input audio. Values are set in a builder pattern
+/// and validated when the `Frontend` is created, where they are converted
+/// to the C types used by tensorflow.
+#[derive(Clone, Copy, Debug)]
+pub struct FrontendConfig {
+    sample_rate: u32,
+    window_size_ms: u32,
+    window_step_size_ms: u32,
+    num_channels: usize,
+    upper_band_limit: f32,
+    lower_band_limit: f32,
+    output_scale_shift: u32,
+    smoothing_bits: u32,
+    even_smoothing: f32,
+    odd_smoothing: f32,
+    min_signal_remaining: f32,
+    enable_pcan: bool,
+    pcan_strength: f32,
+    pcan_offset: f32,
+    pcan_gain_bits: u32,
+    enable_log: bool,
+    log_scale_shift: u32,
+}
+
+impl Default for FrontendConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrontendConfig {
+    /// Create a new configuration, with the values used in the
+    /// micro_speech example
+    ///
+    /// See tensorflow/lite/micro/examples/micro_speech/micro_features/micro_features_generator.cc
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 16_000,
+            window_size_ms: 30,
+            window_step_size_ms: 20,
+            num_channels: 40,
+            upper_band_limit: 7500.0,
+            lower_band_limit: 125.0,
+            output_scale_shift: 0,
+            smoothing_bits: 10,
+            even_smoothing: 0.025,
+            odd_smoothing: 0.06,
+            min_signal_remaining: 0.05,
+            enable_pcan: true,
+            pcan_strength: 0.95,
+            pcan_offset: 80.0,
+            pcan_gain_bits: 21,
+            enable_log: true,
+            log_scale_shift: 6,
+        }
+    }
+
+    /// Sample rate of the input audio, in Hz
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+    /// Duration of each window, in milliseconds
+    pub fn window_size_ms(mut self, size_ms: u32) -> Self {
+        self.window_size_ms = size_ms;
+        self
+    }
+    /// Step between successive windows, in milliseconds
+    pub fn window_step_size_ms(mut self, step_size_ms: u32) -> Self {
+        self.window_step_size_ms = step_size_ms;
+        self
+    }
+    /// Number of filterbank channels. This is the number of features
+    /// generated for each window
+    pub fn num_channels(mut self, num_channels: usize) -> Self {
+        self.num_channels = num_channels;
+        self
+    }
+    /// Upper frequency limit of the filterbank, in Hz
+    pub fn upper_band_limit(mut self, upper_band_limit: f32) -> Self {
+        self.upper_band_limit = upper_band_limit;
+        self
+    }
+    /// Lower frequency limit of the filterbank, in Hz
+    pub fn lower_band_limit(mut self, lower_band_limit: f32) -> Self {
+        self.lower_band_limit = lower_band_limit;
+        self
+    }
+    /// Output scale shift of the filterbank
+    pub fn output_scale_shift(mut self, output_scale_shift: u32) -> Self {
+        self.output_scale_shift = output_scale_shift;
+        self
+    }
+    /// Number of fractional bits used by the noise reduction smoothing
+    pub fn smoothing_bits(mut self, smoothing_bits: u32) -> Self {
+        self.smoothing_bits = smoothing_bits;
+        self
+    }
+    /// Noise reduction smoothing coefficient for even channels
+    pub fn even_smoothing(mut self, even_smoothing: f32) -> Self {
+        self.even_smoothing = even_smoothing;
+        self
+    }
+    /// Noise reduction smoothing coefficient for odd channels
+    pub fn odd_smoothing(mut self, odd_smoothing: f32) -> Self {
+        self.odd_smoothing = odd_smoothing;
+        self
+    }
+    /// Fraction of the signal that is always preserved by noise reduction
+    pub fn min_signal_remaining(mut self, min_signal_remaining: f32) -> Self {
+        self.min_signal_remaining = min_signal_remaining;
+        self
+    }
+    /// Enable Per-Channel Amplitude Normalization (PCAN)
+    pub fn enable_pcan(mut self, enable_pcan: bool) -> Self {
+        self.enable_pcan = enable_pcan;
+        self
+    }
+    /// PCAN gain control strength
+    pub fn pcan_strength(mut self, strength: f32) -> Self {
+        self.pcan_strength = strength;
+        self
+    }
+    /// PCAN gain control offset
+    pub fn pcan_offset(mut self, offset: f32) -> Self {
+        self.pcan_offset = offset;
+        self
+    }
+    /// Number of fractional bits in the PCAN gain
+    pub fn pcan_gain_bits(mut self, gain_bits: u32) -> Self {
+        self.pcan_gain_bits = gain_bits;
+        self
+    }
+    /// Enable log scaling of the output
+    pub fn enable_log(mut self, enable_log: bool) -> Self {
+        self.enable_log = enable_log;
+        self
+    }
+    /// Scale shift applied to the log scaled output
+    pub fn log_scale_shift(mut self, scale_shift: u32) -> Self {
+        self.log_scale_shift = scale_shift;
+        self
+    }
+
+    /// Check all parameters of this configuration
+    ///
+    /// Counts must fit in a C `int`, and shifts must be less than 32, as
+    /// tensorflow uses them to shift `int32_t` values.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first parameter that is invalid
+    pub fn validate(&self) -> Result<(), FrontendConfigError> {
+        use FrontendConfigError::*;
+
+        let in_unit_range = |x: f32| (0.0..=1.0).contains(&x);
+        let non_negative = |x: f32| x.is_finite() && x >= 0.0;
+        let is_shift = |x: u32| x < 32;
+        let nyquist = self.sample_rate as f32 / 2.0;
+        // Samples in `ms` milliseconds
+        let samples =
+            |ms: u32| u64::from(ms) * u64::from(self.sample_rate) / 1000;
+
+        if self.sample_rate == 0 || self.sample_rate > i32::MAX as u32 {
+            return Err(SampleRate);
+        }
+        if samples(self.window_size_ms) == 0 {
+            return Err(WindowSize);
+        }
+        if samples(self.window_step_size_ms) == 0 {
+            return Err(WindowStepSize);
+        }
+        if self.num_channels == 0 || self.num_channels > i32::MAX as usize {
+            return Err(NumChannels);
+        }
+        let lower = self.lower_band_limit;
+        let upper = self.upper_band_limit;
+        if !(lower >= 0.0 && lower < upper && upper <= nyquist) {
+            return Err(BandLimits);
+        }
+        if !is_shift(self.output_scale_shift) {
+            return Err(OutputScaleShift);
+        }
+        if !is_shift(self.smoothing_bits) {
+            return Err(SmoothingBits);
+        }
+        if !in_unit_range(self.even_smoothing)
+            || !in_unit_range(self.odd_smoothing)
+        {
+            return Err(Smoothing);
+        }
+        if !in_unit_range(self.min_signal_remaining) {
+            return Err(MinSignalRemaining);
+        }
+        if !non_negative(self.pcan_strength) || !non_negative(self.pcan_offset)
+        {
+            return Err(PcanGainControl);
+        }
+        if !is_shift(self.pcan_gain_bits) {
+            return Err(PcanGainBits);
+        }
+        if !is_shift(self.log_scale_shift) {
+            return Err(LogScaleShift);
+        }
+
+        Ok(())
+    }
+
+    /// The `FrontendConfig` struct used by tensorflow. The configuration
+    /// must be valid, so that every value fits its C type
+    fn c_config(&self) -> bindings::FrontendConfig {
+        let mut config: bindings::FrontendConfig = Default::default();
+
+        config.window.size_ms = self.window_size_ms as usize;
+        config.window.step_size_ms = self.window_step_size_ms as usize;
+        config.filterbank.num_channels = self.num_channels as i32;
+        config.filterbank.lower_band_limit = self.lower_band_limit;
+        config.filterbank.upper_band_limit = self.upper_band_limit;
+        config.filterbank.output_scale_shift = self.output_scale_shift as i32;
+        config.noise_reduction.smoothing_bits = self.smoothing_bits as i32;
+        config.noise_reduction.even_smoothing = self.even_smoothing;
+        config.noise_reduction.odd_smoothing = self.odd_smoothing;
+        config.noise_reduction.min_signal_remaining = self.min_signal_remaining;
+        config.pcan_gain_control.enable_pcan = self.enable_pcan as i32;
+        config.pcan_gain_control.strength = self.pcan_strength;
+        config.pcan_gain_control.offset = self.pcan_offset;
+        config.pcan_gain_control.gain_bits = self.pcan_gain_bits as i32;
+        config.log_scale.enable_log = self.enable_log as i32;
+        config.log_scale.scale_shift = self.log_scale_shift as i32;
+
+        config
+    }
+}
+
+/// Bindings for the audio "frontend" library for feature generation
+///
+/// By default the frontend state is allocated on the heap, which requires
+/// the `std` or `alloc` feature. On targets without an allocator use
+/// [`with_config_in`](Frontend::with_config_in) to provide a buffer for the
+/// state instead.
+pub struct Frontend<'a> {
+    state: bindings::FrontendState,
+    num_channels: usize,
+    // Caller-provided memory that the state points into, if any
+    buffer: Option<ManagedSlice<'a, u8>>,
+    buffer_used: usize,
+}
+
+// Frontend allocates memory on the heap or in a buffer that it owns or
+// borrows mutably, therefore the raw pointers that in contains are Send
+unsafe impl Send for Frontend<'_> {}
+
+impl Frontend<'static> {
+    /// Create new frontend state, with the default
+    /// [`FrontendConfig`](crate::FrontendConfig)
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FrontendInitError` if the frontend state could not be
+    /// allocated.
+    pub fn new() -> Result<Self, Error> {
+        Self::with_config(&FrontendConfig::new())
+    }
+
+    /// Create new frontend state from a
+    /// [`FrontendConfig`](crate::FrontendConfig). The state is allocated on
+    /// the heap.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidFrontendConfig` if the configuration failed
+    /// validation.
+    ///
+    /// Returns `Error::FrontendInitError` if the frontend state could not be
+    /// allocated. Without the `std` feature, this is also returned if
+    /// another frontend is being created at the same time, for example by
+    /// an interrupt handler.
+    pub fn with_config(config: &FrontendConfig) -> Result<Self, Error> {
+        config.validate().map_err(Error::InvalidFrontendConfig)?;
+
+        let mut state: bindings::FrontendState = Default::default();
+
+        let populated = allocator::with_global_allocator(|| {
+            let populated = populate_state(config, &mut state);
+            if !populated {
+                // Free any partial allocations
+                free_state_contents(&mut state);
+            }
+            populated
+        })
+        .unwrap_or(false);
+
+        if populated {
+            Ok(Self {
+                state,
+                num_channels: config.num_channels,
+                buffer: None,
+                buffer_used: 0,
+            })
+        } else {
+            Err(Error::FrontendInitError)
+        }
+    }
+}
+
+impl<'a> Frontend<'a> {
+    /// Create new frontend state from a
+    /// [`FrontendConfig`](crate::FrontendConfig), with the state allocated
+    /// in `buffer`. No heap memory is used.
+    ///
+    /// The number of bytes required depends on the configuration. Call
+    /// [`buffer_used_bytes`](Frontend::buffer_used_bytes) to find the number
+    /// of bytes actually used.
+    ///
+    /// ```
+    /// # use tfmicro::{Frontend, FrontendConfig};
+    /// let mut buffer = [0u8; 16 * 1024];
+    ///
+    /// let frontend =
+    ///     Frontend::with_config_in(&FrontendConfig::new(), &mut buffer[..])
+    ///         .unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidFrontendConfig` if the configuration failed
+    /// validation.
+    ///
+    /// Returns `Error::FrontendInitError` if `buffer` is too small. Without
+    /// the `std` feature, this is also returned if another frontend is
+    /// being created at the same time, for example by an interrupt handler.
+    pub fn with_config_in<B>(
+        config: &FrontendConfig,
+        buffer: B,
+    ) -> Result<Self, Error>
+    where
+        B: Into<ManagedSlice<'a, u8>>,
+    {
+        config.validate().map_err(Error::InvalidFrontendConfig)?;
+
+        let mut state: bindings::FrontendState = Default::default();
+        let mut buffer = buffer.into();
+
+        let (populated, buffer_used) =
+            allocator::with_buffer(&mut buffer, || {
+                populate_state(config, &mut state)
+            })
+            .unwrap_or((false, 0));
+
+        if populated {
+            Ok(Self {
+                state,
+                num_channels: config.num_channels,
+                buffer: Some(buffer),
+                buffer_used,
+            })
+        } else {
+            Err(Error::FrontendInitError)
+        }
+    }
+
+    /// Returns the number of bytes of the buffer passed to
+    /// [`with_config_in`](Frontend::with_config_in) that are used by the
+    /// frontend state. Returns zero if the state is on the heap.
+    pub fn buffer_used_bytes(&self) -> usize {
+        self.buffer_used
+    }
+
+    /// Returns the number of features generated for each window
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    /// Generates micro_features objects
+    ///
+    /// Returns num_samples_read
+    ///
+    /// # Panics
+    ///
+    /// Panics if `input` does not complete a window, or if the length of
+    /// `output` is not equal to the number of channels.
+    pub fn generate_micro_features(
+        &mut self,
+        input: &[i16],
+        output: &mut [u16],
+    ) -> usize {
+        let (num_samples_read, frontend_output) = self.process_samples(input);
+        let frontend_output = frontend_output.unwrap_or(&[]);
+
+        assert_eq!(frontend_output.len(), output.len());
+
+        // Copy out the raw data, this still needs to be scaled
+        output.clone_from_slice(frontend_output);
+
+        num_samples_read
+    }
+
+    /// Runs FrontendProcessSamples on `input`, which processes samples
+    /// until a window is complete or `input` is exhausted
+    ///
+    /// Returns num_samples_read, and the raw features if a window was
+    /// completed
+    pub fn process_samples(
+        &mut self,
+        input: &[i16],
+    ) -> (usize, Option<&[u16]>) {
+        let micro_features_state_ref = &mut self.state;
+
+        let len = input.len();
+        let input = input.as_ptr();
+
+        let mut num_samples_read = 0usize;
+        let num_samples_read_ref = &mut num_samples_read;
+
+        let frontend_output = cpp! (unsafe [
+            micro_features_state_ref as "FrontendState*",
+            input as "int16_t*",
+            len as "size_t",
+            num_samples_read_ref as "size_t*"
+        ] -> bindings::FrontendOutput as "FrontendOutput" {
+            return FrontendProcessSamples(
+                micro_features_state_ref,
+                input,
+                len,
+                num_samples_read_ref
+            );
+        });
+
+        let frontend_output_slice = if frontend_output.size > 0 {
+            Some(unsafe {
+                slice::from_raw_parts(
+                    frontend_output.values,
+                    frontend_output.size,
+                )
+            })
+        } else {
+            None
+        };
+
+        (num_samples_read, frontend_output_slice)
+    }
+
+    /// Reset the frontend to its initial state, as if no samples had been
+    /// processed
+    ///
+    /// This discards any partial window and the noise estimates, and can be
+    /// used to restart feature extraction between utterances.
+    pub fn reset(&mut self) {
+        let state_ref = &mut self.state;
+
+        cpp!(unsafe [state_ref as "FrontendState*"] {
+            FrontendReset(state_ref);
+        });
+    }
+}
+
+impl Drop for Frontend<'_> {
+    fn drop(&mut self) {
+        // Free the heap memory allocated by FrontendPopulateState. State in
+        // a caller-provided buffer is released with the buffer. Freeing does
+        // not need the allocator lock, so that a frontend can always be
+        // dropped
+        if self.buffer.is_none() {
+            free_state_contents(&mut self.state);
+        }
+    }
+}
+
+/// Calls FrontendPopulateState. Returns true on success
+fn populate_state(
+    config: &FrontendConfig,
+    state: &mut bindings::FrontendState,
+) -> bool {
+    // Validated to fit in an `int`
+    let sample_rate = config.sample_rate as i32;
+    let config = config.c_config();
+
+    let result = cpp! (unsafe [
+        config as "FrontendConfig",
+        state as "FrontendState*",
+        sample_rate as "int"
+    ] -> u32 as "int" {
+        return FrontendPopulateState(&config,
+                                     state,
+                                     sample_rate);
+    });
+
+    result == 1
+}
+
+/// Calls FrontendFreeStateContents
+fn free_state_contents(state: &mut bindings::FrontendState) {
+    cpp!(unsafe [state as "FrontendState*"] {
+        FrontendFreeStateContents(state);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(FrontendConfig::new().validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_config() {
+        let config = FrontendConfig::new().sample_rate(8_000);
+
+        // 7500Hz upper band limit is above the nyquist frequency
+        assert_eq!(config.validate(), Err(FrontendConfigError::BandLimits));
+        assert_eq!(
+            config
+                .upper_band_limit(3_800.0)
+                .window_step_size_ms(0)
+                .validate(),
+            Err(FrontendConfigError::WindowStepSize)
+        );
+
+        // Shifts of an `int32_t` by 32 or more bits are undefined in C
+        let config = FrontendConfig::new();
+        assert_eq!(config.smoothing_bits(31).validate(), Ok(()));
+        assert_eq!(
+            config.smoothing_bits(32).validate(),
+            Err(FrontendConfigError::SmoothingBits)
+        );
+        assert_eq!(
+            config.num_channels(0).validate(),
+            Err(FrontendConfigError::NumChannels)
+        );
+    }
+
+    #[test]
+    fn frontend_with_config() {
+        let config = FrontendConfig::new()
+            .sample_rate(8_000)
+            .upper_band_limit(3_800.0)
+            .num_channels(49);
+
+        let mut frontend = Frontend::with_config(&config).unwrap();
+
+        // 30ms at 8kHz
+        let audio = [0i16; 240];
+        let mut output = [0u16; 49];
+        frontend.generate_micro_features(&audio, &mut output);
+    }
+
+    #[test]
+    fn frontend_reset() {
+        // 30ms at 16kHz
+        let audio: [i16; 480] = {
+            let mut audio = [0; 480];
+            for (n, a) in audio.iter_mut().enumerate() {
+                *a = ((n as i16 % 32) - 16) * 512;
+            }
+            audio
+        };
+
+        let mut frontend = Frontend::new().unwrap();
+        let mut first = [0u16; 40];
+        let mut second = [0u16; 40];
+
+        frontend.generate_micro_features(&audio, &mut first);
+        frontend.reset();
+        frontend.generate_micro_features(&audio, &mut second);
+
+        // After a reset the same input gives the same output
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn frontend_with_buffer() {
+        let mut buffer = [0u8; 16 * 1024];
+
+        let mut frontend =
+            Frontend::with_config_in(&FrontendConfig::new(), &mut buffer[..])
+                .unwrap();
+        assert!(frontend.buffer_used_bytes() > 0);
+
+        let audio = [0i16; 480];
+        let mut output = [0u16; 40];
+        frontend.generate_micro_features(&audio, &mut output);
+    }
+
+    #[test]
+    fn frontend_with_small_buffer() {
+        let mut buffer = [0u8; 64];
+
+        assert_eq!(
+            Frontend::with_config_in(&FrontendConfig::new(), &mut buffer[..])
+                .err(),
+            Some(Error::FrontendInitError)
+        );
+    }
+
+    #[test]
+    fn frontend_drop() {
+        // Recreating the frontend must not leak memory
+        for _ in 0..100 {
+            let _ = Frontend::new().unwrap();
+        }
+    }
+}
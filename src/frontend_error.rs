@@ -0,0 +1,35 @@
+//! Validation errors of the audio frontend configuration
+//!
+//! Kept outside the `frontend` module, which needs the `tensorflow` feature,
+//! so that `Error::InvalidFrontendConfig` exists with any set of features.
+
+/// A parameter of a [`FrontendConfig`](crate::FrontendConfig) that failed
+/// validation
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrontendConfigError {
+    /// The sample rate must be greater than zero, and fit in a C `int`
+    SampleRate,
+    /// The window must contain at least one sample
+    WindowSize,
+    /// The window step must contain at least one sample
+    WindowStepSize,
+    /// The number of filterbank channels must be greater than zero, and fit
+    /// in a C `int`
+    NumChannels,
+    /// The band limits must satisfy 0 <= lower < upper <= sample_rate / 2
+    BandLimits,
+    /// The output scale shift must be less than 32
+    OutputScaleShift,
+    /// The number of smoothing bits must be less than 32
+    SmoothingBits,
+    /// The smoothing coefficients must be in the range [0, 1]
+    Smoothing,
+    /// The minimum signal remaining must be in the range [0, 1]
+    MinSignalRemaining,
+    /// The PCAN strength and offset must be finite and not negative
+    PcanGainControl,
+    /// The number of PCAN gain bits must be less than 32
+    PcanGainBits,
+    /// The log scale shift must be less than 32
+    LogScaleShift,
+}
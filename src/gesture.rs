@@ -0,0 +1,302 @@
+//! Components for gesture recognition from accelerometer data
+//!
+//! An [`AccelerometerBuffer`](crate::AccelerometerBuffer) keeps the most
+//! recent accelerometer samples, and writes them into the model input
+//! tensor. A [`GesturePredictor`](crate::GesturePredictor) turns the model
+//! outputs into gesture events, once a gesture has been predicted
+//! confidently for a number of consecutive inferences.
+//!
+//! These are ports of the accelerometer handler and `PredictGesture` from
+//! the magic_wand example. See
+//! tensorflow/lite/micro/examples/magic_wand/gesture_predictor.cc
+//!
+//! ```no_run
+//! # use tfmicro::{AccelerometerBuffer, GesturePredictor};
+//! // Input tensor with dimensions [1, 128, 3, 1]
+//! let mut buffer = AccelerometerBuffer::<128>::new();
+//! // Wing, ring and slope gestures, followed by a negative class
+//! let mut predictor = GesturePredictor::new([15, 12, 10]);
+//!
+//! buffer.push([0.0, 0.0, 1000.0]);
+//!
+//! if buffer.is_full() {
+//!     // buffer.write_to(interpreter.input_tensor(0)).unwrap();
+//!     // interpreter.invoke().unwrap();
+//!     let scores = [0.0, 0.0, 0.9, 0.1];
+//!
+//!     if let Some(gesture) = predictor.predict(&scores).unwrap() {
+//!         // Gesture `gesture` recognised
+//!     }
+//! }
+//! ```
+
+use crate::tensor::{ElementType, Tensor};
+use crate::Error;
+
+/// Keeps the most recent `SAMPLES` accelerometer samples, each with x, y and
+/// z components
+///
+/// Samples should be pushed at the rate the model was trained with, 25Hz
+/// for the magic_wand example.
+pub struct AccelerometerBuffer<const SAMPLES: usize> {
+    samples: [[f32; 3]; SAMPLES],
+    // Index of the oldest sample
+    head: usize,
+    len: usize,
+}
+
+impl<const SAMPLES: usize> Default for AccelerometerBuffer<SAMPLES> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SAMPLES: usize> AccelerometerBuffer<SAMPLES> {
+    /// Create a new AccelerometerBuffer, initially empty
+    pub fn new() -> Self {
+        Self {
+            samples: [[0.0; 3]; SAMPLES],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Add a sample, replacing the oldest sample if the buffer is full
+    pub fn push(&mut self, sample: [f32; 3]) {
+        let index = (self.head + self.len) % SAMPLES;
+        self.samples[index] = sample;
+
+        if self.len < SAMPLES {
+            self.len += 1;
+        } else {
+            self.head = (self.head + 1) % SAMPLES;
+        }
+    }
+
+    /// Add samples from a flat slice of x, y, z components. Any trailing
+    /// components that do not form a whole sample are ignored
+    pub fn push_flat(&mut self, data: &[f32]) {
+        for c in data.chunks_exact(3) {
+            self.push([c[0], c[1], c[2]]);
+        }
+    }
+
+    /// Returns the number of samples in the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return whether there are zero samples
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return whether the buffer contains `SAMPLES` samples. Inference
+    /// should only be run once the buffer is full
+    pub fn is_full(&self) -> bool {
+        self.len == SAMPLES
+    }
+
+    /// Returns an iterator over the samples, from oldest to newest
+    pub fn samples(&self) -> impl Iterator<Item = &[f32; 3]> {
+        (0..self.len).map(move |i| &self.samples[(self.head + i) % SAMPLES])
+    }
+
+    /// Writes the samples into `tensor`, oldest sample first. If the
+    /// buffer is not yet full, the remainder of the tensor is set to zero
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InputDataLenMismatch` if the length of `tensor` is
+    /// not `SAMPLES * 3`.
+    ///
+    /// Returns `Error::ElementTypeMismatch` if the element type of `tensor`
+    /// is not `f32`.
+    pub fn write_to(&self, tensor: &mut Tensor) -> Result<(), Error> {
+        if tensor.element_type() != Some(ElementType::Float32) {
+            return Err(Error::ElementTypeMismatch);
+        }
+
+        let data = tensor.as_data_mut::<f32>();
+        if data.len() != SAMPLES * 3 {
+            return Err(Error::InputDataLenMismatch);
+        }
+
+        let mut samples = self.samples();
+        for chunk in data.chunks_exact_mut(3) {
+            match samples.next() {
+                Some(sample) => chunk.copy_from_slice(&sample[..]),
+                None => chunk.iter_mut().for_each(|x| *x = 0.0),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove all samples
+    pub fn reset(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+}
+
+/// Turns the scores output by a gesture model into gesture events
+///
+/// The model outputs a score for each of `GESTURES` gestures, followed by a
+/// score for no gesture. A gesture is reported once its score exceeds the
+/// score threshold for more than the consecutive inference threshold for
+/// that gesture.
+pub struct GesturePredictor<const GESTURES: usize> {
+    consecutive_thresholds: [u32; GESTURES],
+    score_threshold: f32,
+
+    last_prediction: Option<usize>,
+    continuous_count: u32,
+}
+
+impl<const GESTURES: usize> GesturePredictor<GESTURES> {
+    /// Create a new GesturePredictor, with a consecutive inference
+    /// threshold for each gesture
+    ///
+    /// The score threshold is initially 0.8, as in the magic_wand example
+    pub fn new(consecutive_thresholds: [u32; GESTURES]) -> Self {
+        Self {
+            consecutive_thresholds,
+            score_threshold: 0.8,
+
+            last_prediction: None,
+            continuous_count: 0,
+        }
+    }
+
+    /// Minimum score for a gesture to be predicted by a single inference
+    pub fn score_threshold(mut self, score_threshold: f32) -> Self {
+        self.score_threshold = score_threshold;
+        self
+    }
+
+    /// Process the scores from the latest inference
+    ///
+    /// Returns `Some(gesture)` if gesture index `gesture` was recognised
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InputDataLenMismatch` if the length of `scores` is
+    /// not `GESTURES + 1`.
+    pub fn predict(&mut self, scores: &[f32]) -> Result<Option<usize>, Error> {
+        if scores.len() != GESTURES + 1 {
+            return Err(Error::InputDataLenMismatch);
+        }
+
+        // Find whichever gesture is above the score threshold
+        let prediction = scores[..GESTURES]
+            .iter()
+            .rposition(|&score| score > self.score_threshold);
+
+        let prediction = match prediction {
+            Some(prediction) => prediction,
+            None => {
+                // No gesture was detected
+                self.continuous_count = 0;
+                self.last_prediction = None;
+                return Ok(None);
+            }
+        };
+
+        if self.last_prediction == Some(prediction) {
+            self.continuous_count += 1;
+        } else {
+            self.continuous_count = 0;
+        }
+        self.last_prediction = Some(prediction);
+
+        // If we haven't yet had enough consecutive matches for this
+        // gesture, report no gesture
+        if self.continuous_count < self.consecutive_thresholds[prediction] {
+            return Ok(None);
+        }
+
+        // Otherwise, we've seen a gesture. Start counting again
+        self.continuous_count = 0;
+        self.last_prediction = None;
+
+        Ok(Some(prediction))
+    }
+
+    /// Forget all previous predictions
+    pub fn reset(&mut self) {
+        self.last_prediction = None;
+        self.continuous_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accelerometer_buffer() {
+        let mut buffer = AccelerometerBuffer::<3>::new();
+        assert!(buffer.is_empty());
+
+        buffer.push([1.0, 1.0, 1.0]);
+        buffer.push([2.0, 2.0, 2.0]);
+        assert_eq!(buffer.len(), 2);
+        assert!(!buffer.is_full());
+
+        buffer.push_flat(&[3.0, 3.0, 3.0, 4.0, 4.0, 4.0, 5.0]);
+        assert!(buffer.is_full());
+
+        // The oldest sample has been replaced
+        assert!(buffer.samples().eq([
+            [2.0, 2.0, 2.0],
+            [3.0, 3.0, 3.0],
+            [4.0, 4.0, 4.0]
+        ]
+        .iter()));
+
+        buffer.reset();
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn gesture_predictor() {
+        let mut predictor = GesturePredictor::new([2, 1, 0]);
+
+        let ring = [0.1, 0.9, 0.0, 0.0];
+        let slope = [0.0, 0.1, 0.9, 0.0];
+        let negative = [0.1, 0.1, 0.1, 0.7];
+
+        // A single confident inference is sufficient for slope
+        assert_eq!(predictor.predict(&slope), Ok(Some(2)));
+
+        // Ring requires two consecutive inferences
+        assert_eq!(predictor.predict(&ring), Ok(None));
+        assert_eq!(predictor.predict(&negative), Ok(None));
+        assert_eq!(predictor.predict(&ring), Ok(None));
+        assert_eq!(predictor.predict(&ring), Ok(Some(1)));
+
+        // Counting starts again after a gesture
+        assert_eq!(predictor.predict(&ring), Ok(None));
+    }
+
+    #[test]
+    fn gesture_predictor_score_threshold() {
+        let mut predictor = GesturePredictor::new([0, 0, 0]);
+        assert_eq!(predictor.predict(&[0.0, 0.7, 0.0, 0.3]), Ok(None));
+
+        let mut predictor =
+            GesturePredictor::new([0, 0, 0]).score_threshold(0.6);
+        assert_eq!(predictor.predict(&[0.0, 0.7, 0.0, 0.3]), Ok(Some(1)));
+    }
+
+    #[test]
+    fn gesture_predictor_bad_input_length() {
+        let mut predictor = GesturePredictor::new([0, 0, 0]);
+
+        assert_eq!(
+            predictor.predict(&[0.0, 0.9, 0.0]),
+            Err(Error::InputDataLenMismatch)
+        );
+    }
+}
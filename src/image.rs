@@ -0,0 +1,582 @@
+//! Image preprocessing for vision models
+//!
+//! An [`Image`](crate::image::Image) is a borrowed view of a camera frame.
+//! It can be cropped, and then resized, converted and normalized directly
+//! into an input tensor with
+//! [`write_to`](crate::image::Image::write_to). The output size and number
+//! of channels are taken from the dimensions of the tensor. No allocation
+//! is required.
+//!
+//! ```no_run
+//! # use tfmicro::image::{Image, Normalization, PixelFormat, Resize};
+//! # use tfmicro::{include_model, Model, MicroInterpreter, MutableOpResolver};
+//! # let model = include_model!("../examples/models/person_detection_grayscale.tflite");
+//! # let model = Model::from_buffer(model).unwrap();
+//! # let mut arena = [0u8; 93 * 1024];
+//! # let resolver = MutableOpResolver::<3>::empty()
+//! #     .depthwise_conv_2d().conv_2d().average_pool_2d();
+//! # let mut interpreter =
+//! #     MicroInterpreter::new(&model, &resolver, &mut arena[..]).unwrap();
+//! // A 160x120 frame from the camera
+//! let frame = [0u8; 160 * 120 * 2];
+//! let image = Image::new(&frame, 160, 120, PixelFormat::Rgb565).unwrap();
+//!
+//! // Take a square from the centre of the frame, and scale it to the
+//! // 96x96 grayscale input tensor
+//! image
+//!     .crop(20, 0, 120, 120)
+//!     .unwrap()
+//!     .write_to(
+//!         interpreter.input_tensor(0),
+//!         Resize::Bilinear,
+//!         Normalization::None,
+//!     )
+//!     .unwrap();
+//! ```
+
+use core::convert::TryInto;
+
+use crate::tensor::{ElementType, Tensor, TensorInfo};
+use crate::Error;
+
+/// Layout of the pixels in an image buffer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelFormat {
+    /// One byte per pixel
+    Grayscale,
+    /// Two bytes per pixel, big-endian, as output by most camera modules
+    Rgb565,
+    /// Two bytes per pixel, little-endian
+    Rgb565Le,
+    /// Four bytes for each pair of pixels, in the order Y0 U Y1 V
+    Yuv422,
+    /// Three bytes per pixel, in the order R G B
+    Rgb888,
+}
+
+impl PixelFormat {
+    /// Number of bytes used by a row of `width` pixels
+    fn row_bytes(self, width: usize) -> usize {
+        match self {
+            PixelFormat::Grayscale => width,
+            PixelFormat::Rgb565 | PixelFormat::Rgb565Le => width * 2,
+            PixelFormat::Yuv422 => width * 2,
+            PixelFormat::Rgb888 => width * 3,
+        }
+    }
+}
+
+/// Method used to resize an image
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Resize {
+    /// Take the nearest source pixel. Fastest
+    Nearest,
+    /// Linearly interpolate between the four nearest source pixels
+    Bilinear,
+}
+
+/// Mapping from pixel values to the real values expected by the model
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Normalization {
+    /// Pixel values in the range 0 to 255 are written unchanged. For `i8`
+    /// tensors they are offset by -128
+    None,
+    /// Real values are `(pixel - mean) / std`. For quantized tensors these
+    /// are then quantized using the tensor's quantization parameters
+    MeanStd { mean: f32, std: f32 },
+}
+
+/// A borrowed view of an image, or of a region of an image
+#[derive(Clone, Copy, Debug)]
+pub struct Image<'a> {
+    data: &'a [u8],
+    format: PixelFormat,
+    // Bytes between the start of successive rows
+    stride: usize,
+    // Region of the underlying buffer
+    x0: usize,
+    y0: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Image<'a> {
+    /// Create a view of the image in `data`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InputDataLenMismatch` if the length of `data` does
+    /// not match the dimensions and pixel format.
+    ///
+    /// Returns `Error::InvalidImageDimensions` if either dimension is zero,
+    /// or if the width of a `Yuv422` image is odd.
+    pub fn new(
+        data: &'a [u8],
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+    ) -> Result<Self, Error> {
+        if width == 0
+            || height == 0
+            || (format == PixelFormat::Yuv422 && width & 1 != 0)
+        {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let stride = format.row_bytes(width);
+        if stride.checked_mul(height) != Some(data.len()) {
+            return Err(Error::InputDataLenMismatch);
+        }
+
+        Ok(Self {
+            data,
+            format,
+            stride,
+            x0: 0,
+            y0: 0,
+            width,
+            height,
+        })
+    }
+
+    /// Width of the image in pixels
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the image in pixels
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Pixel format of the image
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Returns a view of the region of this image with its top left corner
+    /// at (`x`, `y`)
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageDimensions` if the region is empty, or
+    /// extends outside this image.
+    pub fn crop(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, Error> {
+        let fits = |start: usize, len: usize, limit: usize| {
+            len > 0
+                && matches!(start.checked_add(len), Some(end) if end <= limit)
+        };
+
+        if fits(x, width, self.width) && fits(y, height, self.height) {
+            Ok(Self {
+                x0: self.x0 + x,
+                y0: self.y0 + y,
+                width,
+                height,
+                ..*self
+            })
+        } else {
+            Err(Error::InvalidImageDimensions)
+        }
+    }
+
+    /// Returns the R, G and B components of the pixel at (`x`, `y`)
+    pub fn rgb(&self, x: usize, y: usize) -> [u8; 3] {
+        let x = self.x0 + x;
+        let row = &self.data[(self.y0 + y) * self.stride..];
+
+        match self.format {
+            PixelFormat::Grayscale => [row[x]; 3],
+            PixelFormat::Rgb565 => {
+                rgb565(u16::from_be_bytes([row[2 * x], row[2 * x + 1]]))
+            }
+            PixelFormat::Rgb565Le => {
+                rgb565(u16::from_le_bytes([row[2 * x], row[2 * x + 1]]))
+            }
+            PixelFormat::Yuv422 => {
+                let pair = &row[(x & !1) * 2..][..4];
+                let luma = if x & 1 == 0 { pair[0] } else { pair[2] };
+                yuv_to_rgb(luma, pair[1], pair[3])
+            }
+            PixelFormat::Rgb888 => [row[3 * x], row[3 * x + 1], row[3 * x + 2]],
+        }
+    }
+
+    /// Returns the luma of the pixel at (`x`, `y`), using the ITU-R BT.601
+    /// weights
+    pub fn luma(&self, x: usize, y: usize) -> u8 {
+        match self.format {
+            PixelFormat::Grayscale => {
+                self.data[(self.y0 + y) * self.stride + self.x0 + x]
+            }
+            PixelFormat::Yuv422 => {
+                let x = self.x0 + x;
+                self.data[(self.y0 + y) * self.stride + 2 * x]
+            }
+            _ => {
+                let [r, g, b] = self.rgb(x, y);
+                ((77 * r as u32 + 150 * g as u32 + 29 * b as u32) >> 8) as u8
+            }
+        }
+    }
+
+    /// Resize this image to `width` x `height` with `channels` channels,
+    /// and write the pixels into `output` in row-major order. One channel
+    /// is grayscale, and three channels are R, G and B
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InputDataLenMismatch` if the length of `output` is
+    /// not `width * height * channels`.
+    ///
+    /// Returns `Error::UnsupportedTensorShape` if `channels` is not 1 or 3.
+    pub fn resize_into(
+        &self,
+        output: &mut [u8],
+        width: usize,
+        height: usize,
+        channels: usize,
+        resize: Resize,
+    ) -> Result<(), Error> {
+        if channels != 1 && channels != 3 {
+            return Err(Error::UnsupportedTensorShape);
+        }
+        if output.len() != width * height * channels {
+            return Err(Error::InputDataLenMismatch);
+        }
+
+        self.resample(width, height, channels, resize, |i, value| {
+            output[i] = round(value).clamp(0, 255) as u8
+        });
+
+        Ok(())
+    }
+
+    /// Resize, convert and normalize this image into `tensor`
+    ///
+    /// The tensor must have dimensions `[1, height, width, channels]` or
+    /// `[height, width, channels]`, where `channels` is 1 for grayscale or
+    /// 3 for RGB. The element type must be `f32`, `u8` or `i8`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnsupportedTensorShape` if the dimensions of
+    /// `tensor` are not as above.
+    ///
+    /// Returns `Error::ElementTypeMismatch` if the element type of `tensor`
+    /// is not supported.
+    ///
+    /// Returns an Error if the underlying tensor cannot be represented by a
+    /// [`TensorInfo`](crate::tensor::TensorInfo).
+    pub fn write_to(
+        &self,
+        tensor: &mut Tensor,
+        resize: Resize,
+        normalization: Normalization,
+    ) -> Result<(), Error> {
+        let info: TensorInfo = tensor.inner().try_into()?;
+
+        let (height, width, channels) = match *info.dims {
+            [1, h, w, c] | [h, w, c] => (h as usize, w as usize, c as usize),
+            _ => return Err(Error::UnsupportedTensorShape),
+        };
+        if channels != 1 && channels != 3 {
+            return Err(Error::UnsupportedTensorShape);
+        }
+        let element_type = info.element_type;
+        let quantization = info.quantization;
+
+        // Map a pixel value to a real value
+        let real = |value: f32| match normalization {
+            Normalization::None => value,
+            Normalization::MeanStd { mean, std } => (value - mean) / std,
+        };
+
+        // Quantize a real value. Without normalization, pixel values are
+        // written unchanged
+        let quantize = |value: f32, min: i32, max: i32| -> i32 {
+            let q = match (normalization, quantization) {
+                (Normalization::None, _) => round(value) + min,
+                (_, Some(q)) => round(real(value) / q.scale) + q.zero_point,
+                (_, None) => round(real(value)),
+            };
+            q.clamp(min, max)
+        };
+
+        match element_type {
+            ElementType::Float32 => {
+                let data = tensor.as_data_mut::<f32>();
+                self.resample(width, height, channels, resize, |i, value| {
+                    data[i] = real(value)
+                });
+            }
+            ElementType::UInt8 => {
+                let data = tensor.as_data_mut::<u8>();
+                self.resample(width, height, channels, resize, |i, value| {
+                    data[i] = quantize(value, 0, 255) as u8
+                });
+            }
+            ElementType::Int8 => {
+                let data = tensor.as_data_mut::<i8>();
+                self.resample(width, height, channels, resize, |i, value| {
+                    data[i] = quantize(value, -128, 127) as i8
+                });
+            }
+            _ => return Err(Error::ElementTypeMismatch),
+        }
+
+        Ok(())
+    }
+
+    /// Resample this image to `width` x `height`, calling `f` with the
+    /// output index and value of each channel of each output pixel
+    fn resample(
+        &self,
+        width: usize,
+        height: usize,
+        channels: usize,
+        resize: Resize,
+        mut f: impl FnMut(usize, f32),
+    ) {
+        let x_scale = self.width as f32 / width as f32;
+        let y_scale = self.height as f32 / height as f32;
+
+        let pixel = |x: usize, y: usize| -> [f32; 3] {
+            if channels == 1 {
+                [self.luma(x, y) as f32; 3]
+            } else {
+                let [r, g, b] = self.rgb(x, y);
+                [r as f32, g as f32, b as f32]
+            }
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                // Source coordinates of the centre of this output pixel
+                let sx = (x as f32 + 0.5) * x_scale - 0.5;
+                let sy = (y as f32 + 0.5) * y_scale - 0.5;
+
+                let value = match resize {
+                    Resize::Nearest => {
+                        pixel(nearest(sx, self.width), nearest(sy, self.height))
+                    }
+                    Resize::Bilinear => {
+                        let (x0, x1, fx) = neighbours(sx, self.width);
+                        let (y0, y1, fy) = neighbours(sy, self.height);
+
+                        let (p00, p01) = (pixel(x0, y0), pixel(x1, y0));
+                        let (p10, p11) = (pixel(x0, y1), pixel(x1, y1));
+
+                        let mut value = [0.; 3];
+                        for c in 0..3 {
+                            let top = p00[c] + (p01[c] - p00[c]) * fx;
+                            let bottom = p10[c] + (p11[c] - p10[c]) * fx;
+                            value[c] = top + (bottom - top) * fy;
+                        }
+                        value
+                    }
+                };
+
+                let index = (y * width + x) * channels;
+                for (c, &v) in value[..channels].iter().enumerate() {
+                    f(index + c, v);
+                }
+            }
+        }
+    }
+}
+
+/// Expand a RGB565 pixel to 8 bits per component
+fn rgb565(p: u16) -> [u8; 3] {
+    let r = ((p >> 11) & 0x1f) as u8;
+    let g = ((p >> 5) & 0x3f) as u8;
+    let b = (p & 0x1f) as u8;
+
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// Convert a YUV pixel to RGB, using the ITU-R BT.601 coefficients
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let y = y as i32;
+    let u = u as i32 - 128;
+    let v = v as i32 - 128;
+
+    // Coefficients scaled by 256
+    let clamp = |x: i32| (x >> 8).clamp(0, 255) as u8;
+    [
+        clamp((y << 8) + 359 * v),
+        clamp((y << 8) - 88 * u - 183 * v),
+        clamp((y << 8) + 454 * u),
+    ]
+}
+
+/// Round to the nearest integer, with halves away from zero
+fn round(x: f32) -> i32 {
+    if x >= 0. {
+        (x + 0.5) as i32
+    } else {
+        (x - 0.5) as i32
+    }
+}
+
+/// Index of the source pixel nearest to coordinate `s`
+fn nearest(s: f32, len: usize) -> usize {
+    (round(s).max(0) as usize).min(len - 1)
+}
+
+/// Indices of the source pixels either side of coordinate `s`, and the
+/// weight of the second
+fn neighbours(s: f32, len: usize) -> (usize, usize, f32) {
+    let s = s.max(0.).min((len - 1) as f32);
+    // Truncation is floor, as s is not negative
+    let i0 = s as usize;
+    let i1 = (i0 + 1).min(len - 1);
+
+    (i0, i1, s - i0 as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_image() {
+        let data = [0u8; 12];
+
+        assert!(Image::new(&data, 2, 2, PixelFormat::Rgb888).is_ok());
+        assert!(Image::new(&data, 3, 2, PixelFormat::Yuv422).is_err());
+        assert_eq!(
+            Image::new(&data, 2, 2, PixelFormat::Grayscale).unwrap_err(),
+            Error::InputDataLenMismatch
+        );
+        assert_eq!(
+            Image::new(&data, 0, 2, PixelFormat::Grayscale).unwrap_err(),
+            Error::InvalidImageDimensions
+        );
+    }
+
+    #[test]
+    fn pixel_formats() {
+        // Pure red
+        let rgb565 = [0xf8, 0x00];
+        let image = Image::new(&rgb565, 1, 1, PixelFormat::Rgb565).unwrap();
+        assert_eq!(image.rgb(0, 0), [255, 0, 0]);
+        assert_eq!(image.luma(0, 0), 76);
+
+        let rgb565_le = [0x00, 0xf8];
+        let image =
+            Image::new(&rgb565_le, 1, 1, PixelFormat::Rgb565Le).unwrap();
+        assert_eq!(image.rgb(0, 0), [255, 0, 0]);
+
+        // Two gray pixels of different brightness
+        let yuv422 = [50, 128, 200, 128];
+        let image = Image::new(&yuv422, 2, 1, PixelFormat::Yuv422).unwrap();
+        assert_eq!(image.rgb(0, 0), [50, 50, 50]);
+        assert_eq!(image.rgb(1, 0), [200, 200, 200]);
+        assert_eq!(image.luma(1, 0), 200);
+
+        let rgb888 = [255, 255, 255];
+        let image = Image::new(&rgb888, 1, 1, PixelFormat::Rgb888).unwrap();
+        assert_eq!(image.luma(0, 0), 255);
+    }
+
+    #[test]
+    fn crop() {
+        #[rustfmt::skip]
+        let data = [
+            0, 1, 2, 3,
+            4, 5, 6, 7,
+            8, 9, 10, 11,
+        ];
+        let image = Image::new(&data, 4, 3, PixelFormat::Grayscale).unwrap();
+
+        let cropped = image.crop(1, 1, 2, 2).unwrap();
+        assert_eq!(cropped.width(), 2);
+        assert_eq!(cropped.luma(0, 0), 5);
+        assert_eq!(cropped.luma(1, 1), 10);
+
+        // Crops are relative to the view
+        let cropped = cropped.crop(1, 0, 1, 1).unwrap();
+        assert_eq!(cropped.luma(0, 0), 6);
+
+        assert!(image.crop(3, 0, 2, 1).is_err());
+        assert!(image.crop(0, 0, 0, 1).is_err());
+    }
+
+    #[test]
+    fn resize_nearest() {
+        #[rustfmt::skip]
+        let data = [
+            0, 0, 100, 100,
+            0, 0, 100, 100,
+            50, 50, 200, 200,
+            50, 50, 200, 200,
+        ];
+        let image = Image::new(&data, 4, 4, PixelFormat::Grayscale).unwrap();
+
+        let mut output = [0u8; 4];
+        image
+            .resize_into(&mut output, 2, 2, 1, Resize::Nearest)
+            .unwrap();
+        assert_eq!(output, [0, 100, 50, 200]);
+
+        // Upscaling repeats pixels
+        let mut output = [0u8; 64];
+        image
+            .resize_into(&mut output, 8, 8, 1, Resize::Nearest)
+            .unwrap();
+        assert_eq!(output[..8], [0, 0, 0, 0, 100, 100, 100, 100]);
+    }
+
+    #[test]
+    fn resize_bilinear() {
+        let data = [0, 100];
+        let image = Image::new(&data, 2, 1, PixelFormat::Grayscale).unwrap();
+
+        let mut output = [0u8; 4];
+        image
+            .resize_into(&mut output, 4, 1, 1, Resize::Bilinear)
+            .unwrap();
+        assert_eq!(output, [0, 25, 75, 100]);
+    }
+
+    #[test]
+    fn resize_rgb() {
+        let data = [255, 0, 0, 0, 0, 255];
+        let image = Image::new(&data, 2, 1, PixelFormat::Rgb888).unwrap();
+
+        let mut output = [0u8; 6];
+        image
+            .resize_into(&mut output, 2, 1, 3, Resize::Nearest)
+            .unwrap();
+        assert_eq!(output, data);
+
+        let mut output = [0u8; 2];
+        image
+            .resize_into(&mut output, 2, 1, 1, Resize::Nearest)
+            .unwrap();
+        assert_eq!(output, [76, 28]);
+
+        assert_eq!(
+            image.resize_into(&mut output, 1, 1, 2, Resize::Nearest),
+            Err(Error::UnsupportedTensorShape)
+        );
+    }
+
+    #[test]
+    fn yuv_conversion() {
+        assert_eq!(yuv_to_rgb(128, 128, 128), [128, 128, 128]);
+        assert_eq!(yuv_to_rgb(255, 0, 0), [75, 255, 28]);
+        assert_eq!(yuv_to_rgb(0, 255, 255), [178, 0, 225]);
+    }
+}
@@ -0,0 +1,312 @@
+//! A pool of interpreters for serving inferences from several threads
+//!
+//! An interpreter can be moved to another thread, but can only run one
+//! inference at a time. An `InterpreterPool` builds a fixed number of
+//! interpreters for one model up front, and hands one out to each request.
+//! A request waits if all of them are in use.
+
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use crate::micro_interpreter::MicroInterpreter;
+use crate::micro_op_resolver::OpResolverRepr;
+use crate::model::Model;
+use crate::tensor::{ElemTypeOf, Tensor};
+use crate::{Error, Status};
+
+/// A fixed number of interpreters for one model, shared between threads
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use tfmicro::{include_model, AllOpResolver, InterpreterPool, Model};
+///
+/// let model = include_model!("../examples/models/hello_world.tflite");
+/// let model = Model::from_buffer(model).unwrap();
+/// let resolver: &'static _ = Box::leak(Box::new(AllOpResolver::new()));
+///
+/// let pool = InterpreterPool::new(model, resolver, 4, 4 * 1024).unwrap();
+/// let pool = Arc::new(pool);
+///
+/// let handles: Vec<_> = (0..8)
+///     .map(|n| {
+///         let pool = Arc::clone(&pool);
+///         thread::spawn(move || {
+///             let mut interpreter = pool.get();
+///             interpreter.input(0, &[n as f32 / 8.0]).unwrap();
+///             interpreter.invoke().unwrap();
+///             interpreter.output(0).as_data::<f32>()[0]
+///         })
+///     })
+///     .collect();
+///
+/// for handle in handles {
+///     let y = handle.join().unwrap();
+/// }
+/// ```
+pub struct InterpreterPool<'a> {
+    idle: Mutex<Vec<MicroInterpreter<'a>>>,
+    available: Condvar,
+    len: usize,
+}
+
+impl<'a> InterpreterPool<'a> {
+    /// Create `len` interpreters for `model`, each with a tensor arena of
+    /// `arena_size` bytes on the heap
+    ///
+    /// # Errors
+    ///
+    /// As for [`MicroInterpreter::new`](crate::MicroInterpreter::new)
+    pub fn new<OpResolver: OpResolverRepr>(
+        model: &'a Model,
+        resolver: &'a OpResolver,
+        len: usize,
+        arena_size: usize,
+    ) -> Result<Self, Error> {
+        let idle = (0..len)
+            .map(|_| {
+                MicroInterpreter::new(model, resolver, vec![0u8; arena_size])
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            idle: Mutex::new(idle),
+            available: Condvar::new(),
+            len,
+        })
+    }
+
+    /// The number of interpreters in the pool
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the pool has no interpreters
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of interpreters that are not in use
+    pub fn idle_len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Take an interpreter from the pool, waiting until one is free. It is
+    /// returned to the pool when dropped
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool is empty, as it would wait forever
+    pub fn get(&self) -> PooledInterpreter<'_, 'a> {
+        assert!(!self.is_empty(), "InterpreterPool is empty");
+
+        let mut idle = self.lock();
+        loop {
+            if let Some(interpreter) = idle.pop() {
+                return PooledInterpreter {
+                    pool: self,
+                    interpreter: Some(interpreter),
+                };
+            }
+            idle = self.available.wait(idle).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Take an interpreter from the pool if one is free
+    pub fn try_get(&self) -> Option<PooledInterpreter<'_, 'a>> {
+        let interpreter = self.lock().pop()?;
+
+        Some(PooledInterpreter {
+            pool: self,
+            interpreter: Some(interpreter),
+        })
+    }
+
+    /// Lock the idle interpreters. A thread that panicked whilst holding the
+    /// lock cannot have left the list in an inconsistent state, so poisoning
+    /// is ignored
+    fn lock(&self) -> MutexGuard<'_, Vec<MicroInterpreter<'a>>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// An interpreter taken from an [`InterpreterPool`]
+///
+/// Dereferences to a [`MicroInterpreter`](crate::MicroInterpreter), and is
+/// returned to the pool when dropped.
+pub struct PooledInterpreter<'p, 'a> {
+    pool: &'p InterpreterPool<'a>,
+    // Only `None` whilst being dropped
+    interpreter: Option<MicroInterpreter<'a>>,
+}
+
+impl<'a> Deref for PooledInterpreter<'_, 'a> {
+    type Target = MicroInterpreter<'a>;
+
+    fn deref(&self) -> &MicroInterpreter<'a> {
+        self.interpreter.as_ref().unwrap()
+    }
+}
+
+impl PooledInterpreter<'_, '_> {
+    /// See [`MicroInterpreter::input`](crate::MicroInterpreter::input)
+    pub fn input<T: ElemTypeOf + Clone>(
+        &mut self,
+        n: usize,
+        data: &[T],
+    ) -> Result<(), Error> {
+        self.interpreter.as_mut().unwrap().input(n, data)
+    }
+
+    /// See [`MicroInterpreter::input_tensor`](crate::MicroInterpreter::input_tensor)
+    pub fn input_tensor(&mut self, n: usize) -> &mut Tensor {
+        self.interpreter.as_mut().unwrap().input_tensor(n)
+    }
+
+    /// See [`MicroInterpreter::invoke`](crate::MicroInterpreter::invoke)
+    pub fn invoke(&mut self) -> Result<(), Status> {
+        self.interpreter.as_mut().unwrap().invoke()
+    }
+}
+
+impl Drop for PooledInterpreter<'_, '_> {
+    fn drop(&mut self) {
+        if let Some(interpreter) = self.interpreter.take() {
+            self.pool.lock().push(interpreter);
+            self.pool.available.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::include_model;
+    use crate::micro_op_resolver::AllOpResolver;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn pool(len: usize) -> InterpreterPool<'static> {
+        let model = include_model!("../examples/models/hello_world.tflite");
+        let model = Model::from_buffer(model).unwrap();
+        let resolver: &'static _ = Box::leak(Box::new(AllOpResolver::new()));
+
+        InterpreterPool::new(model, resolver, len, 4 * 1024).unwrap()
+    }
+
+    #[test]
+    fn send() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+
+        // A `MicroInterpreter` can only be `Send` if the model and the
+        // resolvers it reads are `Sync`
+        assert_sync::<Model>();
+        assert_sync::<AllOpResolver>();
+        assert_sync::<crate::MutableOpResolver<1>>();
+
+        assert_send::<MicroInterpreter>();
+        assert_send::<InterpreterPool>();
+        assert_sync::<InterpreterPool>();
+    }
+
+    #[test]
+    fn get_and_return() {
+        let pool = pool(2);
+        assert_eq!(pool.len(), 2);
+
+        let a = pool.get();
+        let b = pool.try_get().unwrap();
+        assert_eq!(pool.idle_len(), 0);
+        assert!(pool.try_get().is_none());
+
+        drop(a);
+        assert_eq!(pool.idle_len(), 1);
+        drop(b);
+        assert_eq!(pool.idle_len(), 2);
+    }
+
+    #[test]
+    fn throughput() {
+        const THREADS: usize = 8;
+        const REQUESTS: usize = 25;
+        const ARENA_SIZE: usize = 10 * 1024;
+
+        // A model large enough for the time to be spent in invoking it,
+        // rather than in taking and returning interpreters
+        let model = include_model!("../examples/models/micro_speech.tflite");
+        let model = Model::from_buffer(model).unwrap();
+        let resolver: &'static _ = Box::leak(Box::new(AllOpResolver::new()));
+        let input = |n: usize| [(n * 7 % 256) as u8; 49 * 40];
+
+        // Expected outputs: a single interpreter runs every request in turn
+        let mut interpreter =
+            MicroInterpreter::new(model, resolver, vec![0u8; ARENA_SIZE])
+                .unwrap();
+        let expected: Vec<Vec<u8>> = (0..THREADS * REQUESTS)
+            .map(|n| {
+                interpreter.input(0, &input(n)[..]).unwrap();
+                interpreter.invoke().unwrap();
+                interpreter.output(0).as_data::<u8>().to_vec()
+            })
+            .collect();
+        let expected = Arc::new(expected);
+
+        let pool = Arc::new(
+            InterpreterPool::new(model, resolver, 4, ARENA_SIZE).unwrap(),
+        );
+        let in_use = Arc::new(AtomicUsize::new(0));
+        let max_in_use = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let pool = Arc::clone(&pool);
+                let expected = Arc::clone(&expected);
+                let in_use = Arc::clone(&in_use);
+                let max_in_use = Arc::clone(&max_in_use);
+
+                thread::spawn(move || {
+                    for r in 0..REQUESTS {
+                        let n = t * REQUESTS + r;
+
+                        let mut interpreter = pool.get();
+                        let used = in_use.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_use.fetch_max(used, Ordering::SeqCst);
+
+                        interpreter.input(0, &input(n)[..]).unwrap();
+                        interpreter.invoke().unwrap();
+                        let y = interpreter.output(0).as_data::<u8>().to_vec();
+
+                        in_use.fetch_sub(1, Ordering::SeqCst);
+                        drop(interpreter);
+
+                        // Each request gets the same answer as the baseline
+                        // for its own input
+                        assert_eq!(y, expected[n], "request {}", n);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // The pool never hands out more interpreters than it has, and every
+        // interpreter is returned
+        assert!(max_in_use.load(Ordering::SeqCst) <= pool.len());
+        assert_eq!(pool.idle_len(), pool.len());
+    }
+
+    #[test]
+    fn concurrent_creation() {
+        // Interpreters are created on several threads at once
+        let handles: Vec<_> =
+            (0..4).map(|_| thread::spawn(|| pool(2).len())).collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 2);
+        }
+    }
+}
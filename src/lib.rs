@@ -38,17 +38,19 @@
 //!
 //! Typically a model is exported from the TensorFlow training framework in a
 //! binary file format with extension `.tflite`. You can import this straight
-//! into Rust with the
-//! [`include_bytes!`](https://doc.rust-lang.org/core/macro.include_bytes.html)
-//! macro.
+//! into Rust with the [`include_model!`](crate::include_model) macro. This
+//! works like
+//! [`include_bytes!`](https://doc.rust-lang.org/core/macro.include_bytes.html),
+//! but aligns the model to 16 bytes as TensorFlow requires.
 //!
 //! Then we can use the [`Model::from_buffer`](crate::Model::from_buffer) method
-//! to perform a zero-copy conversion into a `Model`.
+//! to perform a zero-copy conversion into a `Model`. A buffer that is not
+//! aligned is rejected with `Error::MisalignedBuffer`.
 //!
 //! ```rust
-//! # use tfmicro::{Model};
-//! let model_array = include_bytes!("../examples/models/hello_world.tflite");
-//! let model = Model::from_buffer(&model_array[..]).unwrap();
+//! # use tfmicro::{include_model, Model};
+//! let model_array = include_model!("../examples/models/hello_world.tflite");
+//! let model = Model::from_buffer(model_array).unwrap();
 //! ```
 //!
 //! ### Creating a tensor arena
@@ -68,6 +70,10 @@
 //! the number of bytes actually used by the model by calling
 //! [`arena_used_bytes`](crate::MicroInterpreter::arena_used_bytes).
 //!
+//! Models that run one after another, but never at the same time, can share an
+//! arena with a [`SharedArena`](crate::SharedArena). It reports the arena size
+//! needed by the largest model.
+//!
 //!
 //! ### Instantiating an Interpreter and Input Tensors
 //!
@@ -81,18 +87,27 @@
 //! A op_resolver is required for the interpreter. The simplest option is to
 //! pass an [`AllOpResolver`](crate::AllOpResolver), but to save memory use a
 //! [`MutableOpResolver`](crate::MutableOpResolver) with the required operations
-//! only.
+//! only. The capacity of a `MutableOpResolver` is a const generic parameter,
+//! so it only takes as much memory as the operations it contains.
+//!
+//! A build script can generate a `MutableOpResolver` containing only the
+//! operations that a model uses, with `tfmicro::build::generate_resolver`
+//! (`std` feature). To inspect a model without the C++ TensorFlow build,
+//! use the pure Rust reader in [`schema`](crate::schema). TensorFlow is built
+//! by the default `tensorflow` feature, so add `tfmicro` to
+//! `[build-dependencies]` with `default-features = false` and the `std`
+//! feature for this.
 //!
 //! ```
 //! # use tfmicro::{Model, MicroInterpreter, AllOpResolver};
-//! # let model_array = include_bytes!("../examples/models/hello_world.tflite");
-//! # let model = Model::from_buffer(&model_array[..]).unwrap();
+//! # let model_array = tfmicro::include_model!("../examples/models/hello_world.tflite");
+//! # let model = Model::from_buffer(model_array).unwrap();
 //! # const TENSOR_ARENA_SIZE: usize = 4 * 1024;
 //! # let mut arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
 //! let op_resolver = AllOpResolver::new();
 //!
 //! let mut interpreter =
-//!     MicroInterpreter::new(&model, op_resolver, &mut arena[..]).unwrap();
+//!     MicroInterpreter::new(&model, &op_resolver, &mut arena[..]).unwrap();
 //!
 //! interpreter.input(0, &[0.0]).unwrap(); // Input tensor of length 1
 //! ```
@@ -110,18 +125,62 @@
 //!
 //! ```
 //! # use tfmicro::{Model, MicroInterpreter, AllOpResolver};
-//! # let model_array = include_bytes!("../examples/models/hello_world.tflite");
-//! # let model = Model::from_buffer(&model_array[..]).unwrap();
+//! # let model_array = tfmicro::include_model!("../examples/models/hello_world.tflite");
+//! # let model = Model::from_buffer(model_array).unwrap();
 //! # const TENSOR_ARENA_SIZE: usize = 4 * 1024;
 //! # let mut arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
 //! # let op_resolver = AllOpResolver::new();
 //! # let mut interpreter =
-//! #     MicroInterpreter::new(&model, op_resolver, &mut arena[..]).unwrap();
+//! #     MicroInterpreter::new(&model, &op_resolver, &mut arena[..]).unwrap();
 //! interpreter.invoke().unwrap();
 //!
 //! dbg!(interpreter.output(0).as_data::<f32>());
 //! ```
 //!
+//! To run a model with one input and one output over many samples,
+//! [`invoke_batch`](crate::MicroInterpreter::invoke_batch) takes an iterator of
+//! inputs, and calls a closure with the output for each sample. It also splits
+//! and merges samples for models with a batch dimension greater than one.
+//!
+//! ### Loading models at runtime
+//!
+//! A model read from a file or received over the air can be copied into an
+//! [`OwnedModel`](crate::OwnedModel) (`alloc` feature), which aligns and
+//! verifies it, and dereferences to a `Model`. To store a model together with
+//! its interpreter, for example in a long-running `std` service, an
+//! [`OwnedInterpreter`](crate::OwnedInterpreter) owns the model, op resolver and
+//! tensor arena.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() {
+//! # use tfmicro::{AllOpResolver, OwnedInterpreter, OwnedModel};
+//! let bytes = std::fs::read("examples/models/hello_world.tflite").unwrap();
+//! let model = OwnedModel::from_bytes(&bytes).unwrap();
+//!
+//! let interpreter =
+//!     OwnedInterpreter::new(model, AllOpResolver::new(), 4 * 1024).unwrap();
+//! # }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() {}
+//! ```
+//!
+//! Models received over the air can be wrapped in a
+//! [`container`](crate::container) with a CRC-32, a SHA-256 digest and an
+//! Ed25519 signature. With the `ed25519` feature,
+//! [`Model::from_verified_buffer`](crate::Model::from_verified_buffer) and
+//! `OwnedModel::from_verified_bytes` reject a corrupted or unsigned model before
+//! TensorFlow reads it.
+//!
+//! For over the air updates, a [`ModelStore`](crate::store::ModelStore) keeps
+//! two model slots in flash. An update is tried after it is written, committed
+//! once it has run successfully, and rolled back otherwise.
+//!
+//! A `MicroInterpreter` can be moved to another thread. To serve inferences
+//! from a thread pool, an [`InterpreterPool`](crate::InterpreterPool) (`std`
+//! feature) builds several interpreters for one model, and hands one out to each
+//! request.
+//!
 //! And that's it for a minimal use case! See the [Tests](tests/) folder
 //! for more advanced use cases.
 //!
@@ -142,10 +201,16 @@
 
 #[macro_use]
 extern crate log;
+#[cfg(feature = "tensorflow")]
 #[macro_use]
 extern crate cpp;
 
+#[cfg(feature = "tensorflow")]
 mod bindings;
+#[cfg(any(feature = "tensorflow", feature = "std"))]
+#[macro_use]
+mod builtin_operators;
+#[cfg(feature = "tensorflow")]
 mod interop;
 
 /// Error type for tfmicro
@@ -161,8 +226,50 @@ pub enum Error {
     InputDataLenMismatch,
     /// The element type of the underlying data is not implemented by this crate
     ElementTypeUnimplemented,
+    /// The element type of the supplied data was different to the tensor
+    ElementTypeMismatch,
     /// An error occoured converting some raw string to UTF8
     Utf8Error,
+    /// More operators were added to a `MutableOpResolver` than its capacity
+    OpResolverCapacityExceeded,
+    /// A parameter of a `FrontendConfig` failed validation
+    InvalidFrontendConfig(FrontendConfigError),
+    /// An error occoured when creating the audio frontend state
+    FrontendInitError,
+    /// Results were supplied out of time order
+    TimestampOrder,
+    /// The image dimensions or crop region are invalid
+    InvalidImageDimensions,
+    /// The dimensions of the tensor are not supported by this operation
+    UnsupportedTensorShape,
+    /// A parameter of a `MelConfig` failed validation
+    InvalidMelConfig(MelConfigError),
+    /// The data is not a valid 16-bit PCM WAV file
+    InvalidWav,
+    /// The model buffer is not aligned to `Model::ALIGNMENT` bytes
+    MisalignedBuffer,
+    /// The model container header is not valid, or the model is truncated
+    InvalidContainer,
+    /// The model does not match the checksum in its container
+    ChecksumMismatch,
+    /// The model container is not signed, or not signed with the given key
+    InvalidSignature,
+    /// The model store storage failed, or is too small for two model slots
+    StorageError,
+    /// No model has been staged in the model store
+    NoActiveModel,
+    /// The model container is larger than a model store slot
+    UpdateTooLarge,
+    /// The model failed to run
+    InvokeFailed,
+    /// The model failed to run in `MicroInterpreter::invoke_batch`, on the
+    /// batch starting at this sample
+    BatchInvokeFailed(usize),
+    /// The model has more than one input or output tensor, which is not
+    /// supported by this operation
+    UnsupportedTensorCount,
+    /// The variable tensors of the model do not fit in a `TensorState`
+    TensorStateTooSmall,
 }
 
 /// The status resulting from a TensorFlow operation
@@ -172,6 +279,7 @@ pub enum Status {
     Error,
     DelegateError,
 }
+#[cfg(feature = "tensorflow")]
 impl From<bindings::TfLiteStatus> for Status {
     fn from(status: bindings::TfLiteStatus) -> Self {
         use Status::*;
@@ -184,16 +292,79 @@ impl From<bindings::TfLiteStatus> for Status {
     }
 }
 
+#[cfg(feature = "tensorflow")]
 mod micro_error_reporter;
+#[cfg(feature = "tensorflow")]
 mod operators;
 
+pub mod container;
+#[cfg(feature = "tensorflow")]
 mod frontend;
+mod frontend_error;
+#[cfg(feature = "tensorflow")]
+mod gesture;
+#[cfg(feature = "tensorflow")]
+pub mod image;
+#[cfg(all(feature = "std", feature = "tensorflow"))]
+mod interpreter_pool;
+mod mel;
+#[cfg(feature = "tensorflow")]
 mod micro_interpreter;
+#[cfg(feature = "tensorflow")]
 mod micro_op_resolver;
+#[cfg(feature = "tensorflow")]
 mod model;
+#[cfg(all(feature = "std", feature = "tensorflow"))]
+mod owned_interpreter;
+#[cfg(all(any(feature = "std", feature = "alloc"), feature = "tensorflow"))]
+mod owned_model;
+mod recognize_commands;
+pub mod schema;
+#[cfg(feature = "tensorflow")]
+mod shared_arena;
+#[cfg(feature = "tensorflow")]
+pub mod store;
+#[cfg(feature = "tensorflow")]
 mod tensor;
 
-pub use frontend::Frontend;
+#[cfg(feature = "std")]
+pub mod build;
+#[cfg(feature = "std")]
+pub mod c_array;
+#[cfg(feature = "std")]
+pub mod csv;
+#[cfg(feature = "std")]
+pub mod eval;
+#[cfg(feature = "std")]
+pub mod npy;
+#[cfg(feature = "std")]
+pub mod wav;
+
+#[cfg(feature = "tensorflow")]
+pub use frontend::{
+    FeatureGenerator, FeatureProvider, Frontend, FrontendConfig,
+};
+pub use frontend_error::FrontendConfigError;
+#[cfg(feature = "tensorflow")]
+pub use gesture::{AccelerometerBuffer, GesturePredictor};
+#[cfg(all(feature = "std", feature = "tensorflow"))]
+pub use interpreter_pool::{InterpreterPool, PooledInterpreter};
+pub use mel::{FixedMelSpectrogram, MelConfig, MelConfigError, MelSpectrogram};
+#[cfg(feature = "tensorflow")]
 pub use micro_interpreter::MicroInterpreter;
+#[cfg(feature = "tensorflow")]
 pub use micro_op_resolver::{AllOpResolver, MutableOpResolver};
-pub use model::Model;
+#[cfg(feature = "tensorflow")]
+pub use model::{Finding, Model, OperatorCode};
+#[cfg(all(feature = "std", feature = "tensorflow"))]
+pub use owned_interpreter::OwnedInterpreter;
+#[cfg(all(
+    any(feature = "std", feature = "alloc"),
+    feature = "tensorflow"
+))]
+pub use owned_model::OwnedModel;
+pub use recognize_commands::{Command, CommandRecognizer};
+#[cfg(feature = "tensorflow")]
+pub use shared_arena::{ArenaInterpreter, SharedArena, TensorState};
+#[cfg(feature = "tensorflow")]
+pub use tensor::{ElementType, QuantizationParams, Tensor, TensorInfo};
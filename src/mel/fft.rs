@@ -0,0 +1,362 @@
+//! Radix-2 fast Fourier transforms of real data, in floating and fixed point
+//!
+//! A real transform of length `n` is calculated with a complex transform of
+//! length `n / 2`, whose input is the even samples as the real parts and
+//! the odd samples as the imaginary parts. Complex numbers are stored as
+//! interleaved real and imaginary parts, so the `n` samples are transformed
+//! in place.
+
+use core::f32::consts::PI;
+use core::ops::{Add, Mul, Sub};
+
+/// A complex number
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub(crate) struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    /// Complex conjugate
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    /// Square of the magnitude
+    pub fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+/// Permute the interleaved complex numbers in `data` into bit-reversed
+/// order
+fn bit_reverse<T>(data: &mut [T]) {
+    let n = data.len() / 2;
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+
+        if i < j {
+            data.swap(2 * i, 2 * j);
+            data.swap(2 * i + 1, 2 * j + 1);
+        }
+    }
+}
+
+/// Calls `butterfly(a, b, k)` for each butterfly of a complex transform of
+/// length `m`, in order, where `a` and `b` are the indices of the complex
+/// numbers combined and `k` is the index of the twiddle factor for a real
+/// transform of length `2 * m`
+fn butterflies<F: FnMut(usize, usize, usize)>(m: usize, mut butterfly: F) {
+    // Combining transforms of length `len / 2`
+    let mut len = 2;
+    while len <= m {
+        let half = len / 2;
+        let step = 2 * m / len;
+
+        for start in (0..m).step_by(len) {
+            for k in 0..half {
+                butterfly(start + k, start + k + half, k * step);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Fill `table` with the twiddle factors for a real transform of length
+/// `2 * table.len()`
+///
+/// Only the first quarter circle is stored, as `exp(-2πik / n)` for `k` in
+/// `0..n / 4`, with interleaved real and imaginary parts.
+pub(crate) fn twiddles(table: &mut [f32]) {
+    let n = 2 * table.len();
+
+    for (k, t) in table.chunks_exact_mut(2).enumerate() {
+        let angle = -2. * PI * k as f32 / n as f32;
+        t[0] = libm::cosf(angle);
+        t[1] = libm::sinf(angle);
+    }
+}
+
+/// Twiddle factor `exp(-2πik / n)` for `k` in `0..n / 2`, from a table
+/// calculated by [`twiddles`]
+fn twiddle(table: &[f32], k: usize) -> Complex {
+    let quarter = table.len() / 2;
+
+    if k < quarter {
+        Complex::new(table[2 * k], table[2 * k + 1])
+    } else {
+        // A quarter turn further round, so multiplied by -i
+        let t = twiddle(table, k - quarter);
+        Complex::new(t.im, -t.re)
+    }
+}
+
+fn get(data: &[f32], k: usize) -> Complex {
+    Complex::new(data[2 * k], data[2 * k + 1])
+}
+
+fn set(data: &mut [f32], k: usize, value: Complex) {
+    data[2 * k] = value.re;
+    data[2 * k + 1] = value.im;
+}
+
+/// Calls `f(k, power)` with the power `|X[k]|²` of each bin `k` in
+/// `1..=n / 2` of the transform `X` of the `n` real samples in `data`
+///
+/// The length of `data` must be a power of two, and at least 4. `data` is
+/// overwritten. `table` is as calculated by [`twiddles`] for this length.
+pub(crate) fn power_spectrum<F>(data: &mut [f32], table: &[f32], mut f: F)
+where
+    F: FnMut(usize, f32),
+{
+    let m = data.len() / 2;
+    debug_assert!(data.len().is_power_of_two() && m >= 2);
+    debug_assert_eq!(table.len(), m);
+
+    bit_reverse(data);
+    butterflies(m, |a, b, k| {
+        let x = get(data, a);
+        let y = get(data, b) * twiddle(table, k);
+
+        set(data, a, x + y);
+        set(data, b, x - y);
+    });
+
+    // Separate the transforms of the even and odd samples, and combine
+    // them into the transform of all the samples
+    for k in 1..=m {
+        let z = get(data, k % m);
+        let c = get(data, m - k).conj();
+
+        let even = (z + c) * Complex::new(0.5, 0.);
+        let odd = (z - c) * Complex::new(0., -0.5);
+        let w = if k == m {
+            Complex::new(-1., 0.)
+        } else {
+            twiddle(table, k)
+        };
+
+        f(k, (even + w * odd).norm_sqr());
+    }
+}
+
+/// Convert `x` in the range [-1, 1] to Q15, saturating at 1
+pub(crate) fn q15(x: f32) -> i16 {
+    libm::roundf(x * 32768.).clamp(-32768., 32767.) as i16
+}
+
+/// Multiply `x` by the Q15 value `y`, rounding to nearest
+fn mul_q15(x: i64, y: i64) -> i64 {
+    (x * y + (1 << 14)) >> 15
+}
+
+/// Fill `table` with the twiddle factors for a real transform of length
+/// `2 * table.len()`, in fixed point
+///
+/// Each is `exp(-2πik / n)` for `k` in `0..n / 2`, with the real and
+/// imaginary parts in Q15, packed into the upper and lower halves.
+pub(crate) fn twiddles_q15(table: &mut [i32]) {
+    let n = 2 * table.len();
+
+    for (k, t) in table.iter_mut().enumerate() {
+        let angle = -2. * PI * k as f32 / n as f32;
+        let re = q15(libm::cosf(angle));
+        let im = q15(libm::sinf(angle));
+
+        *t = (re as i32) << 16 | im as u16 as i32;
+    }
+}
+
+/// Twiddle factor `k` from a table calculated by [`twiddles_q15`]
+fn twiddle_q15(table: &[i32], k: usize) -> (i64, i64) {
+    let t = table[k];
+
+    ((t >> 16) as i64, t as i16 as i64)
+}
+
+/// Calls `f(k, power)` with the power `|X[k]|²` of each bin `k` in
+/// `1..=n / 2` of the transform `X` of the `n` Q15 samples in `data`, in
+/// Q30
+///
+/// The length of `data` must be a power of two, at least 4 and no more
+/// than 32768, so that the transform does not overflow. `data` is
+/// overwritten. `table` is as calculated by [`twiddles_q15`] for this
+/// length.
+pub(crate) fn power_spectrum_q15<F>(data: &mut [i32], table: &[i32], mut f: F)
+where
+    F: FnMut(usize, u64),
+{
+    let m = data.len() / 2;
+    debug_assert!(data.len().is_power_of_two());
+    debug_assert!((2..=1 << 14).contains(&m));
+    debug_assert_eq!(table.len(), m);
+
+    // Each output is at most `m` times the largest input, so fits in an
+    // i32. Products are calculated in an i64
+    bit_reverse(data);
+    butterflies(m, |a, b, k| {
+        let (x_re, x_im) = (data[2 * a] as i64, data[2 * a + 1] as i64);
+        let (y_re, y_im) = (data[2 * b] as i64, data[2 * b + 1] as i64);
+        let (t_re, t_im) = twiddle_q15(table, k);
+        let (u_re, u_im) = (
+            mul_q15(y_re, t_re) - mul_q15(y_im, t_im),
+            mul_q15(y_re, t_im) + mul_q15(y_im, t_re),
+        );
+
+        data[2 * a] = (x_re + u_re) as i32;
+        data[2 * a + 1] = (x_im + u_im) as i32;
+        data[2 * b] = (x_re - u_re) as i32;
+        data[2 * b + 1] = (x_im - u_im) as i32;
+    });
+
+    // As for `power_spectrum`, but the result is twice `X[k]`, to avoid
+    // rounding before the power is calculated
+    for k in 1..=m {
+        let (z, c) = (2 * (k % m), 2 * (m - k));
+        let (z_re, z_im) = (data[z] as i64, data[z + 1] as i64);
+        let (c_re, c_im) = (data[c] as i64, -(data[c + 1] as i64));
+
+        let (even_re, even_im) = (z_re + c_re, z_im + c_im);
+        let (odd_re, odd_im) = (z_im - c_im, c_re - z_re);
+        let (w_re, w_im) = if k == m {
+            (-1 << 15, 0)
+        } else {
+            twiddle_q15(table, k)
+        };
+
+        let re = even_re + mul_q15(odd_re, w_re) - mul_q15(odd_im, w_im);
+        let im = even_im + mul_q15(odd_re, w_im) + mul_q15(odd_im, w_re);
+        let power = (re * re) as u64 + (im * im) as u64;
+
+        f(k, power >> 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Direct evaluation of the power spectrum, for bins `1..=n / 2`
+    fn dft_power(input: &[f32], output: &mut [f32]) {
+        let n = input.len();
+
+        for (k, out) in (1..).zip(output.iter_mut()) {
+            let x = input.iter().enumerate().fold(
+                Complex::default(),
+                |acc, (i, &x)| {
+                    let angle = -2. * PI * (i * k % n) as f32 / n as f32;
+                    acc + Complex::new(
+                        x * libm::cosf(angle),
+                        x * libm::sinf(angle),
+                    )
+                },
+            );
+            *out = x.norm_sqr();
+        }
+    }
+
+    fn signal(data: &mut [f32]) {
+        for (i, x) in data.iter_mut().enumerate() {
+            *x = 0.4 * libm::sinf(i as f32 * 0.7) + 0.01 * i as f32 - 0.3;
+        }
+    }
+
+    #[test]
+    fn power_spectrum_matches_dft() {
+        const N: usize = 64;
+
+        let mut data = [0.; N];
+        signal(&mut data);
+
+        let mut expected = [0.; N / 2];
+        dft_power(&data, &mut expected);
+
+        let mut table = [0.; N / 2];
+        twiddles(&mut table);
+
+        let mut bins = 0;
+        power_spectrum(&mut data, &table, |k, power| {
+            let e = expected[k - 1];
+            assert!((power - e).abs() < 1e-4 * e.max(1.), "{}", k);
+            bins += 1;
+        });
+        assert_eq!(bins, N / 2);
+    }
+
+    #[test]
+    fn power_spectrum_q15_matches_dft() {
+        const N: usize = 64;
+
+        let mut input = [0.; N];
+        signal(&mut input);
+
+        let mut expected = [0.; N / 2];
+        dft_power(&input, &mut expected);
+
+        let mut table = [0; N / 2];
+        twiddles_q15(&mut table);
+
+        let mut data = [0; N];
+        for (d, &x) in data.iter_mut().zip(input.iter()) {
+            *d = q15(x) as i32;
+        }
+
+        power_spectrum_q15(&mut data, &table, |k, power| {
+            let power = power as f32 / (1u64 << 30) as f32;
+            let e = expected[k - 1];
+            assert!((power - e).abs() < 1e-2 * e.max(1.), "{}", k);
+        });
+    }
+
+    #[test]
+    fn twiddle_symmetry() {
+        let mut table = [0.; 8];
+        twiddles(&mut table);
+
+        for k in 0..8 {
+            let angle = -2. * PI * k as f32 / 16.;
+            let t = twiddle(&table, k);
+
+            assert!((t.re - libm::cosf(angle)).abs() < 1e-6);
+            assert!((t.im - libm::sinf(angle)).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Fixed-point log-mel spectrogram and MFCC feature generation
+
+use super::{fft, hann, log_mel, Filterbank, Frame, MelConfig, MelConfigError};
+use crate::Error;
+
+/// Generates log-mel spectrograms or MFCCs from 16-bit PCM audio, as for a
+/// [`MelSpectrogram`](crate::MelSpectrogram), but in fixed point
+///
+/// The window, FFT and mel spectrogram are calculated with integer
+/// arithmetic, and only the logarithm of each mel bin, and the MFCCs, in
+/// floating point. For targets without a floating point unit. The FFT size
+/// can be no more than 32768.
+///
+/// All memory is held within this struct, so no allocator is required. It
+/// takes about `12 * FFT_SIZE + 8 * MEL_BINS` bytes.
+pub struct FixedMelSpectrogram<const FFT_SIZE: usize, const MEL_BINS: usize> {
+    config: MelConfig,
+    window_size: usize,
+    window_step_size: usize,
+
+    frame: Frame<i16, FFT_SIZE>,
+    // Q15
+    window: [i16; FFT_SIZE],
+    // The windowed samples, transformed in place
+    spectrum: [i32; FFT_SIZE],
+    // The first half holds the twiddle factors of the FFT. The second half
+    // holds the position of FFT bins 1 to FFT_SIZE / 2 on the mel bins, as
+    // the mel bin index in the upper half and the Q15 weight in the lower
+    // half, or -1 outside the band limits. See `Filterbank::position`
+    tables: [i32; FFT_SIZE],
+    // Q30
+    mel: [u64; MEL_BINS],
+}
+
+impl<const FFT_SIZE: usize, const MEL_BINS: usize>
+    FixedMelSpectrogram<FFT_SIZE, MEL_BINS>
+{
+    /// Create a new FixedMelSpectrogram with the default configuration
+    pub fn new() -> Result<Self, Error> {
+        Self::with_config(&MelConfig::new())
+    }
+
+    /// Create a new FixedMelSpectrogram with `config`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidMelConfig` if any parameter of `config` is
+    /// invalid for this FFT size and number of mel bins.
+    pub fn with_config(config: &MelConfig) -> Result<Self, Error> {
+        config
+            .validate(FFT_SIZE, MEL_BINS)
+            .map_err(Error::InvalidMelConfig)?;
+        if FFT_SIZE > 1 << 15 {
+            return Err(Error::InvalidMelConfig(MelConfigError::FftSize));
+        }
+
+        let window_size = config.window_size();
+
+        let mut window = [0; FFT_SIZE];
+        for (n, w) in window[..window_size].iter_mut().enumerate() {
+            *w = fft::q15(hann(n, window_size));
+        }
+
+        let mut tables = [0; FFT_SIZE];
+        let (twiddles, positions) = tables.split_at_mut(FFT_SIZE / 2);
+        fft::twiddles_q15(twiddles);
+
+        let filterbank = Filterbank::new(config, FFT_SIZE, MEL_BINS);
+        for (k, p) in (1..).zip(positions.iter_mut()) {
+            *p = match filterbank.position(k) {
+                Some((i, weight)) => {
+                    let weight = libm::roundf(weight * 32768.) as i32;
+
+                    ((i as i32) << 16) + weight
+                }
+                None => -1,
+            };
+        }
+
+        Ok(Self {
+            config: *config,
+            window_size,
+            window_step_size: config.window_step_size(),
+
+            frame: Frame::new(),
+            window,
+            spectrum: [0; FFT_SIZE],
+            tables,
+            mel: [0; MEL_BINS],
+        })
+    }
+
+    /// Returns the number of features generated for each window
+    pub fn num_features(&self) -> usize {
+        self.config.num_mfcc.unwrap_or(MEL_BINS)
+    }
+
+    /// Process samples from `samples`, until either a window is complete
+    /// or `samples` is exhausted. When a window is complete, its features
+    /// are written to `slice`, which has length
+    /// [`num_features`](Self::num_features).
+    ///
+    /// Returns the number of samples read, and whether `slice` was written
+    pub fn next_slice(
+        &mut self,
+        samples: &[i16],
+        slice: &mut [f32],
+    ) -> (usize, bool) {
+        let (num_samples_read, full) =
+            self.frame.push(samples, self.window_size, |x| x);
+        if !full {
+            return (num_samples_read, false);
+        }
+
+        self.compute(slice);
+        self.frame.advance(self.window_size, self.window_step_size);
+
+        (num_samples_read, true)
+    }
+
+    /// Discard the samples received so far
+    pub fn reset(&mut self) {
+        self.frame.reset();
+    }
+
+    /// Calculate the features of the current window into `output`
+    fn compute(&mut self, output: &mut [f32]) {
+        // Windowed samples in Q15, zero padded to the FFT size
+        for ((s, &x), &w) in self
+            .spectrum
+            .iter_mut()
+            .zip(self.frame.samples.iter())
+            .zip(self.window.iter())
+        {
+            *s = (x as i32 * w as i32 + (1 << 14)) >> 15;
+        }
+
+        // Mel spectrogram from the power spectrum. The power of a bin is
+        // less than 2^62, and the sum of all of them is too, as the
+        // samples are less than 1
+        let mel = &mut self.mel;
+        mel.iter_mut().for_each(|m| *m = 0);
+
+        // `power * weight`, where `weight` is in Q15, without overflowing
+        let weighted = |power: u64, weight: u64| {
+            (power >> 15) * weight + (((power & 0x7fff) * weight) >> 15)
+        };
+
+        let (twiddles, positions) = self.tables.split_at(FFT_SIZE / 2);
+        fft::power_spectrum_q15(&mut self.spectrum, twiddles, |k, power| {
+            let position = positions[k - 1];
+            if position < 0 {
+                return;
+            }
+            let i = (position >> 16) as usize;
+            let weight = (position & 0xffff) as u64;
+
+            if i < MEL_BINS {
+                mel[i] += weighted(power, weight);
+            }
+            if i > 0 && i <= MEL_BINS {
+                mel[i - 1] += weighted(power, (1 << 15) - weight);
+            }
+        });
+
+        let mut mel = [0.; MEL_BINS];
+        for (m, &fixed) in mel.iter_mut().zip(self.mel.iter()) {
+            *m = fixed as f32 / (1u64 << 30) as f32;
+        }
+        log_mel(&mut mel, &self.config, output);
+    }
+}
+
+#[cfg(feature = "tensorflow")]
+impl<const FFT_SIZE: usize, const MEL_BINS: usize> crate::FeatureGenerator
+    for FixedMelSpectrogram<FFT_SIZE, MEL_BINS>
+{
+    type Feature = f32;
+
+    fn slice_len(&self) -> usize {
+        self.num_features()
+    }
+
+    fn next_slice(
+        &mut self,
+        samples: &[i16],
+        slice: &mut [f32],
+    ) -> (usize, bool) {
+        FixedMelSpectrogram::next_slice(self, samples, slice)
+    }
+
+    fn reset(&mut self) {
+        FixedMelSpectrogram::reset(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::*;
+    use super::*;
+    use crate::MelSpectrogram;
+
+    /// The samples and window are rounded to Q15, which adds a little noise
+    /// in mel bins with no power
+    fn assert_near(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 0.05, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn tone_reference() {
+        let mut samples = [0; 64];
+        tone(&mut samples);
+
+        let config = config();
+        let mut mel =
+            FixedMelSpectrogram::<64, 8>::with_config(&config).unwrap();
+        let mut slice = [0.; 8];
+        assert_eq!(mel.next_slice(&samples, &mut slice), (64, true));
+        assert_near(&slice, &TONE_LOG_MEL);
+
+        let config = config.mfcc(4);
+        let mut mfcc =
+            FixedMelSpectrogram::<64, 8>::with_config(&config).unwrap();
+        let mut slice = [0.; 4];
+        assert_eq!(mfcc.next_slice(&samples, &mut slice), (64, true));
+        assert_near(&slice, &TONE_MFCC);
+    }
+
+    #[test]
+    fn matches_float() {
+        let config = config().sample_rate(16_000).upper_band_limit(7600.);
+        let mut fixed =
+            FixedMelSpectrogram::<256, 16>::with_config(&config).unwrap();
+        let mut float =
+            MelSpectrogram::<256, 16>::with_config(&config).unwrap();
+
+        let mut samples = [0; 192];
+        signal(&mut samples);
+
+        // Two windows, the second overlapping the first
+        for samples in [&samples[..128], &samples[128..]].iter() {
+            let mut expected = [0.; 16];
+            assert!(float.next_slice(samples, &mut expected).1);
+            let mut actual = [0.; 16];
+            assert!(fixed.next_slice(samples, &mut actual).1);
+
+            assert_near(&actual, &expected);
+        }
+    }
+
+    #[test]
+    fn memory() {
+        use core::mem::size_of;
+
+        let size = size_of::<FixedMelSpectrogram<512, 40>>();
+        assert!(size <= 12 * 512 + 8 * 40 + 64);
+    }
+}
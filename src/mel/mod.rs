@@ -0,0 +1,677 @@
+//! Pure-Rust log-mel spectrogram and MFCC feature generation
+//!
+//! For models that were trained on log-mel spectrograms or MFCCs, as
+//! generated by `tf.signal`, rather than with the microfrontend pipeline
+//! used by [`Frontend`](crate::Frontend).
+//!
+//! Each window of samples is scaled to the range [-1, 1), multiplied by a
+//! periodic Hann window and zero-padded to the FFT size. The power spectrum
+//! is mapped onto triangular filters equally spaced on the HTK mel scale,
+//! and the natural logarithm taken. Optionally, MFCCs are then calculated
+//! with an orthonormal DCT-II.
+//!
+//! A [`FixedMelSpectrogram`](crate::FixedMelSpectrogram) calculates the same
+//! features in fixed point, for targets without a floating point unit.
+//!
+//! With the `tensorflow` feature, both implement
+//! [`FeatureGenerator`](crate::FeatureGenerator), so they can be used with a
+//! [`FeatureProvider`](crate::FeatureProvider) to stream audio into a model
+//! input tensor.
+//!
+//! ```
+//! # #[cfg(feature = "tensorflow")]
+//! # fn main() {
+//! # use tfmicro::{FeatureProvider, MelConfig, MelSpectrogram};
+//! // 25ms windows every 10ms, 13 MFCCs from 40 mel bins
+//! let config = MelConfig::new()
+//!     .window_size_ms(25)
+//!     .window_step_size_ms(10)
+//!     .mfcc(13);
+//!
+//! let mfcc = MelSpectrogram::<512, 40>::with_config(&config).unwrap();
+//! let mut provider = FeatureProvider::<_, 98, 13>::new(mfcc);
+//!
+//! provider.push_samples(&[0; 1600]);
+//! # }
+//! # #[cfg(not(feature = "tensorflow"))]
+//! # fn main() {}
+//! ```
+
+use core::f32::consts::PI;
+
+use crate::Error;
+
+mod fft;
+mod fixed;
+
+pub use fixed::FixedMelSpectrogram;
+
+/// A parameter of a [`MelConfig`](crate::MelConfig) that failed validation
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MelConfigError {
+    /// The sample rate must be greater than zero
+    SampleRate,
+    /// The window must contain at least one sample, and no more than the
+    /// FFT size
+    WindowSize,
+    /// The window step must contain at least one sample, and no more than
+    /// the window
+    WindowStepSize,
+    /// The FFT size must be a power of two, and at least 4. For a
+    /// `FixedMelSpectrogram`, it must be no more than 32768
+    FftSize,
+    /// There must be at least one mel bin
+    MelBins,
+    /// The band limits must satisfy 0 <= lower < upper <= sample_rate / 2
+    BandLimits,
+    /// The number of MFCCs must be between one and the number of mel bins
+    MfccCount,
+    /// The log offset must be finite and greater than zero
+    LogOffset,
+}
+
+/// Configuration for a [`MelSpectrogram`](crate::MelSpectrogram)
+///
+/// The FFT size and number of mel bins are const generic parameters of the
+/// `MelSpectrogram`, and everything else is set here in a builder pattern.
+#[derive(Clone, Copy, Debug)]
+pub struct MelConfig {
+    sample_rate: u32,
+    window_size_ms: u32,
+    window_step_size_ms: u32,
+    lower_band_limit: f32,
+    upper_band_limit: f32,
+    num_mfcc: Option<usize>,
+    log_offset: f32,
+}
+
+impl Default for MelConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MelConfig {
+    /// Create a new configuration: 30ms windows every 20ms at 16kHz, with
+    /// mel bins between 125Hz and 7500Hz. No MFCCs are calculated
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 16_000,
+            window_size_ms: 30,
+            window_step_size_ms: 20,
+            lower_band_limit: 125.0,
+            upper_band_limit: 7500.0,
+            num_mfcc: None,
+            log_offset: 1e-6,
+        }
+    }
+
+    /// Sample rate of the input audio, in Hz
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Length of each window, in milliseconds
+    pub fn window_size_ms(mut self, window_size_ms: u32) -> Self {
+        self.window_size_ms = window_size_ms;
+        self
+    }
+
+    /// Step between the start of successive windows, in milliseconds
+    pub fn window_step_size_ms(mut self, window_step_size_ms: u32) -> Self {
+        self.window_step_size_ms = window_step_size_ms;
+        self
+    }
+
+    /// Lower edge of the lowest mel bin, in Hz
+    pub fn lower_band_limit(mut self, lower_band_limit: f32) -> Self {
+        self.lower_band_limit = lower_band_limit;
+        self
+    }
+
+    /// Upper edge of the highest mel bin, in Hz
+    pub fn upper_band_limit(mut self, upper_band_limit: f32) -> Self {
+        self.upper_band_limit = upper_band_limit;
+        self
+    }
+
+    /// Output the first `num_mfcc` MFCCs, instead of the log-mel
+    /// spectrogram
+    pub fn mfcc(mut self, num_mfcc: usize) -> Self {
+        self.num_mfcc = Some(num_mfcc);
+        self
+    }
+
+    /// Offset added to the mel spectrogram before taking the logarithm, to
+    /// avoid taking the logarithm of zero
+    pub fn log_offset(mut self, log_offset: f32) -> Self {
+        self.log_offset = log_offset;
+        self
+    }
+
+    /// Number of samples in each window
+    fn window_size(&self) -> usize {
+        (self.window_size_ms as u64 * self.sample_rate as u64 / 1000) as usize
+    }
+
+    /// Number of samples between the start of successive windows
+    fn window_step_size(&self) -> usize {
+        (self.window_step_size_ms as u64 * self.sample_rate as u64 / 1000)
+            as usize
+    }
+
+    /// Check each parameter, for an FFT of `fft_size` and `mel_bins` mel
+    /// bins
+    pub fn validate(
+        &self,
+        fft_size: usize,
+        mel_bins: usize,
+    ) -> Result<(), MelConfigError> {
+        let nyquist = self.sample_rate as f32 / 2.;
+
+        if self.sample_rate == 0 {
+            Err(MelConfigError::SampleRate)
+        } else if !fft_size.is_power_of_two() || fft_size < 4 {
+            Err(MelConfigError::FftSize)
+        } else if self.window_size() == 0 || self.window_size() > fft_size {
+            Err(MelConfigError::WindowSize)
+        } else if self.window_step_size() == 0
+            || self.window_step_size() > self.window_size()
+        {
+            Err(MelConfigError::WindowStepSize)
+        } else if mel_bins == 0 {
+            Err(MelConfigError::MelBins)
+        } else if !(self.lower_band_limit >= 0.
+            && self.lower_band_limit < self.upper_band_limit
+            && self.upper_band_limit <= nyquist)
+        {
+            Err(MelConfigError::BandLimits)
+        } else if matches!(self.num_mfcc, Some(n) if n == 0 || n > mel_bins) {
+            Err(MelConfigError::MfccCount)
+        } else if !(self.log_offset > 0. && self.log_offset.is_finite()) {
+            Err(MelConfigError::LogOffset)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Convert a frequency in Hz to the HTK mel scale
+fn hz_to_mel(hz: f32) -> f32 {
+    1127. * libm::logf(1. + hz / 700.)
+}
+
+/// The triangular mel filters, as the position of each FFT bin on them
+struct Filterbank {
+    lower_mel: f32,
+    upper_mel: f32,
+    mel_bins: usize,
+    bin_hz: f32,
+}
+
+impl Filterbank {
+    fn new(config: &MelConfig, fft_size: usize, mel_bins: usize) -> Self {
+        Self {
+            lower_mel: hz_to_mel(config.lower_band_limit),
+            upper_mel: hz_to_mel(config.upper_band_limit),
+            mel_bins,
+            bin_hz: config.sample_rate as f32 / fft_size as f32,
+        }
+    }
+
+    /// Mel edge `i`. Mel bin `i` rises from edge `i` to edge `i + 1`, then
+    /// falls to edge `i + 2`, and the edges are equally spaced
+    fn edge(&self, i: usize) -> f32 {
+        self.lower_mel
+            + (self.upper_mel - self.lower_mel) * i as f32
+                / (self.mel_bins + 1) as f32
+    }
+
+    /// For FFT bin `k`, the index of the mel bin whose rising edge it is
+    /// on, and the weight for that mel bin. The remaining weight is given
+    /// to the previous mel bin, on its falling edge
+    ///
+    /// Bins outside the band limits contribute nothing, and are `None`.
+    fn position(&self, k: usize) -> Option<(usize, f32)> {
+        let mel = hz_to_mel(k as f32 * self.bin_hz);
+
+        // The DC bin is excluded, as in tf.signal
+        if k == 0 || mel <= self.lower_mel || mel >= self.upper_mel {
+            return None;
+        }
+        let i = (0..=self.mel_bins)
+            .find(|&i| mel < self.edge(i + 1))
+            .unwrap_or(self.mel_bins);
+
+        Some((i, (mel - self.edge(i)) / (self.edge(i + 1) - self.edge(i))))
+    }
+}
+
+/// Periodic Hann window of `window_size`, for sample `n`
+fn hann(n: usize, window_size: usize) -> f32 {
+    0.5 - 0.5 * libm::cosf(2. * PI * n as f32 / window_size as f32)
+}
+
+/// Take the logarithm of the mel spectrogram `mel`, and write it or its
+/// MFCCs to `output`
+fn log_mel(mel: &mut [f32], config: &MelConfig, output: &mut [f32]) {
+    let log_offset = config.log_offset;
+    mel.iter_mut()
+        .for_each(|m| *m = libm::logf(*m + log_offset));
+
+    match config.num_mfcc {
+        None => output.copy_from_slice(mel),
+        Some(_) => {
+            // Orthonormal DCT-II
+            let n = mel.len() as f32;
+            for (k, out) in output.iter_mut().enumerate() {
+                let sum: f32 = mel
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &m)| {
+                        m * libm::cosf(
+                            PI * k as f32 * (2 * i + 1) as f32 / (2. * n),
+                        )
+                    })
+                    .sum();
+                let scale = if k == 0 { 1. / n } else { 2. / n };
+
+                *out = sum * libm::sqrtf(scale);
+            }
+        }
+    }
+}
+
+/// The samples of the current window, and how many have been received
+struct Frame<T, const N: usize> {
+    samples: [T; N],
+    len: usize,
+}
+
+impl<T: Copy + Default, const N: usize> Frame<T, N> {
+    fn new() -> Self {
+        Self {
+            samples: [T::default(); N],
+            len: 0,
+        }
+    }
+
+    /// Append `samples`, converted with `convert`, until the window of
+    /// `window_size` is full. Returns the number of samples read, and
+    /// whether the window is full
+    fn push<F: Fn(i16) -> T>(
+        &mut self,
+        samples: &[i16],
+        window_size: usize,
+        convert: F,
+    ) -> (usize, bool) {
+        let needed = window_size - self.len;
+        let num_samples_read = needed.min(samples.len());
+
+        for (s, &x) in self.samples[self.len..]
+            .iter_mut()
+            .zip(&samples[..num_samples_read])
+        {
+            *s = convert(x);
+        }
+        self.len += num_samples_read;
+
+        (num_samples_read, self.len == window_size)
+    }
+
+    /// Retain the overlap of a full window with the next window
+    fn advance(&mut self, window_size: usize, window_step_size: usize) {
+        self.samples.copy_within(window_step_size..window_size, 0);
+        self.len = window_size - window_step_size;
+    }
+
+    fn reset(&mut self) {
+        self.samples = [T::default(); N];
+        self.len = 0;
+    }
+}
+
+/// Generates log-mel spectrograms or MFCCs from 16-bit PCM audio, with an
+/// FFT of `FFT_SIZE` and `MEL_BINS` mel bins
+///
+/// All memory is held within this struct, so no allocator is required. It
+/// takes about `16 * FFT_SIZE + 4 * MEL_BINS` bytes.
+pub struct MelSpectrogram<const FFT_SIZE: usize, const MEL_BINS: usize> {
+    config: MelConfig,
+    window_size: usize,
+    window_step_size: usize,
+
+    frame: Frame<f32, FFT_SIZE>,
+    window: [f32; FFT_SIZE],
+    // The windowed samples, transformed in place
+    spectrum: [f32; FFT_SIZE],
+    // The first half holds the twiddle factors of the FFT. The second half
+    // holds the position of FFT bins 1 to FFT_SIZE / 2 on the mel bins, as
+    // the mel bin index plus the weight, or -1 outside the band limits. See
+    // `Filterbank::position`
+    tables: [f32; FFT_SIZE],
+    mel: [f32; MEL_BINS],
+}
+
+impl<const FFT_SIZE: usize, const MEL_BINS: usize>
+    MelSpectrogram<FFT_SIZE, MEL_BINS>
+{
+    /// Create a new MelSpectrogram with the default configuration
+    pub fn new() -> Result<Self, Error> {
+        Self::with_config(&MelConfig::new())
+    }
+
+    /// Create a new MelSpectrogram with `config`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidMelConfig` if any parameter of `config` is
+    /// invalid for this FFT size and number of mel bins.
+    pub fn with_config(config: &MelConfig) -> Result<Self, Error> {
+        config
+            .validate(FFT_SIZE, MEL_BINS)
+            .map_err(Error::InvalidMelConfig)?;
+
+        let window_size = config.window_size();
+
+        let mut window = [0.; FFT_SIZE];
+        for (n, w) in window[..window_size].iter_mut().enumerate() {
+            *w = hann(n, window_size);
+        }
+
+        let mut tables = [0.; FFT_SIZE];
+        let (twiddles, positions) = tables.split_at_mut(FFT_SIZE / 2);
+        fft::twiddles(twiddles);
+
+        let filterbank = Filterbank::new(config, FFT_SIZE, MEL_BINS);
+        for (k, p) in (1..).zip(positions.iter_mut()) {
+            *p = match filterbank.position(k) {
+                Some((i, weight)) => i as f32 + weight,
+                None => -1.,
+            };
+        }
+
+        Ok(Self {
+            config: *config,
+            window_size,
+            window_step_size: config.window_step_size(),
+
+            frame: Frame::new(),
+            window,
+            spectrum: [0.; FFT_SIZE],
+            tables,
+            mel: [0.; MEL_BINS],
+        })
+    }
+
+    /// Returns the number of features generated for each window
+    pub fn num_features(&self) -> usize {
+        self.config.num_mfcc.unwrap_or(MEL_BINS)
+    }
+
+    /// Process samples from `samples`, until either a window is complete
+    /// or `samples` is exhausted. When a window is complete, its features
+    /// are written to `slice`, which has length
+    /// [`num_features`](Self::num_features).
+    ///
+    /// Returns the number of samples read, and whether `slice` was written
+    pub fn next_slice(
+        &mut self,
+        samples: &[i16],
+        slice: &mut [f32],
+    ) -> (usize, bool) {
+        let (num_samples_read, full) =
+            self.frame
+                .push(samples, self.window_size, |x| x as f32 / 32768.);
+        if !full {
+            return (num_samples_read, false);
+        }
+
+        self.compute(slice);
+        self.frame.advance(self.window_size, self.window_step_size);
+
+        (num_samples_read, true)
+    }
+
+    /// Discard the samples received so far
+    pub fn reset(&mut self) {
+        self.frame.reset();
+    }
+
+    /// Calculate the features of the current window into `output`
+    fn compute(&mut self, output: &mut [f32]) {
+        // Windowed samples, zero padded to the FFT size
+        for ((s, x), w) in self
+            .spectrum
+            .iter_mut()
+            .zip(self.frame.samples.iter())
+            .zip(self.window.iter())
+        {
+            *s = x * w;
+        }
+
+        // Mel spectrogram from the power spectrum
+        let mel = &mut self.mel;
+        mel.iter_mut().for_each(|m| *m = 0.);
+
+        let (twiddles, positions) = self.tables.split_at(FFT_SIZE / 2);
+        fft::power_spectrum(&mut self.spectrum, twiddles, |k, power| {
+            let position = positions[k - 1];
+            if position < 0. {
+                return;
+            }
+            let i = position as usize;
+            let weight = position - i as f32;
+
+            if i < MEL_BINS {
+                mel[i] += power * weight;
+            }
+            if i > 0 && i <= MEL_BINS {
+                mel[i - 1] += power * (1. - weight);
+            }
+        });
+
+        log_mel(&mut self.mel, &self.config, output);
+    }
+}
+
+#[cfg(feature = "tensorflow")]
+impl<const FFT_SIZE: usize, const MEL_BINS: usize> crate::FeatureGenerator
+    for MelSpectrogram<FFT_SIZE, MEL_BINS>
+{
+    type Feature = f32;
+
+    fn slice_len(&self) -> usize {
+        self.num_features()
+    }
+
+    fn next_slice(
+        &mut self,
+        samples: &[i16],
+        slice: &mut [f32],
+    ) -> (usize, bool) {
+        MelSpectrogram::next_slice(self, samples, slice)
+    }
+
+    fn reset(&mut self) {
+        MelSpectrogram::reset(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic test signal, using integer arithmetic only so that
+    /// it is easily reproduced
+    pub(super) fn signal(samples: &mut [i16]) {
+        for (n, s) in samples.iter_mut().enumerate() {
+            *s = ((n * n * 37 + n * 1013) % 4001) as i16 - 2000;
+        }
+    }
+
+    pub(super) fn config() -> MelConfig {
+        MelConfig::new()
+            .sample_rate(8000)
+            .window_size_ms(8)
+            .window_step_size_ms(4)
+            .lower_band_limit(100.)
+            .upper_band_limit(3800.)
+    }
+
+    pub(super) fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!(
+                (a - e).abs() <= 1e-3 * e.abs().max(1.),
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(MelSpectrogram::<512, 40>::new().is_ok());
+    }
+
+    #[test]
+    fn invalid_config() {
+        let invalid = |config: MelConfig, error| {
+            assert_eq!(
+                MelSpectrogram::<512, 40>::with_config(&config).err(),
+                Some(Error::InvalidMelConfig(error))
+            )
+        };
+
+        invalid(
+            MelConfig::new().window_size_ms(40),
+            MelConfigError::WindowSize,
+        );
+        invalid(
+            MelConfig::new().window_step_size_ms(40),
+            MelConfigError::WindowStepSize,
+        );
+        invalid(
+            MelConfig::new().upper_band_limit(9000.),
+            MelConfigError::BandLimits,
+        );
+        invalid(MelConfig::new().mfcc(41), MelConfigError::MfccCount);
+        invalid(MelConfig::new().log_offset(0.), MelConfigError::LogOffset);
+
+        assert_eq!(
+            MelSpectrogram::<500, 40>::new().err(),
+            Some(Error::InvalidMelConfig(MelConfigError::FftSize))
+        );
+        assert_eq!(
+            MelSpectrogram::<2, 1>::with_config(&config()).err(),
+            Some(Error::InvalidMelConfig(MelConfigError::FftSize))
+        );
+    }
+
+    #[test]
+    fn windows() {
+        let mut mel = MelSpectrogram::<64, 8>::with_config(&config()).unwrap();
+        let mut slice = [0.; 8];
+
+        // The first window is 64 samples, and each subsequent window 32
+        assert_eq!(mel.next_slice(&[0; 40], &mut slice), (40, false));
+        assert_eq!(mel.next_slice(&[0; 40], &mut slice), (24, true));
+        assert_eq!(mel.next_slice(&[0; 40], &mut slice), (32, true));
+
+        mel.reset();
+        assert_eq!(mel.next_slice(&[0; 40], &mut slice), (40, false));
+    }
+
+    /// A tone at FFT bin 16 of 64, at 2kHz, with an amplitude of 0.5.
+    /// Every sample is exact, as the tone has a period of 4 samples
+    pub(super) fn tone(samples: &mut [i16]) {
+        for (n, s) in samples.iter_mut().enumerate() {
+            *s = [16384, 0, -16384, 0][n % 4];
+        }
+    }
+
+    // With a periodic Hann window as long as the FFT, the power spectrum of
+    // the tone is 64 in bin 16 and 16 in bins 15 and 17, and zero
+    // elsewhere. The reference values were calculated from this in double
+    // precision, with the mel weights of tf.signal's
+    // linear_to_mel_weight_matrix, independently of this implementation
+    pub(super) const TONE_LOG_MEL: [f32; 8] = [
+        -13.815511, -13.815511, -13.815511, -13.815511, -13.815511, 4.154293,
+        3.474854, -13.815511,
+    ];
+    pub(super) const TONE_MFCC: [f32; 4] =
+        [-26.609813, -12.17995, -0.130005, 10.498851];
+
+    #[test]
+    fn tone_reference() {
+        let mut samples = [0; 64];
+        tone(&mut samples);
+
+        let mut mel = MelSpectrogram::<64, 8>::with_config(&config()).unwrap();
+        let mut slice = [0.; 8];
+        assert_eq!(mel.next_slice(&samples, &mut slice), (64, true));
+        assert_close(&slice, &TONE_LOG_MEL);
+
+        let config = config().mfcc(4);
+        let mut mfcc = MelSpectrogram::<64, 8>::with_config(&config).unwrap();
+        let mut slice = [0.; 4];
+        assert_eq!(mfcc.next_slice(&samples, &mut slice), (64, true));
+        assert_close(&slice, &TONE_MFCC);
+    }
+
+    #[test]
+    fn memory() {
+        use core::mem::size_of;
+
+        // Only the buffers scale with the FFT size
+        assert!(size_of::<MelSpectrogram<512, 40>>() <= 16 * 512 + 4 * 40 + 64);
+    }
+
+    // Reference values were calculated in double precision with a direct
+    // DFT, for the first two windows of the test signal
+
+    #[test]
+    fn log_mel_reference() {
+        let mut mel = MelSpectrogram::<64, 8>::with_config(&config()).unwrap();
+
+        let mut samples = [0; 96];
+        signal(&mut samples);
+
+        let mut slice = [0.; 8];
+        assert_eq!(mel.next_slice(&samples, &mut slice), (64, true));
+        assert_close(&slice, &LOG_MEL_0);
+        assert_eq!(mel.next_slice(&samples[64..], &mut slice), (32, true));
+        assert_close(&slice, &LOG_MEL_1);
+    }
+
+    #[test]
+    fn mfcc_reference() {
+        let config = config().mfcc(4);
+        let mut mfcc = MelSpectrogram::<64, 8>::with_config(&config).unwrap();
+        assert_eq!(mfcc.num_features(), 4);
+
+        let mut samples = [0; 96];
+        signal(&mut samples);
+
+        let mut slice = [0.; 4];
+        assert_eq!(mfcc.next_slice(&samples, &mut slice), (64, true));
+        assert_close(&slice, &MFCC_0);
+        assert_eq!(mfcc.next_slice(&samples[64..], &mut slice), (32, true));
+        assert_close(&slice, &MFCC_1);
+    }
+
+    const LOG_MEL_0: [f32; 8] = [
+        -3.557731, -2.34861, -1.178137, -1.618763, -3.144008, -1.993954,
+        -1.613629, -1.998824,
+    ];
+    const LOG_MEL_1: [f32; 8] = [
+        -4.366134, -3.137049, -3.268632, -2.561641, -2.190785, -2.678009,
+        -1.795373, -0.856823,
+    ];
+    const MFCC_0: [f32; 4] = [-6.170799, -0.694632, -0.517868, -1.400159];
+    const MFCC_1: [f32; 4] = [-7.37316, -2.478963, -0.023294, -0.935413];
+}
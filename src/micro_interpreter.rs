@@ -8,11 +8,11 @@
 //!
 //! ```rust
 //! # use tfmicro::{
-//! #     MicroInterpreter, AllOpResolver, Model,
+//! #     include_model, MicroInterpreter, AllOpResolver, Model,
 //! # };
 //! // model
-//! let model = include_bytes!("../examples/models/hello_world.tflite");
-//! let model = Model::from_buffer(&model[..]).unwrap();
+//! let model = include_model!("../examples/models/hello_world.tflite");
+//! let model = Model::from_buffer(model).unwrap();
 //!
 //! // resolver
 //! let all_op_resolver = AllOpResolver::new();
@@ -23,25 +23,25 @@
 //!
 //! let _ = MicroInterpreter::new(
 //!     &model,
-//!     all_op_resolver,
+//!     &all_op_resolver,
 //!     &mut tensor_arena[..],
 //! ).unwrap();
 //! ```
 //!
 //! Remember that once once you have instantiated the `MicroInterpreter`,
-//! the references you provided for `model`, `tensor_arena` must remain in
-//! scope. This is because the underlying C++ microinterpreter
-//! contains pointers to these objects.
+//! the references you provided for `model`, `op_resolver` and
+//! `tensor_arena` must remain in scope. This is because the underlying C++
+//! microinterpreter contains pointers to these objects.
 //!
 //! For example, the following will not compile:
 //!
 //! ```compile_fail
 //! # use tfmicro::{
-//! #     MicroInterpreter, AllOpResolver, Model,
+//! #     include_model, MicroInterpreter, AllOpResolver, Model,
 //! # };
 //! let mut interpreter = {
-//!     let model = include_bytes!("../examples/models/hello_world.tflite");
-//!     let model = Model::from_buffer(&model[..]).unwrap();
+//!     let model = include_model!("../examples/models/hello_world.tflite");
+//!     let model = Model::from_buffer(model).unwrap();
 //!
 //!     // ...
 //! # let all_op_resolver = AllOpResolver::new();
@@ -50,7 +50,7 @@
 //!
 //!     MicroInterpreter::new(
 //!         &model,
-//!         all_op_resolver,
+//!         &all_op_resolver,
 //!         &mut tensor_arena[..],
 //!     ).unwrap()
 //! }; // Error [model, ..] dropped here whilst still borrowed
@@ -59,9 +59,14 @@
 //! interpreter.input_info(0);
 //! ```
 
+use core::cell::UnsafeCell;
 use core::convert::TryInto;
 use core::marker::PhantomData;
 use core::mem::MaybeUninit;
+use core::ptr;
+use core::slice;
+#[cfg(not(feature = "std"))]
+use core::sync::atomic::{AtomicU8, Ordering};
 
 use crate::micro_error_reporter::MicroErrorReporter;
 use crate::micro_op_resolver::OpResolverRepr;
@@ -74,27 +79,138 @@ use crate::bindings;
 use crate::bindings::tflite;
 
 cpp! {{
+    #include "tensorflow/lite/core/api/op_resolver.h"
     #include "tensorflow/lite/micro/micro_interpreter.h"
-    #include "tensorflow/lite/micro/micro_mutable_op_resolver.h"
     #include "tensorflow/lite/micro/kernels/micro_ops.h"
     #include "tensorflow/lite/micro/micro_error_reporter.h"
     #include "tensorflow/lite/micro/testing/micro_test.h"
     #include "tensorflow/lite/schema/schema_generated.h"
     #include "tensorflow/lite/version.h"
+
+    #include <new>
+
+    // An OpResolver over an array of registrations that is owned by Rust
+    class RustOpResolver : public tflite::OpResolver {
+     public:
+      RustOpResolver(const TfLiteRegistration* registrations, size_t len)
+          : registrations_(registrations), len_(len) {}
+
+      const TfLiteRegistration* FindOp(tflite::BuiltinOperator op,
+                                       int version) const override {
+        for (size_t i = 0; i < len_; ++i) {
+          if (registrations_[i].builtin_code == op) {
+            return &registrations_[i];
+          }
+        }
+        return nullptr;
+      }
+
+      const TfLiteRegistration* FindOp(const char* op,
+                                       int version) const override {
+        // Custom operators are not supported
+        return nullptr;
+      }
+
+     private:
+      const TfLiteRegistration* registrations_;
+      size_t len_;
+    };
 }}
 
-static mut ERROR_REPORTER: MaybeUninit<MicroErrorReporter> =
-    MaybeUninit::uninit();
+/// Storage for the global error reporter
+struct ErrorReporterCell(UnsafeCell<MaybeUninit<MicroErrorReporter>>);
+
+// Only written once, see `error_reporter`
+unsafe impl Sync for ErrorReporterCell {}
+
+static ERROR_REPORTER: ErrorReporterCell =
+    ErrorReporterCell(UnsafeCell::new(MaybeUninit::uninit()));
+
+/// Returns a pointer to the global error reporter, initialising it the first
+/// time
+///
+/// With the `std` feature this is guarded by a `Once`. Without it, the first
+/// caller initialises the reporter, and any others spin until it is done, so
+/// interpreters can be created on several threads at the same time either way.
+fn error_reporter() -> *mut MicroErrorReporter {
+    #[cfg(feature = "std")]
+    {
+        static INIT: std::sync::Once = std::sync::Once::new();
+
+        // The reporter is only written here, which happens once
+        INIT.call_once(|| unsafe {
+            *ERROR_REPORTER.0.get() =
+                MaybeUninit::new(MicroErrorReporter::new());
+        });
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        const UNINIT: u8 = 0;
+        const INITIALISING: u8 = 1;
+        const READY: u8 = 2;
+        static STATE: AtomicU8 = AtomicU8::new(UNINIT);
+
+        // Only the caller that moves the state from UNINIT writes the
+        // reporter. Others wait until it is READY before using it
+        match STATE.compare_exchange(
+            UNINIT,
+            INITIALISING,
+            Ordering::Acquire,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => {
+                unsafe {
+                    *ERROR_REPORTER.0.get() =
+                        MaybeUninit::new(MicroErrorReporter::new());
+                }
+                STATE.store(READY, Ordering::Release);
+            }
+            Err(_) => {
+                while STATE.load(Ordering::Acquire) != READY {
+                    core::hint::spin_loop();
+                }
+            }
+        }
+    }
+
+    ERROR_REPORTER.0.get().cast()
+}
 
 /// An interpreter for TensorFlow models
 pub struct MicroInterpreter<'a> {
-    // bindgen types
-    micro_interpreter: tflite::MicroInterpreter,
+    // bindgen types. The C++ interpreter is placed in the tensor arena, see
+    // `new`
+    micro_interpreter: *mut tflite::MicroInterpreter,
+
+    // Owns the tensor arena if it was passed as a `Vec`, so that it lives as
+    // long as the interpreter
+    _tensor_arena: ManagedSlice<'a, u8>,
+
+    // Bytes at the start of the tensor arena used by the C++ op resolver
+    // and interpreter
+    arena_header_bytes: usize,
 
     // See https://doc.rust-lang.org/std/marker/struct.PhantomData.html#unused-lifetime-parameters
     _phantom: PhantomData<&'a ()>,
 }
 
+// The C++ interpreter is in the tensor arena, so moving a
+// `MicroInterpreter` only moves a pointer to it, and the pointers it keeps
+// to itself stay valid. It points into:
+//
+// * the model, which is a `Model` and so `Sync`, and is only read
+// * the op registrations of a resolver, which is `Sync` as required by
+//   `OpResolverRepr`, and are only read
+// * the tensor arena, which it borrows mutably or owns, so no other thread
+//   can reach it
+// * the global error reporter, which is written once before any interpreter
+//   refers to it, and is only read after that
+//
+// Moving the interpreter to another thread only shares references to the
+// model and the resolver with the thread that built it, which is sound as
+// both are `Sync`. Nothing it points to is tied to the thread that built it
+unsafe impl Send for MicroInterpreter<'_> {}
+
 impl<'a> MicroInterpreter<'a> {
     // From tensorflow source:
     // tensorflow/lite/micro/micro_interpreter.h
@@ -111,81 +227,125 @@ impl<'a> MicroInterpreter<'a> {
     /// Create a new micro_interpreter from a Model, a MicroOpResolver and a
     /// tensor arena (scratchpad).
     ///
+    /// The op resolver is borrowed, as the interpreter refers to the
+    /// operator registrations it contains for as long as it exists.
+    ///
+    /// The small C++ op resolver that TensorFlow looks the registrations up
+    /// through, and the C++ interpreter itself, are placed at the start of
+    /// the tensor arena. The resolver must live as long as the interpreter,
+    /// and the interpreter keeps pointers to itself, so neither can move.
+    /// This takes a few hundred bytes of the arena, depending on the target,
+    /// in addition to what TensorFlow needs for the model, and is included
+    /// in [`arena_used_bytes`](Self::arena_used_bytes).
+    ///
     /// # Errors
     ///
+    /// Returns `Error::OpResolverCapacityExceeded` if more operators were
+    /// added to the op resolver than it has capacity for.
+    ///
     /// Returns `Error::InterpreterInitError` if there is an error creating
     /// the interpreter.
     ///
     /// Returns `Error::AllocateTensors` if there is error in the call to
     /// `AllocateTensors`.
-    pub fn new<'m: 'a, 't: 'a, TArena, OpResolver>(
+    pub fn new<'m: 'a, 'r: 'a, 't: 'a, TArena, OpResolver>(
         model: &'m Model,
-        resolver: OpResolver,
+        resolver: &'r OpResolver,
         tensor_arena: TArena,
     ) -> Result<Self, Error>
     where
         OpResolver: OpResolverRepr,
         TArena: Into<ManagedSlice<'t, u8>>,
     {
-        let resolver = resolver.to_inner();
+        let registrations = resolver.registrations()?;
+        let registrations_len = registrations.len();
+        let model = model.as_tflite();
+        let registrations = registrations.as_ptr();
 
         let mut tensor_arena = tensor_arena.into();
 
         let tensor_arena_size = tensor_arena.len();
-        let tensor_arena = tensor_arena.as_mut_ptr();
+        let tensor_arena_ptr = tensor_arena.as_mut_ptr();
 
-        // Idempotent block to get a pointer to a MicroErrorReporter
-        let micro_error_reporter_ref = unsafe {
-            // Initialise MicroErrorReporter. We assume that `new` is a pure
-            // function that only fills in the MicroErrorReporter vtable
-            let micro_error_reporter = MicroErrorReporter::new();
-            ERROR_REPORTER = MaybeUninit::new(micro_error_reporter);
-
-            &ERROR_REPORTER // return reference with 'static lifetime
-        };
+        // Pointer to a MicroErrorReporter with 'static lifetime
+        let micro_error_reporter_ref = error_reporter();
 
         let mut status = bindings::TfLiteStatus::kTfLiteError;
-
-        // Create interpreter
-        let mut micro_interpreter = unsafe {
+        let mut allocate_tensors_status = bindings::TfLiteStatus::kTfLiteError;
+
+        // The interpreter keeps a reference to the C++ op resolver, so it
+        // must live as long as the interpreter does. The interpreter also
+        // keeps pointers to itself, so it must not move once it is built.
+        // Both are placed at the start of the tensor arena, which is
+        // borrowed or owned for 'a and does not move when the arena does.
+        // The registrations the resolver points to are borrowed for 'r
+        let mut arena_header_bytes = 0usize;
+        let micro_interpreter = unsafe {
+            let arena_header_bytes_ref = &mut arena_header_bytes;
             let status_ref = &mut status;
+            let allocate_tensors_status_ref = &mut allocate_tensors_status;
 
-            cpp! ([
+            cpp!([
                 model as "const tflite::Model*",
-                resolver as "tflite::MicroMutableOpResolver<128>",
-                tensor_arena as "uint8_t*",
+                registrations as "const TfLiteRegistration*",
+                registrations_len as "size_t",
+                tensor_arena_ptr as "uint8_t*",
                 tensor_arena_size as "size_t",
                 micro_error_reporter_ref as "tflite::MicroErrorReporter*",
-                status_ref as "TfLiteStatus*"
-            ] -> tflite::MicroInterpreter as "tflite::MicroInterpreter"
+                arena_header_bytes_ref as "size_t*",
+                status_ref as "TfLiteStatus*",
+                allocate_tensors_status_ref as "TfLiteStatus*"
+            ] -> *mut tflite::MicroInterpreter as "tflite::MicroInterpreter*"
               {
+                  // The rest of the arena starts on a 16 byte boundary, as
+                  // it would have done without the header
+                  const uintptr_t resolver_align = alignof(RustOpResolver);
+                  const uintptr_t interpreter_align =
+                      alignof(tflite::MicroInterpreter);
+                  uintptr_t start = (uintptr_t)tensor_arena_ptr;
+                  uintptr_t resolver_at = (start + resolver_align - 1)
+                      & ~(resolver_align - 1);
+                  uintptr_t interpreter_at = (resolver_at
+                      + sizeof(RustOpResolver) + interpreter_align - 1)
+                      & ~(interpreter_align - 1);
+                  uintptr_t end = (interpreter_at
+                      + sizeof(tflite::MicroInterpreter) + 15)
+                      & ~(uintptr_t)15;
+                  size_t used = end - start;
+                  if (used > tensor_arena_size) {
+                      return nullptr;
+                  }
+                  *arena_header_bytes_ref = used;
+
+                  RustOpResolver* op_resolver = new ((void*)resolver_at)
+                      RustOpResolver(registrations, registrations_len);
                   tflite::ErrorReporter* error_reporter = micro_error_reporter_ref;
+
                   // Build an interpreter to run the model with.
-                  tflite::MicroInterpreter interpreter(model,
-                                                       resolver,
-                                                       tensor_arena,
-                                                       tensor_arena_size,
-                                                       error_reporter);
+                  tflite::MicroInterpreter* interpreter =
+                      new ((void*)interpreter_at)
+                      tflite::MicroInterpreter(model,
+                                               *op_resolver,
+                                               tensor_arena_ptr + used,
+                                               tensor_arena_size - used,
+                                               error_reporter);
 
                   // Get status
-                  *status_ref = interpreter.initialization_status();
+                  *status_ref = interpreter->initialization_status();
+
+                  if (*status_ref == kTfLiteOk) {
+                      *allocate_tensors_status_ref = interpreter->AllocateTensors();
+                  }
 
                   return interpreter;
               })
         };
+        if micro_interpreter.is_null() {
+            return Err(Error::AllocateTensorsError);
+        }
         if status != bindings::TfLiteStatus::kTfLiteOk {
             return Err(Error::InterpreterInitError);
         }
-
-        // Allocate tensors
-        let allocate_tensors_status = unsafe {
-            let interpreter_ref = &mut micro_interpreter;
-
-            cpp! ([interpreter_ref as "tflite::MicroInterpreter*"]
-                   -> bindings::TfLiteStatus as "TfLiteStatus" {
-                return interpreter_ref->AllocateTensors();
-            })
-        };
         if allocate_tensors_status != bindings::TfLiteStatus::kTfLiteOk {
             return Err(Error::AllocateTensorsError);
         }
@@ -193,6 +353,8 @@ impl<'a> MicroInterpreter<'a> {
         // Create self
         Ok(Self {
             micro_interpreter,
+            _tensor_arena: tensor_arena,
+            arena_header_bytes,
             _phantom: PhantomData,
         })
     }
@@ -205,7 +367,7 @@ impl<'a> MicroInterpreter<'a> {
     /// Panics if the underlying tensor cannot be represented by a
     /// [`TensorInfo`](crate::tensor::TensorInfo).
     pub fn input_info(&self, n: usize) -> TensorInfo {
-        let interpreter = &self.micro_interpreter;
+        let interpreter = self.micro_interpreter;
         let input_tensor: &'a Tensor = unsafe {
             // Call method on micro_interpreter
             let inp = cpp!([
@@ -246,22 +408,7 @@ impl<'a> MicroInterpreter<'a> {
         n: usize,
         data: &[T],
     ) -> Result<(), Error> {
-        let interpreter = &self.micro_interpreter;
-        let input_tensor: &mut Tensor = unsafe {
-            // Call method on micro_interpreter
-            let inp = cpp!([
-                interpreter as "tflite::MicroInterpreter*",
-                n as "size_t"]
-                -> *mut bindings::TfLiteTensor as "TfLiteTensor*" {
-                return interpreter->input(n);
-            });
-
-            // Check result
-            assert!(!inp.is_null(), "Obtained nullptr from TensorFlow");
-
-            // From bindgen type to Rust type
-            inp.into()
-        };
+        let input_tensor = self.input_tensor(n);
 
         // Returns Err if tensor cannot be repesented (eg. unimplemented
         // type)
@@ -278,6 +425,32 @@ impl<'a> MicroInterpreter<'a> {
         }
     }
 
+    /// Returns a mutable reference to the `n`th input tensor. This can be
+    /// used to fill the input tensor in place.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the attempt to get a pointer from TensorFlow returns a
+    /// nullptr. This can occour if the tensor index `n` is invalid.
+    pub fn input_tensor(&mut self, n: usize) -> &mut Tensor {
+        let interpreter = self.micro_interpreter;
+        unsafe {
+            // Call method on micro_interpreter
+            let inp = cpp!([
+                interpreter as "tflite::MicroInterpreter*",
+                n as "size_t"]
+                -> *mut bindings::TfLiteTensor as "TfLiteTensor*" {
+                return interpreter->input(n);
+            });
+
+            // Check result
+            assert!(!inp.is_null(), "Obtained nullptr from TensorFlow");
+
+            // From bindgen type to Rust type
+            inp.into()
+        }
+    }
+
     /// Runs the Tensorflow operation to transform input tensors to output
     /// tensors
     ///
@@ -286,7 +459,7 @@ impl<'a> MicroInterpreter<'a> {
     /// Returns a TensorFlow [`Status`](crate::Status) if an error occours in
     /// TensorFlow.
     pub fn invoke(&mut self) -> Result<(), Status> {
-        let interpreter = &self.micro_interpreter;
+        let interpreter = self.micro_interpreter;
 
         let status = unsafe {
             cpp!([interpreter as "tflite::MicroInterpreter*"]
@@ -302,14 +475,141 @@ impl<'a> MicroInterpreter<'a> {
         }
     }
 
-    /// Returns an immutable reference to the nth output tensor
+    /// Runs the model over a sequence of samples, calling `outputs` with the
+    /// output tensor for each sample in turn
+    ///
+    /// The model must have one input and one output tensor. Each sample in
+    /// `inputs` fills one batch entry of the input tensor. The element type
+    /// and shape of the tensor are checked once, rather than for every
+    /// sample, and the arena is reused throughout.
+    ///
+    /// If the first dimension of the input tensor is greater than one, the
+    /// model is run once per batch of that many samples, and the output
+    /// tensor is split on its first dimension. `outputs` is then
+    /// called with a tensor that has a first dimension of one. If the last
+    /// batch is not full, the remaining batch entries keep the data of the
+    /// previous batch, and their outputs are skipped.
+    ///
+    /// ```
+    /// # use tfmicro::{include_model, AllOpResolver, MicroInterpreter, Model};
+    /// # let model = include_model!("../examples/models/hello_world.tflite");
+    /// # let model = Model::from_buffer(model).unwrap();
+    /// # let op_resolver = AllOpResolver::new();
+    /// # let mut arena = [0; 4 * 1024];
+    /// # let mut interpreter =
+    /// #     MicroInterpreter::new(&model, &op_resolver, &mut arena[..]).unwrap();
+    /// let xs = [[0.0f32], [0.5], [1.0]];
+    /// let mut ys = Vec::new();
+    ///
+    /// interpreter
+    ///     .invoke_batch(xs.iter().map(|x| &x[..]), |output| {
+    ///         ys.push(output.as_data::<f32>()[0])
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(ys.len(), 3);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnsupportedTensorCount` if the model does not have
+    /// exactly one input and one output tensor.
+    ///
+    /// Returns `Error::ElementTypeMismatch` if `T` is not the element type
+    /// of the input tensor.
+    ///
+    /// Returns `Error::InputDataLenMismatch` if a sample is not the length
+    /// of one batch entry of the input tensor. Earlier samples have already
+    /// been run.
+    ///
+    /// Returns `Error::UnsupportedTensorShape` if the model is batched, and
+    /// the first dimension of the output tensor is not the same as that of
+    /// the input tensor.
+    ///
+    /// Returns `Error::BatchInvokeFailed` with the index of the first sample
+    /// in the batch if an error occours in TensorFlow. Earlier samples have
+    /// already been run.
+    pub fn invoke_batch<'d, T, I, F>(
+        &mut self,
+        inputs: I,
+        mut outputs: F,
+    ) -> Result<(), Error>
+    where
+        T: ElemTypeOf + Clone + 'd,
+        I: IntoIterator<Item = &'d [T]>,
+        F: FnMut(&Tensor),
+    {
+        if self.inputs_len() != 1 || self.outputs_len() != 1 {
+            return Err(Error::UnsupportedTensorCount);
+        }
+
+        // Check the input tensor once. Its data stays at the same place in
+        // the arena for as long as the interpreter exists
+        let (batch, sample_len, input) = {
+            let tensor = self.input_tensor(0);
+            let info: TensorInfo = tensor.inner().try_into()?;
+            if info.element_type != T::elem_type_of() {
+                return Err(Error::ElementTypeMismatch);
+            }
+            let batch = match info.dims.first() {
+                Some(&d) if d > 1 => d as usize,
+                _ => 1,
+            };
+
+            let data = tensor.as_data_mut::<T>();
+            (batch, data.len() / batch, data.as_mut_ptr())
+        };
+        if batch > 1
+            && self.output(0).info().dims.first() != Some(&(batch as i32))
+        {
+            return Err(Error::UnsupportedTensorShape);
+        }
+
+        let mut inputs = inputs.into_iter();
+        let mut first = 0;
+        loop {
+            // Fill as many batch entries as there are samples left
+            let mut len = 0;
+            for (n, sample) in inputs.by_ref().take(batch).enumerate() {
+                if sample.len() != sample_len {
+                    return Err(Error::InputDataLenMismatch);
+                }
+
+                let entry = unsafe {
+                    slice::from_raw_parts_mut(
+                        input.add(n * sample_len),
+                        sample_len,
+                    )
+                };
+                entry.clone_from_slice(sample);
+                len = n + 1;
+            }
+            if len == 0 {
+                return Ok(());
+            }
+
+            self.invoke().map_err(|_| Error::BatchInvokeFailed(first))?;
+
+            if batch == 1 {
+                outputs(self.output(0));
+            } else {
+                split_batch(self.output(0), batch, len, &mut outputs);
+            }
+            if len < batch {
+                return Ok(());
+            }
+            first += batch;
+        }
+    }
+
+    /// Returns an immutable reference to the nth output tensor. The
+    /// reference must be dropped before the interpreter is invoked again
     ///
     /// # Panics
     ///
     /// Panics if the attempt to get a pointer from TensorFlow returns a
     /// nullptr. This can occour if the tensor index `n` is invalid.
-    pub fn output(&self, n: usize) -> &'a Tensor {
-        let interpreter = &self.micro_interpreter;
+    pub fn output(&self, n: usize) -> &Tensor {
+        let interpreter = self.micro_interpreter;
         unsafe {
             // Call method on micro_interpreter
             let out = cpp!([
@@ -327,30 +627,137 @@ impl<'a> MicroInterpreter<'a> {
         }
     }
 
+    /// Returns the number of input tensors
+    pub fn inputs_len(&self) -> usize {
+        let interpreter = self.micro_interpreter;
+        unsafe {
+            cpp!([interpreter as "tflite::MicroInterpreter*"]
+                  -> usize as "size_t" {
+                return interpreter->inputs_size();
+            })
+        }
+    }
+
+    /// Returns the number of output tensors
+    pub fn outputs_len(&self) -> usize {
+        let interpreter = self.micro_interpreter;
+        unsafe {
+            cpp!([interpreter as "tflite::MicroInterpreter*"]
+                  -> usize as "size_t" {
+                return interpreter->outputs_size();
+            })
+        }
+    }
+
+    /// Returns the number of tensors in the model
+    pub(crate) fn tensors_len(&self) -> usize {
+        let interpreter = self.micro_interpreter;
+        unsafe {
+            cpp!([interpreter as "tflite::MicroInterpreter*"]
+                  -> usize as "size_t" {
+                return interpreter->tensors_size();
+            })
+        }
+    }
+
+    /// Returns a mutable reference to the `n`th tensor of the model
+    ///
+    /// # Panics
+    ///
+    /// Panics if the attempt to get a pointer from TensorFlow returns a
+    /// nullptr. This can occour if the tensor index `n` is invalid.
+    pub(crate) fn tensor_mut(&mut self, n: usize) -> &mut Tensor {
+        let interpreter = self.micro_interpreter;
+        unsafe {
+            let tensor = cpp!([
+                interpreter as "tflite::MicroInterpreter*",
+                n as "size_t"]
+                -> *mut bindings::TfLiteTensor as "TfLiteTensor*" {
+                return interpreter->tensor(n);
+            });
+
+            // Check result
+            assert!(!tensor.is_null(), "Obtained nullptr from TensorFlow");
+
+            // From bindgen type to Rust type
+            tensor.into()
+        }
+    }
+
     /// Returns the actual number of bytes required for the arena
     ///
+    /// This includes the bytes at the start of the arena that hold the C++
+    /// op resolver and interpreter, see [`new`](Self::new), so an arena
+    /// of this size is large enough to build the interpreter again.
     pub fn arena_used_bytes(&self) -> usize {
-        let interpreter = &self.micro_interpreter;
-        unsafe {
+        let interpreter = self.micro_interpreter;
+        let used = unsafe {
             cpp!([interpreter as "tflite::MicroInterpreter*"]
                   -> usize as "size_t" {
                 return interpreter->arena_used_bytes();
             })
-        }
+        };
+
+        self.arena_header_bytes + used
+    }
+}
+
+/// Dimensions of a tensor, laid out as a `TfLiteIntArray`
+#[repr(C)]
+struct Dims {
+    size: cty::c_int,
+    data: [cty::c_int; Dims::CAPACITY],
+}
+
+impl Dims {
+    const CAPACITY: usize = 8;
+}
+
+/// Call `f` with a tensor for each of the first `len` entries of the
+/// `batch` entries on the first dimension of `tensor`
+fn split_batch<F: FnMut(&Tensor)>(
+    tensor: &Tensor,
+    batch: usize,
+    len: usize,
+    f: &mut F,
+) {
+    let info = tensor.info();
+    assert!(info.dims.len() <= Dims::CAPACITY, "Too many dimensions");
+
+    let mut dims = Dims {
+        size: info.dims.len() as cty::c_int,
+        data: [0; Dims::CAPACITY],
+    };
+    dims.data[..info.dims.len()].copy_from_slice(info.dims);
+    dims.data[0] = 1;
+
+    // A copy of the tensor, with the dimensions and data of one entry. It
+    // only lives for the call to `f`, whilst `tensor` is borrowed
+    let inner = tensor.inner();
+    let entry_bytes = inner.bytes / batch;
+    let mut entry = unsafe { ptr::read(inner) };
+    entry.bytes = entry_bytes;
+    entry.dims = &mut dims as *mut Dims as *mut bindings::TfLiteIntArray;
+
+    for n in 0..len {
+        entry.data.raw = unsafe { inner.data.raw.add(n * entry_bytes) };
+        f((&mut entry as *mut bindings::TfLiteTensor).into());
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::include_model;
     use crate::micro_op_resolver::AllOpResolver;
+    use crate::schema::builder::{Builder, Field::*};
     use crate::tensor::ElementType;
 
     #[test]
     fn new_interpreter_static_arena() {
         // model
-        let model = include_bytes!("../examples/models/hello_world.tflite");
-        let model = Model::from_buffer(&model[..]).unwrap();
+        let model = include_model!("../examples/models/hello_world.tflite");
+        let model = Model::from_buffer(model).unwrap();
 
         // resolver
         let all_op_resolver = AllOpResolver::new();
@@ -361,7 +768,7 @@ mod tests {
 
         let _ = MicroInterpreter::new(
             &model,
-            all_op_resolver,
+            &all_op_resolver,
             &mut tensor_arena[..],
         )
         .unwrap();
@@ -377,8 +784,8 @@ mod tests {
     #[cfg(any(feature = "std", feature = "alloc"))]
     fn new_interpreter_alloc_arena() {
         // model
-        let model = include_bytes!("../examples/models/hello_world.tflite");
-        let model = Model::from_buffer(&model[..]).unwrap();
+        let model = include_model!("../examples/models/hello_world.tflite");
+        let model = Model::from_buffer(model).unwrap();
 
         // resolver
         let all_op_resolver = AllOpResolver::new();
@@ -386,15 +793,21 @@ mod tests {
         // arena
         let tensor_arena: Vec<u8> = vec![0u8; 4 * 1024];
 
-        let _ = MicroInterpreter::new(&model, all_op_resolver, tensor_arena)
-            .unwrap();
+        let mut interpreter =
+            MicroInterpreter::new(&model, &all_op_resolver, tensor_arena)
+                .unwrap();
+
+        // The interpreter owns the arena, so it is still valid here
+        interpreter.input(0, &[1.57f32]).unwrap();
+        interpreter.invoke().unwrap();
+        assert!(interpreter.output(0).as_data::<f32>()[0] > 0.9);
     }
 
     #[test]
     fn input_info() {
         // model
-        let model = include_bytes!("../examples/models/hello_world.tflite");
-        let model = Model::from_buffer(&model[..]).unwrap();
+        let model = include_model!("../examples/models/hello_world.tflite");
+        let model = Model::from_buffer(model).unwrap();
 
         // resolver
         let all_op_resolver = AllOpResolver::new();
@@ -405,7 +818,7 @@ mod tests {
 
         let interpreter = MicroInterpreter::new(
             &model,
-            all_op_resolver,
+            &all_op_resolver,
             &mut tensor_arena[..],
         )
         .unwrap();
@@ -416,5 +829,195 @@ mod tests {
         assert_eq!(info.name, "dense_2_input");
         assert_eq!(info.element_type, ElementType::Float32);
         assert_eq!(info.dims, [1, 1]);
+        assert_eq!(info.quantization, None);
+    }
+
+    #[test]
+    fn inputs_outputs_len() {
+        let model = include_model!("../examples/models/hello_world.tflite");
+        let model = Model::from_buffer(model).unwrap();
+
+        let all_op_resolver = AllOpResolver::new();
+
+        const TENSOR_ARENA_SIZE: usize = 4 * 1024;
+        let mut tensor_arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
+
+        let mut interpreter = MicroInterpreter::new(
+            &model,
+            &all_op_resolver,
+            &mut tensor_arena[..],
+        )
+        .unwrap();
+
+        assert_eq!(interpreter.inputs_len(), 1);
+        assert_eq!(interpreter.outputs_len(), 1);
+
+        // A float32 [1, 1] input is 4 bytes
+        interpreter.input(0, &[0.5f32]).unwrap();
+        assert_eq!(
+            interpreter.input_tensor(0).as_bytes(),
+            0.5f32.to_ne_bytes()
+        );
+    }
+
+    #[test]
+    fn invoke_batch() {
+        let model = include_model!("../examples/models/hello_world.tflite");
+        let model = Model::from_buffer(model).unwrap();
+
+        let all_op_resolver = AllOpResolver::new();
+
+        const TENSOR_ARENA_SIZE: usize = 4 * 1024;
+        let mut tensor_arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
+
+        let mut interpreter = MicroInterpreter::new(
+            &model,
+            &all_op_resolver,
+            &mut tensor_arena[..],
+        )
+        .unwrap();
+
+        // sin(x) for each sample
+        let xs = [[0.0f32], [1.57], [3.14]];
+        let mut ys = [0.0f32; 3];
+        let mut n = 0;
+        interpreter
+            .invoke_batch(xs.iter().map(|x| &x[..]), |output| {
+                assert_eq!(output.info().dims, [1, 1]);
+                ys[n] = output.as_data::<f32>()[0];
+                n += 1;
+            })
+            .unwrap();
+        assert_eq!(n, 3);
+        assert!(ys[0].abs() < 0.1 && ys[1] > 0.9 && ys[2].abs() < 0.2);
+
+        assert_eq!(
+            interpreter.invoke_batch(Some(&[0i8][..]), |_| {}),
+            Err(Error::ElementTypeMismatch)
+        );
+        assert_eq!(
+            interpreter.invoke_batch(Some(&[0.0f32, 1.0][..]), |_| {}),
+            Err(Error::InputDataLenMismatch)
+        );
+    }
+
+    /// A model with one RELU operator, whose float32 input and output
+    /// tensors each hold a batch of three entries of two values
+    fn batched_model() -> Builder {
+        let mut model = Builder::new("model");
+        model.table(
+            "model",
+            &[
+                I32(3),
+                Offset("codes"),
+                Offset("subgraphs"),
+                Absent,
+                Offset("buffers"),
+            ],
+        );
+        model.vector("codes", &[Offset("relu")]);
+        model.vector("subgraphs", &[Offset("subgraph")]);
+        model.vector("buffers", &[Offset("buffer")]);
+        model.table("buffer", &[]);
+        model.table("relu", &[U8(19), Absent, Absent, I32(19)]);
+
+        // Tensor 0 is the input and tensor 1 the output, of the subgraph
+        // and of its operator
+        model.table(
+            "subgraph",
+            &[
+                Offset("tensors"),
+                Offset("inputs"),
+                Offset("outputs"),
+                Offset("operators"),
+            ],
+        );
+        model.vector("tensors", &[Offset("x"), Offset("y")]);
+        model.vector("operators", &[Offset("operator")]);
+        model.table("operator", &[Absent, Offset("inputs"), Offset("outputs")]);
+        model.vector("inputs", &[I32(0)]);
+        model.vector("outputs", &[I32(1)]);
+
+        // Float32 tensors with the shape and name
+        model.table("x", &[Offset("shape"), Absent, Absent, Offset("x_name")]);
+        model.table("y", &[Offset("shape"), Absent, Absent, Offset("y_name")]);
+        model.vector("shape", &[I32(3), I32(2)]);
+        model.string("x_name", "x");
+        model.string("y_name", "y");
+
+        model
+    }
+
+    #[test]
+    fn invoke_batch_batched() {
+        use crate::micro_op_resolver::MutableOpResolver;
+
+        let buffer = batched_model();
+        let model = Model::from_buffer(buffer.as_bytes()).unwrap();
+        let resolver = MutableOpResolver::<1>::empty().relu();
+
+        const TENSOR_ARENA_SIZE: usize = 4 * 1024;
+        let mut tensor_arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
+
+        let mut interpreter =
+            MicroInterpreter::new(&model, &resolver, &mut tensor_arena[..])
+                .unwrap();
+        assert_eq!(interpreter.input_info(0).dims, [3, 2]);
+
+        // Seven samples fill two batches, and one entry of a third
+        let xs = [
+            [-1.0f32, 1.0],
+            [2.0, -2.0],
+            [3.0, 3.0],
+            [-4.0, -4.0],
+            [5.0, 0.5],
+            [-6.0, 6.0],
+            [7.0, -0.5],
+        ];
+        let mut ys = [[0.0f32; 2]; 7];
+        let mut n = 0;
+        interpreter
+            .invoke_batch(xs.iter().map(|x| &x[..]), |output| {
+                assert_eq!(output.info().dims, [1, 2]);
+                ys[n].copy_from_slice(output.as_data::<f32>());
+                n += 1;
+            })
+            .unwrap();
+        assert_eq!(n, 7);
+
+        for (x, y) in xs.iter().zip(&ys) {
+            assert_eq!(*y, [x[0].max(0.0), x[1].max(0.0)]);
+        }
+    }
+
+    #[test]
+    fn split_batch() {
+        // A [3, 2] output tensor
+        let mut data = [0.0f32, 1.0, 2.0, 3.0, 4.0, 5.0];
+        let mut dims = Dims {
+            size: 2,
+            data: [0; Dims::CAPACITY],
+        };
+        dims.data[..2].copy_from_slice(&[3, 2]);
+
+        let mut tensor = bindings::TfLiteTensor::default();
+        tensor.type_ = bindings::TfLiteType::kTfLiteFloat32;
+        tensor.name = b"output\0".as_ptr().cast();
+        tensor.bytes = 6 * 4;
+        tensor.dims = &mut dims as *mut Dims as *mut bindings::TfLiteIntArray;
+        tensor.data.raw = data.as_mut_ptr().cast();
+        let tensor: &Tensor =
+            (&mut tensor as *mut bindings::TfLiteTensor).into();
+
+        // Only the first two entries are used
+        let mut entries = [[0.0f32; 2]; 2];
+        let mut n = 0;
+        super::split_batch(tensor, 3, 2, &mut |entry: &Tensor| {
+            assert_eq!(entry.info().dims, [1, 2]);
+            entries[n].copy_from_slice(entry.as_data::<f32>());
+            n += 1;
+        });
+        assert_eq!(n, 2);
+        assert_eq!(entries, [[0.0, 1.0], [2.0, 3.0]]);
     }
 }
@@ -1,88 +1,146 @@
 //! Tensorflow Lite Op Resolvers
 //!
+//! The operator registrations are held on the Rust side, in an array whose
+//! length is a const generic parameter. This means that a resolver only
+//! takes as much memory as the number of operators it can contain.
+//!
+//! At interpreter construction time these registrations are wrapped by a
+//! small C++ `tflite::OpResolver` implementation, see micro_interpreter.rs
 
-use crate::bindings::tflite;
+use crate::bindings;
+use crate::Error;
 
 use core::fmt;
 
 cpp! {{
-    #include "tensorflow/lite/micro/micro_mutable_op_resolver.h"
-    #include "tensorflow/lite/micro/kernels/all_ops_resolver.h"
+    #include "tensorflow/lite/micro/kernels/micro_ops.h"
 }}
 
-// AllOpsResolver has the same memory representation as
-// MicroMutableOpResolver<128>.
-//
-// That is:
-// class AllOpsResolver : public MicroMutableOpResolver<128> { ... }
-//
-// Thus we can cast between the two types.
-
-type OpResolverT = tflite::ops::micro::AllOpsResolver;
-
-/// Marker trait for types that have the memory representation of a
-/// `OpResolver`
-pub trait OpResolverRepr {
-    fn to_inner(self) -> OpResolverT;
+/// Marker trait for types that contain a set of operator registrations
+/// that can be used by the interpreter
+///
+/// Resolvers must be `Sync`, as a
+/// [`MicroInterpreter`](crate::MicroInterpreter) that reads their
+/// registrations can be sent to another thread.
+pub trait OpResolverRepr: Sync {
+    /// Returns the operator registrations contained in this resolver
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::OpResolverCapacityExceeded` if more operators were
+    /// added to the resolver than it has capacity for.
+    fn registrations(&self) -> Result<&[bindings::TfLiteRegistration], Error>;
 }
 
 /// An Op Resolver populated with all available operators
-#[derive(Default)]
-pub struct AllOpResolver(OpResolverT);
+pub struct AllOpResolver(MutableOpResolver<ALL_OPS_COUNT>);
 impl OpResolverRepr for AllOpResolver {
-    fn to_inner(self) -> OpResolverT {
-        self.0
+    fn registrations(&self) -> Result<&[bindings::TfLiteRegistration], Error> {
+        self.0.registrations()
+    }
+}
+impl Default for AllOpResolver {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 /// An Op Resolver that has no operators by default, but can be added by
 /// calling methods in a builder pattern
-#[derive(Default)]
-pub struct MutableOpResolver {
-    pub(crate) inner: OpResolverT,
-    capacity: usize,
+///
+/// `N` is the maximum number of operators that can be added. Each
+/// registration takes some memory, so `N` should be no larger than the
+/// number of operators the model actually uses.
+///
+/// ```
+/// # use tfmicro::MutableOpResolver;
+/// let resolver = MutableOpResolver::<3>::empty()
+///     .depthwise_conv_2d()
+///     .fully_connected()
+///     .softmax();
+///
+/// assert_eq!(resolver.len(), 3);
+/// ```
+pub struct MutableOpResolver<const N: usize> {
+    registrations: [bindings::TfLiteRegistration; N],
     len: usize,
+    // Set if an attempt was made to add more than `N` operators
+    overflow: bool,
 }
-impl OpResolverRepr for MutableOpResolver {
-    fn to_inner(self) -> OpResolverT {
-        self.inner
+impl<const N: usize> OpResolverRepr for MutableOpResolver<N> {
+    fn registrations(&self) -> Result<&[bindings::TfLiteRegistration], Error> {
+        if self.overflow {
+            Err(Error::OpResolverCapacityExceeded)
+        } else {
+            Ok(&self.registrations[..self.len])
+        }
     }
 }
-impl fmt::Debug for MutableOpResolver {
+impl<const N: usize> fmt::Debug for MutableOpResolver<N> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_fmt(format_args!("MutableOpResolver (ops = {})", self.len))
+        f.write_fmt(format_args!(
+            "MutableOpResolver (ops = {}, capacity = {})",
+            self.len, N
+        ))
     }
 }
-
-impl AllOpResolver {
-    /// Create a new Op Resolver, populated with all available
-    /// operators
-    pub fn new() -> Self {
-        // The C++ compiler fills in the MicroMutableOpResolver with the
-        // operators enumerated in AllOpsResolver
-        let micro_op_resolver = unsafe {
-            cpp!([] -> OpResolverT as "tflite::ops::micro::AllOpsResolver" {
-                // All ops
-                tflite::ops::micro::AllOpsResolver resolver;
-
-                return resolver;
-            })
-        };
-
-        Self(micro_op_resolver)
+impl<const N: usize> Default for MutableOpResolver<N> {
+    fn default() -> Self {
+        Self::empty()
     }
 }
 
-impl MutableOpResolver {
-    /// Check the number of operators is OK
-    pub(crate) fn check_then_inc_len(&mut self) {
-        assert!(
-            self.len < self.capacity,
-            "Tensorflow micro does not support more than {} operators.",
-            self.capacity
-        );
+// The registrations only contain pointers to static functions and static
+// strings within tensorflow
+unsafe impl<const N: usize> Send for MutableOpResolver<N> {}
+unsafe impl<const N: usize> Sync for MutableOpResolver<N> {}
+
+/// Defines `AllOpResolver::new` and `ALL_OPS_COUNT` from the list of
+/// builtin operators
+macro_rules! all_op_resolver {
+    ($(($method:ident, $name:ident, $code:literal),)*) => {
+        /// The number of operators registered by
+        /// [`AllOpResolver`](crate::AllOpResolver)
+        pub(crate) const ALL_OPS_COUNT: usize =
+            [$(stringify!($method)),*].len();
+
+        impl AllOpResolver {
+            /// Create a new Op Resolver, populated with all available
+            /// operators
+            ///
+            /// See lite/micro/kernels/all_ops_resolver.cc
+            pub fn new() -> Self {
+                let resolver = MutableOpResolver::empty()$(.$method())*;
+
+                Self(resolver)
+            }
+        }
+    };
+}
 
-        self.len += 1;
+builtin_operators!(all_op_resolver);
+
+impl<const N: usize> MutableOpResolver<N> {
+    /// Add a registration to this resolver, if there is capacity
+    /// remaining. Otherwise the resolver is marked as overflowed, and
+    /// constructing an interpreter with it will fail
+    pub(crate) fn add_registration(
+        mut self,
+        registration: bindings::TfLiteRegistration,
+    ) -> Self {
+        if self.len < N {
+            self.registrations[self.len] = registration;
+            self.len += 1;
+        } else {
+            warn!(
+                "MutableOpResolver has capacity for {} operators, \
+                 but more were added",
+                N
+            );
+            self.overflow = true;
+        }
+
+        self
     }
 
     /// Returns the current number of operators in this resolver
@@ -95,32 +153,17 @@ impl MutableOpResolver {
         self.len == 0
     }
 
+    /// Returns the maximum number of operators in this resolver
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
     /// Create a new MutableOpResolver, initially empty
     pub fn empty() -> Self {
-        // Maximum number of registrations
-        //
-        // tensorflow/lite/micro/kernels/all_ops_resolver.h:L27
-        let tflite_registrations_max = 128;
-
-        let micro_op_resolver = unsafe {
-            // Create resolver object
-            //
-            // We still need to take the full memory footprint of
-            // `MicroMutableOpResolver`, in order to be layout
-            // compatible. However the unreferenced operations themselves will
-            // be optimised away
-            cpp!([] -> OpResolverT as
-                 "tflite::MicroMutableOpResolver<128>" {
-
-                tflite::MicroMutableOpResolver<128> resolver;
-                return resolver;
-            })
-        };
-
         Self {
-            inner: micro_op_resolver,
-            capacity: tflite_registrations_max,
+            registrations: [Default::default(); N],
             len: 0,
+            overflow: false,
         }
     }
 }
@@ -131,14 +174,41 @@ mod tests {
 
     #[test]
     fn all_ops_resolver() {
-        let _ = AllOpResolver::new();
+        let resolver = AllOpResolver::new();
+
+        assert_eq!(resolver.registrations().unwrap().len(), ALL_OPS_COUNT);
     }
 
     #[test]
     fn mutable_op_resolver() {
-        let _ = MutableOpResolver::empty()
+        let _ = MutableOpResolver::<3>::empty()
             .depthwise_conv_2d()
             .fully_connected()
             .softmax();
     }
+
+    #[test]
+    fn registration_versions() {
+        let resolver = MutableOpResolver::<2>::empty().fully_connected().abs();
+        let registrations = resolver.registrations().unwrap();
+
+        assert_eq!(registrations[0].builtin_code, 9);
+        assert_eq!(registrations[0].version, 4);
+        assert_eq!(registrations[1].builtin_code, 101);
+        assert_eq!(registrations[1].version, 1);
+    }
+
+    #[test]
+    fn mutable_op_resolver_capacity_exceeded() {
+        let resolver = MutableOpResolver::<2>::empty()
+            .depthwise_conv_2d()
+            .fully_connected()
+            .softmax();
+
+        assert_eq!(resolver.len(), 2);
+        assert_eq!(
+            resolver.registrations().unwrap_err(),
+            Error::OpResolverCapacityExceeded
+        );
+    }
 }
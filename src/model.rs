@@ -1,60 +1,688 @@
 //! TensorFlow model
 
+use core::fmt;
+use core::slice;
+use core::str;
+
 use crate::bindings::tflite;
+#[cfg(feature = "ed25519")]
+use crate::container;
+use crate::interop;
+use crate::micro_op_resolver::OpResolverRepr;
+use crate::schema::{self, TensorType};
 use crate::Error;
 
+cpp! {{
+    #include "tensorflow/lite/schema/schema_generated.h"
+}}
+
+/// Include a `.tflite` file as a `&'static [u8]` that is aligned to 16
+/// bytes, as required by [`Model::from_buffer`](crate::Model::from_buffer)
+///
+/// The path is relative to the current file, as for `include_bytes!`.
+///
+/// ```
+/// use tfmicro::{include_model, Model};
+///
+/// let model = include_model!("../examples/models/hello_world.tflite");
+/// let model = Model::from_buffer(model).unwrap();
+/// ```
+#[macro_export]
+macro_rules! include_model {
+    ($path:expr) => {{
+        #[repr(C, align(16))]
+        struct Aligned<T: ?Sized>(T);
+
+        static MODEL: &Aligned<[u8]> = &Aligned(*include_bytes!($path));
+
+        &MODEL.0
+    }};
+}
+
 /// A TensorFlow model
+///
+/// This is the model buffer, which has been verified by TensorFlow.
 #[repr(transparent)]
-#[derive(Default)]
-pub struct Model(tflite::Model);
+pub struct Model([u8]);
+
+/// An operator used by a model, as listed in the model's operator codes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OperatorCode<'a> {
+    /// Builtin operator code, as in the TensorFlow Lite schema
+    pub builtin_code: i32,
+    /// Name of the builtin operator, for example `FULLY_CONNECTED`, or the
+    /// custom code of a custom operator
+    pub name: &'a str,
+    /// Whether this is a custom operator
+    pub custom: bool,
+    /// Version of the operator
+    pub version: i32,
+}
+
+/// A problem found by [`Model::validate`]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Finding {
+    /// The schema version of the model is not the one supported by
+    /// TensorFlow Micro
+    SchemaVersion(u32),
+    /// The model does not have exactly one subgraph. Only the first
+    /// subgraph is run
+    SubgraphCount(usize),
+    /// More operators were added to the op resolver than its capacity
+    OpResolverCapacityExceeded,
+    /// The operator code at `index` is a custom operator, or a builtin
+    /// operator that is not in the op resolver
+    UnsupportedOperator { index: usize, builtin_code: i32 },
+    /// Input or output tensor `tensor` has an element type that is not an
+    /// [`ElementType`](crate::ElementType)
+    UnsupportedElementType { tensor: usize },
+    /// Tensor `tensor` has a dimension that is not known until run time
+    DynamicShape { tensor: usize },
+    /// The constant data of tensor `tensor` is not aligned to its element
+    /// size of `alignment` bytes
+    MisalignedBuffer { tensor: usize, alignment: usize },
+    /// The model failed the checks of the [`schema`](crate::schema) reader,
+    /// such as that every index into the tensors, buffers and operator
+    /// codes is in range. No other checks are made
+    InvalidModel,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Finding::*;
+
+        match *self {
+            SchemaVersion(version) => write!(
+                f,
+                "schema version {} is not supported, expected {}",
+                version,
+                schema::SCHEMA_VERSION
+            ),
+            SubgraphCount(count) => write!(
+                f,
+                "model has {} subgraphs, but only 1 is supported",
+                count
+            ),
+            OpResolverCapacityExceeded => {
+                write!(f, "op resolver capacity exceeded")
+            }
+            UnsupportedOperator {
+                index,
+                builtin_code,
+            } => match schema::builtin_operator_name(builtin_code) {
+                Some(name) if builtin_code != schema::CUSTOM => write!(
+                    f,
+                    "operator {} ({}) is not in the op resolver",
+                    index, name
+                ),
+                _ => write!(f, "operator {} is a custom operator", index),
+            },
+            UnsupportedElementType { tensor } => {
+                write!(f, "tensor {} has an unsupported element type", tensor)
+            }
+            DynamicShape { tensor } => {
+                write!(f, "tensor {} has a dynamic shape", tensor)
+            }
+            MisalignedBuffer { tensor, alignment } => write!(
+                f,
+                "data of tensor {} is not aligned to {} bytes",
+                tensor, alignment
+            ),
+            InvalidModel => write!(f, "model is malformed"),
+        }
+    }
+}
 
 impl Model {
+    /// Alignment in bytes required of the buffer passed to
+    /// [`from_buffer`](Model::from_buffer)
+    pub const ALIGNMENT: usize = 16;
+
     /// Create a tensorflow model that lives as long as the underlying buffer
     ///
+    /// The buffer must be aligned to [`ALIGNMENT`](Model::ALIGNMENT) bytes,
+    /// as TensorFlow reads the weights in place. Use
+    /// [`include_model!`](crate::include_model) to include a model in the
+    /// binary.
+    ///
     /// # Errors
     ///
+    /// Returns `Error::MisalignedBuffer` if the buffer is not aligned.
+    /// Unaligned reads are slow, or cause a hard fault on targets such as
+    /// the Cortex-M0.
+    ///
     /// Returns `Error::InvalidModel` if the buffer failed verification
     pub fn from_buffer(buffer: &[u8]) -> Result<&Self, Error> {
-        let len = buffer.len();
-        let buffer = buffer.as_ptr();
+        if !buffer.is_empty()
+            && buffer.as_ptr() as usize & (Self::ALIGNMENT - 1) != 0
+        {
+            return Err(Error::MisalignedBuffer);
+        }
 
-        let model = unsafe {
-            cpp!([buffer as "const void*", len as "size_t"]
-                  -> *const tflite::Model as "const tflite::Model*" {
+        let len = buffer.len();
+        let ptr = buffer.as_ptr();
 
-                auto verifier = flatbuffers::Verifier((const uint8_t *)buffer, len);
-                if (!::tflite::VerifyModelBuffer(verifier)) {
-                    return NULL;
-                }
+        let verified = unsafe {
+            cpp!([ptr as "const void*", len as "size_t"]
+                  -> bool as "bool" {
 
-                return ::tflite::GetModel(buffer);
+                auto verifier = flatbuffers::Verifier((const uint8_t *)ptr, len);
+                return ::tflite::VerifyModelBuffer(verifier);
             })
         };
 
-        if !model.is_null() {
-            Ok(unsafe { &*(model as *const Self) })
+        if verified {
+            Ok(unsafe { Self::from_buffer_unchecked(buffer) })
         } else {
             Err(Error::InvalidModel)
         }
     }
+
+    /// A model in `buffer`, which must have been verified by
+    /// [`from_buffer`](Model::from_buffer) and not modified since
+    pub(crate) unsafe fn from_buffer_unchecked(buffer: &[u8]) -> &Self {
+        // Model is a transparent wrapper of the buffer
+        &*(buffer as *const [u8] as *const Self)
+    }
+
+    /// The model buffer
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The root table of the model, as used by TensorFlow
+    pub(crate) fn as_tflite(&self) -> *const tflite::Model {
+        let buffer = self.0.as_ptr();
+        unsafe {
+            cpp!([buffer as "const void*"]
+                  -> *const tflite::Model as "const tflite::Model*" {
+                return ::tflite::GetModel(buffer);
+            })
+        }
+    }
+
+    /// Create a tensorflow model from a signed
+    /// [`container`](crate::container), after checking that the model
+    /// matches its checksums and was signed with the Ed25519 public key
+    /// `key` (`ed25519` feature)
+    ///
+    /// The model is only passed to the TensorFlow verifier once these
+    /// checks pass. The container must be aligned to
+    /// [`ALIGNMENT`](Model::ALIGNMENT) bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidContainer` if the container header is not
+    /// valid, or the model is truncated.
+    ///
+    /// Returns `Error::ChecksumMismatch` if the model does not match the
+    /// checksums in the header.
+    ///
+    /// Returns `Error::InvalidSignature` if the container is not signed, or
+    /// was signed with a different key.
+    ///
+    /// Otherwise, returns the same errors as
+    /// [`from_buffer`](Model::from_buffer)
+    #[cfg(feature = "ed25519")]
+    pub fn from_verified_buffer<'a>(
+        buffer: &'a [u8],
+        key: &[u8; 32],
+    ) -> Result<&'a Self, Error> {
+        let container = container::Container::parse(buffer)?;
+        container.verify(key)?;
+
+        Self::from_buffer(container.model())
+    }
+
+    /// Returns the schema version of the model
+    pub fn version(&self) -> u32 {
+        let model = self.as_tflite();
+        unsafe {
+            cpp!([model as "const tflite::Model*"] -> u32 as "uint32_t" {
+                return model->version();
+            })
+        }
+    }
+
+    /// Returns the number of subgraphs in the model. Only the first
+    /// subgraph is run by the interpreter
+    pub fn subgraphs_len(&self) -> usize {
+        let model = self.as_tflite();
+        unsafe {
+            cpp!([model as "const tflite::Model*"] -> usize as "size_t" {
+                auto subgraphs = model->subgraphs();
+                return subgraphs ? subgraphs->size() : 0;
+            })
+        }
+    }
+
+    /// Returns the number of operators in the first subgraph
+    pub fn operators_len(&self) -> usize {
+        let model = self.as_tflite();
+        unsafe {
+            cpp!([model as "const tflite::Model*"] -> usize as "size_t" {
+                auto subgraphs = model->subgraphs();
+                if (!subgraphs || subgraphs->size() == 0) {
+                    return 0;
+                }
+                auto operators = subgraphs->Get(0)->operators();
+                return operators ? operators->size() : 0;
+            })
+        }
+    }
+
+    /// Returns the number of tensors in the first subgraph
+    pub fn tensors_len(&self) -> usize {
+        let model = self.as_tflite();
+        unsafe {
+            cpp!([model as "const tflite::Model*"] -> usize as "size_t" {
+                auto subgraphs = model->subgraphs();
+                if (!subgraphs || subgraphs->size() == 0) {
+                    return 0;
+                }
+                auto tensors = subgraphs->Get(0)->tensors();
+                return tensors ? tensors->size() : 0;
+            })
+        }
+    }
+
+    /// Returns the number of distinct operators used by the model
+    pub fn operator_codes_len(&self) -> usize {
+        let model = self.as_tflite();
+        unsafe {
+            cpp!([model as "const tflite::Model*"] -> usize as "size_t" {
+                auto operator_codes = model->operator_codes();
+                return operator_codes ? operator_codes->size() : 0;
+            })
+        }
+    }
+
+    /// Returns the `n`th operator code of the model
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Utf8Error` if the name of a custom operator is not
+    /// valid UTF-8.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is not less than
+    /// [`operator_codes_len`](Model::operator_codes_len).
+    pub fn operator_code(&self, n: usize) -> Result<OperatorCode, Error> {
+        assert!(n < self.operator_codes_len(), "Invalid operator code index");
+
+        let model = self.as_tflite();
+        let mut builtin_code = 0i32;
+        let mut custom = false;
+        let mut version = 0i32;
+
+        let name = unsafe {
+            let builtin_code_ref = &mut builtin_code;
+            let custom_ref = &mut custom;
+            let version_ref = &mut version;
+
+            let name = cpp!([
+                model as "const tflite::Model*",
+                n as "size_t",
+                builtin_code_ref as "int32_t*",
+                custom_ref as "bool*",
+                version_ref as "int32_t*"
+            ] -> *const cty::c_char as "const char*" {
+                auto opcode = model->operator_codes()->Get(n);
+
+                *builtin_code_ref = opcode->builtin_code();
+                *version_ref = opcode->version();
+
+                if (opcode->builtin_code() == tflite::BuiltinOperator_CUSTOM) {
+                    *custom_ref = true;
+                    auto custom_code = opcode->custom_code();
+                    return custom_code ? custom_code->c_str() : "";
+                }
+                return tflite::EnumNameBuiltinOperator(opcode->builtin_code());
+            });
+
+            let len = interop::strlen::strlen(name);
+            slice::from_raw_parts(name as *const u8, len)
+        };
+
+        Ok(OperatorCode {
+            builtin_code,
+            name: str::from_utf8(name).or(Err(Error::Utf8Error))?,
+            custom,
+            version,
+        })
+    }
+
+    /// Returns an iterator over the operator codes of the model
+    pub fn operator_codes(
+        &self,
+    ) -> impl Iterator<Item = Result<OperatorCode, Error>> {
+        (0..self.operator_codes_len()).map(move |n| self.operator_code(n))
+    }
+
+    /// Check that the model can be run by an interpreter with `resolver`
+    ///
+    /// `from_buffer` only checks that the model is a valid flatbuffer. This
+    /// checks the schema version, the number of subgraphs, that every
+    /// operator is in `resolver`, that the input and output tensors have a
+    /// supported [`ElementType`](crate::ElementType), that no tensor has a
+    /// dynamic shape, and that constant data is aligned. The model is read
+    /// with the [`schema`](crate::schema) reader, which also checks that
+    /// every index in it is in range.
+    ///
+    /// Returns an iterator over every problem found, which is empty for a
+    /// model that passes all the checks.
+    ///
+    /// ```
+    /// # use tfmicro::{Model, MutableOpResolver};
+    /// let model = tfmicro::include_model!("../examples/models/micro_speech.tflite");
+    /// let model = Model::from_buffer(model).unwrap();
+    ///
+    /// // SOFTMAX is missing
+    /// let resolver = MutableOpResolver::<2>::empty()
+    ///     .depthwise_conv_2d()
+    ///     .fully_connected();
+    ///
+    /// for finding in model.validate(&resolver) {
+    ///     println!("{}", finding);
+    /// }
+    /// ```
+    pub fn validate<'m, R: OpResolverRepr>(
+        &'m self,
+        resolver: &'m R,
+    ) -> impl Iterator<Item = Finding> + 'm {
+        let model = schema::Model::from_buffer(self.as_bytes());
+        let invalid = Some(Finding::InvalidModel).filter(|_| model.is_err());
+
+        invalid.into_iter().chain(
+            model
+                .into_iter()
+                .flat_map(move |model| findings(model, resolver)),
+        )
+    }
+}
+
+/// Problems found in `model` by [`Model::validate`]
+fn findings<'m, R: OpResolverRepr>(
+    model: schema::Model<'m>,
+    resolver: &'m R,
+) -> impl Iterator<Item = Finding> + 'm {
+    let version = model.version();
+    let version = Some(Finding::SchemaVersion(version))
+        .filter(|_| version != schema::SCHEMA_VERSION);
+
+    let subgraphs = model.subgraphs().len();
+    let subgraphs =
+        Some(Finding::SubgraphCount(subgraphs)).filter(|_| subgraphs != 1);
+
+    let (registrations, capacity) = match resolver.registrations() {
+        Ok(registrations) => (registrations, None),
+        Err(_) => (&[][..], Some(Finding::OpResolverCapacityExceeded)),
+    };
+    let operators = model.operator_codes().iter().enumerate().filter_map(
+        move |(index, op)| {
+            let builtin_code = op.builtin_code();
+            let registered =
+                registrations.iter().any(|r| r.builtin_code == builtin_code);
+
+            if registered && !op.is_custom() {
+                None
+            } else {
+                Some(Finding::UnsupportedOperator {
+                    index,
+                    builtin_code,
+                })
+            }
+        },
+    );
+
+    let subgraph = model.subgraphs().get(0);
+    let tensors = subgraph.into_iter().flat_map(move |subgraph| {
+        let is_input_or_output = move |n: usize| {
+            subgraph
+                .inputs()
+                .iter()
+                .chain(subgraph.outputs().iter())
+                .any(|tensor| tensor as usize == n)
+        };
+
+        subgraph.tensors().iter().enumerate().flat_map(
+            move |(index, tensor)| {
+                let tensor_type = tensor.tensor_type();
+
+                let element_type = match tensor_type {
+                    Some(TensorType::Float32)
+                    | Some(TensorType::UInt8)
+                    | Some(TensorType::Int8)
+                    | Some(TensorType::Int32) => None,
+                    _ if is_input_or_output(index) => {
+                        Some(Finding::UnsupportedElementType { tensor: index })
+                    }
+                    _ => None,
+                };
+
+                // Unknown dimensions are -1 in the shape signature, and
+                // usually 1 in the shape
+                let dynamic_shape =
+                    Some(Finding::DynamicShape { tensor: index }).filter(
+                        |_| {
+                            tensor
+                                .shape()
+                                .iter()
+                                .chain(tensor.shape_signature().iter())
+                                .any(|dim| dim < 0)
+                        },
+                    );
+
+                let data = model
+                    .buffers()
+                    .get(tensor.buffer() as usize)
+                    .map_or(&[][..], |buffer| buffer.data());
+                let alignment = tensor_type.and_then(TensorType::size);
+                let misaligned = match alignment {
+                    Some(alignment)
+                        if !data.is_empty()
+                            && data.as_ptr() as usize & (alignment - 1)
+                                != 0 =>
+                    {
+                        Some(Finding::MisalignedBuffer {
+                            tensor: index,
+                            alignment,
+                        })
+                    }
+                    _ => None,
+                };
+
+                element_type
+                    .into_iter()
+                    .chain(dynamic_shape)
+                    .chain(misaligned)
+            },
+        )
+    });
+
+    version
+        .into_iter()
+        .chain(subgraphs)
+        .chain(capacity)
+        .chain(operators)
+        .chain(tensors)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::schema::builder::{Builder, Field::*};
+
+    /// A buffer aligned to 16 bytes
+    #[repr(C, align(16))]
+    struct Aligned<T>(T);
 
     #[test]
     fn model_from_buffer() {
-        let model = include_bytes!("../examples/models/hello_world.tflite");
+        let model = include_model!("../examples/models/hello_world.tflite");
 
         // Instantiate the model
-        let _ = Model::from_buffer(&model[..]).unwrap();
+        let _ = Model::from_buffer(model).unwrap();
+    }
+
+    #[test]
+    fn model_summary() {
+        let model = include_model!("../examples/models/micro_speech.tflite");
+        let model = Model::from_buffer(model).unwrap();
+
+        assert_eq!(model.version(), 3);
+        assert_eq!(model.subgraphs_len(), 1);
+        assert_eq!(model.operators_len(), 3);
+
+        let mut names = model.operator_codes().map(|op| op.unwrap().name);
+        assert_eq!(names.next(), Some("DEPTHWISE_CONV_2D"));
+        assert_eq!(names.next(), Some("FULLY_CONNECTED"));
+        assert_eq!(names.next(), Some("SOFTMAX"));
+        assert_eq!(names.next(), None);
+    }
+
+    #[test]
+    fn validate() {
+        use crate::micro_op_resolver::{AllOpResolver, MutableOpResolver};
+
+        let model = include_model!("../examples/models/hello_world.tflite");
+        let model = Model::from_buffer(model).unwrap();
+
+        let resolver = AllOpResolver::new();
+        assert_eq!(model.validate(&resolver).next(), None);
+
+        // QUANTIZE and DEQUANTIZE are missing
+        let resolver = MutableOpResolver::<1>::empty().fully_connected();
+        let mut findings = model.validate(&resolver);
+        assert_eq!(
+            findings.next(),
+            Some(Finding::UnsupportedOperator {
+                index: 1,
+                builtin_code: 114
+            })
+        );
+        assert_eq!(
+            findings.next(),
+            Some(Finding::UnsupportedOperator {
+                index: 2,
+                builtin_code: 6
+            })
+        );
+        assert_eq!(findings.next(), None);
+
+        let resolver =
+            MutableOpResolver::<1>::empty().fully_connected().quantize();
+        assert_eq!(
+            model.validate(&resolver).next(),
+            Some(Finding::OpResolverCapacityExceeded)
+        );
+    }
+
+    /// A model with one subgraph, holding a float32 tensor of shape
+    /// `[1, 4]` whose first dimension is unknown in its shape signature
+    fn dynamic_shape_model() -> Builder {
+        let mut model = Builder::new("model");
+        model.table(
+            "model",
+            &[
+                I32(3),
+                Absent,
+                Offset("subgraphs"),
+                Absent,
+                Offset("buffers"),
+            ],
+        );
+        model.vector("subgraphs", &[Offset("subgraph")]);
+        model.vector("buffers", &[Offset("buffer")]);
+        model.table("buffer", &[]);
+        model.table("subgraph", &[Offset("tensors")]);
+        model.vector("tensors", &[Offset("tensor")]);
+
+        // A float32 tensor with the shape and shape signature
+        model.table(
+            "tensor",
+            &[
+                Offset("shape"),
+                U8(0),
+                Absent,
+                Absent,
+                Absent,
+                Absent,
+                Absent,
+                Offset("shape_signature"),
+            ],
+        );
+        model.vector("shape", &[I32(1), I32(4)]);
+        model.vector("shape_signature", &[I32(-1), I32(4)]);
+
+        model
+    }
+
+    #[test]
+    fn validate_dynamic_shape() {
+        use crate::micro_op_resolver::AllOpResolver;
+
+        let buffer = dynamic_shape_model();
+        let model = Model::from_buffer(buffer.as_bytes()).unwrap();
+
+        // The shape itself is fully known
+        let resolver = AllOpResolver::new();
+        let mut findings = model.validate(&resolver);
+        assert_eq!(findings.next(), Some(Finding::DynamicShape { tensor: 0 }));
+        assert_eq!(findings.next(), None);
+    }
+
+    #[test]
+    fn misaligned_buffer() {
+        const MODEL: &[u8] =
+            include_bytes!("../examples/models/hello_world.tflite");
+
+        // Move the model one byte away from alignment
+        let mut buffer = Aligned([0; MODEL.len() + 1]);
+        buffer.0[1..].copy_from_slice(MODEL);
+
+        assert_eq!(
+            Model::from_buffer(&buffer.0[1..]).unwrap_err(),
+            Error::MisalignedBuffer
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ed25519", any(feature = "std", feature = "alloc")))]
+    fn from_verified_buffer() {
+        use crate::owned_model::copy_at_offset;
+        use ed25519_dalek::{PublicKey, SecretKey};
+
+        let secret_key = [7; 32];
+        let key = PublicKey::from(&SecretKey::from_bytes(&secret_key).unwrap())
+            .to_bytes();
+
+        let model = include_bytes!("../examples/models/hello_world.tflite");
+        let container =
+            container::wrap_signed(&model[..], &secret_key).unwrap();
+        let (mut buffer, range) = copy_at_offset(&container, 0);
+        let buffer = &mut buffer[range];
+
+        let model = Model::from_verified_buffer(buffer, &key).unwrap();
+        assert_eq!(model.operators_len(), 5);
+
+        assert_eq!(
+            Model::from_verified_buffer(buffer, &[0; 32]).unwrap_err(),
+            Error::InvalidSignature
+        );
+
+        buffer[1000] ^= 1;
+        assert_eq!(
+            Model::from_verified_buffer(buffer, &key).unwrap_err(),
+            Error::ChecksumMismatch
+        );
     }
 
     #[test]
     #[should_panic]
     fn bad_model_from_buffer() {
-        let model = &include_bytes!("../examples/models/hello_world.tflite");
+        let model = include_model!("../examples/models/hello_world.tflite");
 
         let _ = Model::from_buffer(&model[..88]).unwrap();
         //                                  ^^
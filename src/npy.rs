@@ -0,0 +1,305 @@
+//! Reading and writing arrays in the NumPy `.npy` format
+//!
+//! Allows feature and tensor data to be loaded with `numpy.load`, for
+//! comparison against the Python training pipeline, and arrays saved with
+//! `numpy.save` to be used as model inputs.
+//!
+//! Requires the `std` feature.
+
+use std::io::{self, Read, Write};
+
+/// Element types that can be stored in a `.npy` file
+pub trait NpyElement: Copy {
+    /// NumPy type description string
+    const DESCR: &'static str;
+
+    /// Write this element in little-endian byte order
+    fn write_le<W: Write>(self, writer: &mut W) -> io::Result<()>;
+}
+
+macro_rules! npy_element {
+    ($t:ty, $descr:expr) => {
+        impl NpyElement for $t {
+            const DESCR: &'static str = $descr;
+
+            fn write_le<W: Write>(self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(&self.to_le_bytes())
+            }
+        }
+    };
+}
+
+npy_element!(u8, "|u1");
+npy_element!(i8, "|i1");
+npy_element!(u16, "<u2");
+npy_element!(i16, "<i2");
+npy_element!(i32, "<i4");
+npy_element!(f32, "<f4");
+
+/// Write `data` to `writer` as a `.npy` file with dimensions `shape`, in
+/// row-major order
+///
+/// # Errors
+///
+/// Returns an `io::Error` of kind `InvalidInput` if the length of `data` is
+/// not the product of `shape`, or any error from `writer`.
+pub fn write_npy<W: Write, T: NpyElement>(
+    mut writer: W,
+    shape: &[usize],
+    data: &[T],
+) -> io::Result<()> {
+    if shape.iter().product::<usize>() != data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "data length does not match shape",
+        ));
+    }
+
+    let shape = match shape {
+        [n] => format!("({},)", n),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        T::DESCR,
+        shape
+    );
+
+    // The header is padded with spaces and terminated by a newline, so
+    // that the data is aligned to 64 bytes
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+
+    for &x in data {
+        x.write_le(&mut writer)?;
+    }
+
+    Ok(())
+}
+
+/// An array read from a `.npy` file
+#[derive(Clone, PartialEq, Debug)]
+pub struct Npy {
+    /// Dimensions of the array
+    pub shape: Vec<usize>,
+    /// Elements of the array in row-major order, converted to `f64`
+    pub data: Vec<f64>,
+}
+
+/// Read a `.npy` file from `reader`
+///
+/// Arrays of 8, 16, 32 and 64-bit integers and 32 and 64-bit floats are
+/// supported, in either byte order. The elements are converted to `f64`,
+/// which is exact for all but the largest 64-bit integers.
+///
+/// # Errors
+///
+/// Returns an `io::Error` of kind `InvalidData` if the file is not a
+/// supported `.npy` file, or any error from `reader`.
+pub fn read_npy<R: Read>(mut reader: R) -> io::Result<Npy> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    if bytes.len() < 10 || &bytes[..6] != b"\x93NUMPY" {
+        return Err(invalid("not a .npy file"));
+    }
+
+    // Versions 2 and 3 have a 32-bit header length
+    let (header_start, header_len) = match bytes[6] {
+        1 => (10, u16::from_le_bytes([bytes[8], bytes[9]]) as usize),
+        2 | 3 if bytes.len() >= 12 => (
+            12,
+            u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]])
+                as usize,
+        ),
+        _ => return Err(invalid("unsupported .npy version")),
+    };
+    let header = bytes
+        .get(header_start..header_start + header_len)
+        .and_then(|h| std::str::from_utf8(h).ok())
+        .ok_or_else(|| invalid("invalid .npy header"))?;
+    let data = &bytes[header_start + header_len..];
+
+    let descr = header_value(header, "descr")
+        .map(|d| d.trim_matches(&['\'', '"'][..]))
+        .ok_or_else(|| invalid("missing descr in .npy header"))?;
+    if header_value(header, "fortran_order") != Some("False") {
+        return Err(invalid("fortran order .npy files are not supported"));
+    }
+    let shape = header_value(header, "shape")
+        .ok_or_else(|| invalid("missing shape in .npy header"))?
+        .trim_matches(&['(', ')'][..])
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| invalid("invalid shape in .npy header"))?;
+
+    let (big_endian, kind) = match descr.as_bytes().first() {
+        Some(b'>') => (true, &descr[1..]),
+        Some(b'<') | Some(b'|') | Some(b'=') => (false, &descr[1..]),
+        _ => (false, descr),
+    };
+    let size = match kind {
+        "u1" | "i1" | "b1" => 1,
+        "u2" | "i2" => 2,
+        "u4" | "i4" | "f4" => 4,
+        "u8" | "i8" | "f8" => 8,
+        _ => return Err(invalid("unsupported .npy element type")),
+    };
+
+    let len = shape.iter().product::<usize>();
+    if data.len() < len * size {
+        return Err(invalid("truncated .npy file"));
+    }
+
+    let data = data[..len * size]
+        .chunks_exact(size)
+        .map(|element| {
+            let mut le = [0u8; 8];
+            le[..size].copy_from_slice(element);
+            if big_endian {
+                le[..size].reverse();
+            }
+            let [a, b, c, d, ..] = le;
+
+            match kind {
+                "u1" | "b1" => a as f64,
+                "i1" => a as i8 as f64,
+                "u2" => u16::from_le_bytes([a, b]) as f64,
+                "i2" => i16::from_le_bytes([a, b]) as f64,
+                "u4" => u32::from_le_bytes([a, b, c, d]) as f64,
+                "i4" => i32::from_le_bytes([a, b, c, d]) as f64,
+                "f4" => f32::from_le_bytes([a, b, c, d]) as f64,
+                "u8" => u64::from_le_bytes(le) as f64,
+                "i8" => i64::from_le_bytes(le) as f64,
+                _ => f64::from_le_bytes(le),
+            }
+        })
+        .collect();
+
+    Ok(Npy { shape, data })
+}
+
+/// Returns the value of `key` in a `.npy` header, which is a Python
+/// dictionary literal
+fn header_value<'h>(header: &'h str, key: &str) -> Option<&'h str> {
+    let start = header
+        .find(&format!("'{}'", key))
+        .or_else(|| header.find(&format!("\"{}\"", key)))?;
+    let value = header[start + key.len() + 2..]
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start();
+
+    // Tuples may contain commas
+    let end = if value.starts_with('(') {
+        value.find(')')? + 1
+    } else {
+        value.find(&[',', '}'][..])?
+    };
+
+    Some(value[..end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn npy_header() {
+        let mut output = Vec::new();
+        write_npy(&mut output, &[2, 3], &[1u16, 2, 3, 4, 5, 6]).unwrap();
+
+        assert_eq!(&output[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([output[8], output[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+
+        let header = std::str::from_utf8(&output[10..10 + header_len]).unwrap();
+        assert!(header.starts_with(
+            "{'descr': '<u2', 'fortran_order': False, 'shape': (2, 3), }"
+        ));
+        assert!(header.ends_with('\n'));
+
+        assert_eq!(
+            &output[10 + header_len..],
+            &[1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0]
+        );
+    }
+
+    #[test]
+    fn npy_1d() {
+        let mut output = Vec::new();
+        write_npy(&mut output, &[2], &[1.0f32, 2.0]).unwrap();
+
+        let header = String::from_utf8_lossy(&output[10..]);
+        assert!(header.contains("'descr': '<f4'"));
+        assert!(header.contains("'shape': (2,)"));
+    }
+
+    #[test]
+    fn npy_round_trip() {
+        let mut output = Vec::new();
+        write_npy(&mut output, &[2, 2], &[-1i16, 2, 300, -400]).unwrap();
+
+        let npy = read_npy(&output[..]).unwrap();
+        assert_eq!(npy.shape, [2, 2]);
+        assert_eq!(npy.data, [-1., 2., 300., -400.]);
+
+        let mut output = Vec::new();
+        write_npy(&mut output, &[3], &[0.5f32, -1.25, 3.]).unwrap();
+
+        let npy = read_npy(&output[..]).unwrap();
+        assert_eq!(npy.shape, [3]);
+        assert_eq!(npy.data, [0.5, -1.25, 3.]);
+    }
+
+    #[test]
+    fn npy_read_big_endian_f8() {
+        let header =
+            "{'descr': '>f8', 'fortran_order': False, 'shape': (2,), }";
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend(&(header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        bytes.extend(&1.5f64.to_be_bytes());
+        bytes.extend(&(-2f64).to_be_bytes());
+
+        let npy = read_npy(&bytes[..]).unwrap();
+        assert_eq!(npy.shape, [2]);
+        assert_eq!(npy.data, [1.5, -2.]);
+    }
+
+    #[test]
+    fn npy_read_invalid() {
+        let error = read_npy(&b"not numpy"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Truncated data
+        let mut output = Vec::new();
+        write_npy(&mut output, &[4], &[0i32; 4]).unwrap();
+        let error = read_npy(&output[..output.len() - 1]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn npy_shape_mismatch() {
+        let error = write_npy(Vec::new(), &[2, 2], &[0u8; 3]).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,402 @@
+//! An interpreter that owns its model, op resolver and tensor arena
+//!
+//! A [`MicroInterpreter`](crate::MicroInterpreter) borrows everything it
+//! runs on, which suits firmware where they are all statics or locals of
+//! `main`. An `OwnedInterpreter` bundles them together instead, so that it
+//! can be stored in a struct, returned from a function, or replaced when a
+//! new model is loaded.
+
+use core::ptr::NonNull;
+
+use crate::micro_interpreter::MicroInterpreter;
+use crate::micro_op_resolver::{AllOpResolver, OpResolverRepr};
+use crate::owned_model::OwnedModel;
+use crate::tensor::{ElemTypeOf, Tensor, TensorInfo};
+use crate::{Error, Status};
+
+/// An interpreter that owns its model, op resolver and tensor arena
+///
+/// ```
+/// use tfmicro::{AllOpResolver, OwnedInterpreter, OwnedModel};
+///
+/// let bytes = include_bytes!("../examples/models/hello_world.tflite");
+/// let model = OwnedModel::from_bytes(&bytes[..]).unwrap();
+///
+/// let mut interpreter =
+///     OwnedInterpreter::new(model, AllOpResolver::new(), 4 * 1024).unwrap();
+///
+/// interpreter.input(0, &[0.0f32]).unwrap();
+/// interpreter.invoke().unwrap();
+/// let y = interpreter.output(0).as_data::<f32>()[0];
+/// ```
+///
+/// If a model is unloaded, or replacing it fails and the previous model
+/// cannot be loaded again, the interpreter has no model until
+/// [`replace_model`](OwnedInterpreter::replace_model) succeeds.
+///
+/// # Panics
+///
+/// The methods that use the model, from [`input_info`](Self::input_info)
+/// to [`arena_used_bytes`](Self::arena_used_bytes), panic if no model is
+/// loaded. Check [`has_model`](Self::has_model) first if the model may
+/// have been unloaded.
+pub struct OwnedInterpreter<R: OpResolverRepr + 'static = AllOpResolver> {
+    // Refers to the model, resolver and arena. `None` if no model is loaded
+    interpreter: Option<MicroInterpreter<'static>>,
+    // Heap allocations owned by `self`, and freed in `drop` after the
+    // interpreter. They are held as raw pointers rather than boxes, so that
+    // moving `self` does not assert unique access to them while the
+    // interpreter refers to them
+    model: Option<NonNull<OwnedModel>>,
+    resolver: NonNull<R>,
+    arena: NonNull<[u8]>,
+}
+
+// The model, resolver and arena are owned, and only reached through `self`.
+// The interpreter can be sent as `MicroInterpreter` is
+unsafe impl<R: OpResolverRepr + Send + 'static> Send for OwnedInterpreter<R> {}
+
+impl<R: OpResolverRepr + 'static> OwnedInterpreter<R> {
+    /// Create an interpreter for `model`, with a tensor arena of
+    /// `arena_size` bytes on the heap
+    ///
+    /// # Errors
+    ///
+    /// As for [`MicroInterpreter::new`](crate::MicroInterpreter::new)
+    pub fn new(
+        model: OwnedModel,
+        resolver: R,
+        arena_size: usize,
+    ) -> Result<Self, Error> {
+        let mut interpreter = Self {
+            interpreter: None,
+            model: None,
+            resolver: NonNull::from(Box::leak(Box::new(resolver))),
+            arena: NonNull::from(Box::leak(
+                vec![0u8; arena_size].into_boxed_slice(),
+            )),
+        };
+        interpreter.replace_model(model)?;
+
+        Ok(interpreter)
+    }
+
+    /// Build an interpreter for `model`, with the resolver and arena of
+    /// `self`
+    ///
+    /// # Safety
+    ///
+    /// `model` must be owned by `self` until the interpreter is dropped,
+    /// and no other interpreter may exist for the arena.
+    unsafe fn build(
+        &mut self,
+        model: NonNull<OwnedModel>,
+    ) -> Result<MicroInterpreter<'static>, Error> {
+        MicroInterpreter::new(
+            &*model.as_ptr(),
+            &*self.resolver.as_ptr(),
+            &mut *self.arena.as_ptr(),
+        )
+    }
+
+    /// Replace the model, rebuilding the interpreter with the same op
+    /// resolver and tensor arena
+    ///
+    /// # Errors
+    ///
+    /// As for [`MicroInterpreter::new`](crate::MicroInterpreter::new). The
+    /// interpreter is then rebuilt for the previous model. If that fails
+    /// too, the previous model is dropped and the interpreter has no model.
+    pub fn replace_model(&mut self, model: OwnedModel) -> Result<(), Error> {
+        let model = NonNull::from(Box::leak(Box::new(model)));
+
+        // The previous interpreter is dropped before the arena is reused,
+        // so that only one refers to it
+        self.interpreter = None;
+
+        // The model is owned by `self` from here until it is replaced or
+        // `self` is dropped, after the interpreter that refers to it
+        match unsafe { self.build(model) } {
+            Ok(interpreter) => {
+                self.interpreter = Some(interpreter);
+                if let Some(previous) = self.model.replace(model) {
+                    drop(unsafe { Box::from_raw(previous.as_ptr()) });
+                }
+                Ok(())
+            }
+            Err(e) => {
+                drop(unsafe { Box::from_raw(model.as_ptr()) });
+
+                // The arena has been overwritten, so the interpreter for
+                // the previous model is rebuilt
+                if let Some(previous) = self.model {
+                    match unsafe { self.build(previous) } {
+                        Ok(interpreter) => self.interpreter = Some(interpreter),
+                        Err(_) => self.unload_model(),
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Drop the model and its interpreter, keeping the op resolver and
+    /// tensor arena for the next [`replace_model`](Self::replace_model)
+    pub fn unload_model(&mut self) {
+        self.interpreter = None;
+        if let Some(model) = self.model.take() {
+            drop(unsafe { Box::from_raw(model.as_ptr()) });
+        }
+    }
+
+    /// Returns true if a model is loaded
+    pub fn has_model(&self) -> bool {
+        self.interpreter.is_some()
+    }
+
+    /// The model run by this interpreter, if any
+    pub fn model(&self) -> Option<&OwnedModel> {
+        // Owned by `self`, and only freed through `&mut self`
+        self.model.map(|model| unsafe { &*model.as_ptr() })
+    }
+
+    /// The op resolver used by this interpreter
+    pub fn resolver(&self) -> &R {
+        unsafe { self.resolver.as_ref() }
+    }
+
+    /// Size of the tensor arena in bytes
+    pub fn arena_size(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// The underlying interpreter, if a model is loaded
+    ///
+    /// Only a shared reference is given out, so that the interpreter cannot
+    /// be replaced by one that borrows data with a shorter lifetime.
+    pub fn interpreter(&self) -> Option<&MicroInterpreter<'_>> {
+        self.interpreter.as_ref()
+    }
+
+    /// The interpreter for the loaded model
+    ///
+    /// # Panics
+    ///
+    /// Panics if no model is loaded.
+    fn loaded(&self) -> &MicroInterpreter<'static> {
+        self.interpreter.as_ref().expect("no model is loaded")
+    }
+
+    /// See [`loaded`](Self::loaded)
+    fn loaded_mut(&mut self) -> &mut MicroInterpreter<'static> {
+        self.interpreter.as_mut().expect("no model is loaded")
+    }
+
+    /// See [`MicroInterpreter::input_info`](crate::MicroInterpreter::input_info)
+    ///
+    /// # Panics
+    ///
+    /// Panics if no model is loaded.
+    pub fn input_info(&self, n: usize) -> TensorInfo<'_> {
+        self.loaded().input_info(n)
+    }
+
+    /// See [`MicroInterpreter::input`](crate::MicroInterpreter::input)
+    ///
+    /// # Panics
+    ///
+    /// Panics if no model is loaded.
+    pub fn input<T: ElemTypeOf + Clone>(
+        &mut self,
+        n: usize,
+        data: &[T],
+    ) -> Result<(), Error> {
+        self.loaded_mut().input(n, data)
+    }
+
+    /// See [`MicroInterpreter::input_tensor`](crate::MicroInterpreter::input_tensor)
+    ///
+    /// # Panics
+    ///
+    /// Panics if no model is loaded.
+    pub fn input_tensor(&mut self, n: usize) -> &mut Tensor {
+        self.loaded_mut().input_tensor(n)
+    }
+
+    /// See [`MicroInterpreter::invoke`](crate::MicroInterpreter::invoke)
+    ///
+    /// # Panics
+    ///
+    /// Panics if no model is loaded.
+    pub fn invoke(&mut self) -> Result<(), Status> {
+        self.loaded_mut().invoke()
+    }
+
+    /// See [`MicroInterpreter::output`](crate::MicroInterpreter::output)
+    ///
+    /// # Panics
+    ///
+    /// Panics if no model is loaded.
+    pub fn output(&self, n: usize) -> &Tensor {
+        self.loaded().output(n)
+    }
+
+    /// See [`MicroInterpreter::inputs_len`](crate::MicroInterpreter::inputs_len)
+    ///
+    /// # Panics
+    ///
+    /// Panics if no model is loaded.
+    pub fn inputs_len(&self) -> usize {
+        self.loaded().inputs_len()
+    }
+
+    /// See [`MicroInterpreter::outputs_len`](crate::MicroInterpreter::outputs_len)
+    ///
+    /// # Panics
+    ///
+    /// Panics if no model is loaded.
+    pub fn outputs_len(&self) -> usize {
+        self.loaded().outputs_len()
+    }
+
+    /// See [`MicroInterpreter::arena_used_bytes`](crate::MicroInterpreter::arena_used_bytes)
+    ///
+    /// # Panics
+    ///
+    /// Panics if no model is loaded.
+    pub fn arena_used_bytes(&self) -> usize {
+        self.loaded().arena_used_bytes()
+    }
+}
+
+impl<R: OpResolverRepr + 'static> Drop for OwnedInterpreter<R> {
+    fn drop(&mut self) {
+        // The interpreter refers to the allocations, so goes first
+        self.unload_model();
+        unsafe {
+            drop(Box::from_raw(self.resolver.as_ptr()));
+            drop(Box::from_raw(self.arena.as_ptr()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MutableOpResolver;
+
+    fn hello_world() -> OwnedModel {
+        let bytes = include_bytes!("../examples/models/hello_world.tflite");
+        OwnedModel::from_bytes(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn invoke() {
+        // Created in a function and moved out of it
+        let mut interpreter = {
+            let resolver = AllOpResolver::new();
+            OwnedInterpreter::new(hello_world(), resolver, 4 * 1024).unwrap()
+        };
+        assert_eq!(interpreter.arena_size(), 4 * 1024);
+        assert_eq!(interpreter.inputs_len(), 1);
+
+        interpreter.input(0, &[0.0f32]).unwrap();
+        interpreter.invoke().unwrap();
+
+        let y = interpreter.output(0).as_data::<f32>()[0];
+        assert!(y.abs() < 0.1, "sin(0) = {}", y);
+    }
+
+    #[test]
+    fn stored_together() {
+        // Interpreters can be created at runtime and stored together
+        let mut interpreters = Vec::new();
+        for _ in 0..2 {
+            let resolver = MutableOpResolver::<3>::empty()
+                .fully_connected()
+                .quantize()
+                .dequantize();
+            interpreters.push(
+                OwnedInterpreter::new(hello_world(), resolver, 4 * 1024)
+                    .unwrap(),
+            );
+        }
+
+        for interpreter in interpreters.iter_mut() {
+            interpreter.input(0, &[1.57f32]).unwrap();
+            interpreter.invoke().unwrap();
+            assert!(interpreter.output(0).as_data::<f32>()[0] > 0.9);
+        }
+    }
+
+    #[test]
+    fn replace_model() {
+        let mut interpreter = OwnedInterpreter::new(
+            hello_world(),
+            AllOpResolver::new(),
+            4 * 1024,
+        )
+        .unwrap();
+
+        // A model that does not fit in the arena is rejected, and the
+        // previous model is kept
+        let bytes = include_bytes!("../examples/models/magic_wand.tflite");
+        let magic_wand = OwnedModel::from_bytes(&bytes[..]).unwrap();
+        assert!(interpreter.replace_model(magic_wand).is_err());
+        assert_eq!(interpreter.model().unwrap().operators_len(), 5);
+
+        interpreter.replace_model(hello_world()).unwrap();
+        interpreter.input(0, &[1.57f32]).unwrap();
+        interpreter.invoke().unwrap();
+        assert!(interpreter.output(0).as_data::<f32>()[0] > 0.9);
+    }
+
+    #[test]
+    fn unload_model() {
+        let mut interpreter = OwnedInterpreter::new(
+            hello_world(),
+            AllOpResolver::new(),
+            4 * 1024,
+        )
+        .unwrap();
+
+        interpreter.unload_model();
+        assert!(!interpreter.has_model());
+        assert!(interpreter.model().is_none());
+        assert!(interpreter.interpreter().is_none());
+
+        // Without a previous model to fall back to, a failed replacement
+        // leaves the interpreter without a model
+        let bytes = include_bytes!("../examples/models/magic_wand.tflite");
+        let magic_wand = OwnedModel::from_bytes(&bytes[..]).unwrap();
+        assert!(interpreter.replace_model(magic_wand).is_err());
+        assert!(!interpreter.has_model());
+
+        interpreter.replace_model(hello_world()).unwrap();
+        interpreter.input(0, &[1.57f32]).unwrap();
+        interpreter.invoke().unwrap();
+        assert!(interpreter.output(0).as_data::<f32>()[0] > 0.9);
+    }
+
+    #[test]
+    #[should_panic(expected = "no model is loaded")]
+    fn no_model() {
+        let mut interpreter = OwnedInterpreter::new(
+            hello_world(),
+            AllOpResolver::new(),
+            4 * 1024,
+        )
+        .unwrap();
+
+        interpreter.unload_model();
+        let _ = interpreter.invoke();
+    }
+
+    #[test]
+    fn arena_too_small() {
+        let result =
+            OwnedInterpreter::new(hello_world(), AllOpResolver::new(), 64);
+
+        assert!(result.is_err());
+    }
+}
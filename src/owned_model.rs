@@ -0,0 +1,201 @@
+//! Models loaded at runtime
+//!
+//! [`Model::from_buffer`](crate::Model::from_buffer) borrows its buffer,
+//! which suits models included in the binary. An `OwnedModel` instead owns
+//! a heap copy of the model, so that a model read from a file or received
+//! over the air can be stored and passed around freely.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec;
+use core::fmt;
+use core::ops::Deref;
+use core::slice;
+
+use crate::model::Model;
+use crate::Error;
+
+/// A block of model data, aligned to `Model::ALIGNMENT`
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+struct Block([u8; Model::ALIGNMENT]);
+
+/// A TensorFlow model that owns its buffer
+///
+/// Dereferences to a [`Model`](crate::Model), so it can be passed anywhere
+/// a `&Model` is expected.
+///
+/// ```
+/// use tfmicro::OwnedModel;
+///
+/// // For example, read from a file at runtime
+/// let bytes = include_bytes!("../examples/models/hello_world.tflite");
+/// let model = OwnedModel::from_bytes(&bytes[..]).unwrap();
+///
+/// assert_eq!(model.version(), 3);
+/// ```
+#[derive(Clone)]
+pub struct OwnedModel {
+    blocks: Box<[Block]>,
+    len: usize,
+}
+
+impl OwnedModel {
+    /// Copy `bytes` into a buffer aligned to
+    /// [`Model::ALIGNMENT`](crate::Model::ALIGNMENT), and verify it. The
+    /// bytes themselves do not need to be aligned
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidModel` if the buffer failed verification
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut blocks = vec![
+            Block([0; Model::ALIGNMENT]);
+            bytes.len() / Model::ALIGNMENT + 1
+        ]
+        .into_boxed_slice();
+        for (block, chunk) in
+            blocks.iter_mut().zip(bytes.chunks(Model::ALIGNMENT))
+        {
+            block.0[..chunk.len()].copy_from_slice(chunk);
+        }
+
+        let model = Self {
+            blocks,
+            len: bytes.len(),
+        };
+        Model::from_buffer(model.as_bytes())?;
+
+        Ok(model)
+    }
+
+    /// Check the signed [`container`](crate::container) in `bytes` as for
+    /// [`Model::from_verified_buffer`](crate::Model::from_verified_buffer),
+    /// and copy the model it holds into an aligned buffer (`ed25519`
+    /// feature). The bytes themselves do not need to be aligned
+    ///
+    /// # Errors
+    ///
+    /// As for [`Model::from_verified_buffer`](crate::Model::from_verified_buffer),
+    /// except that `Error::MisalignedBuffer` is never returned
+    #[cfg(feature = "ed25519")]
+    pub fn from_verified_bytes(
+        bytes: &[u8],
+        key: &[u8; 32],
+    ) -> Result<Self, Error> {
+        let container = crate::container::Container::parse(bytes)?;
+        container.verify(key)?;
+
+        Self::from_bytes(container.model())
+    }
+
+    /// The model buffer
+    pub fn as_bytes(&self) -> &[u8] {
+        // The blocks are contiguous and hold at least `len` bytes
+        unsafe { slice::from_raw_parts(self.blocks.as_ptr().cast(), self.len) }
+    }
+}
+
+impl Deref for OwnedModel {
+    type Target = Model;
+
+    fn deref(&self) -> &Model {
+        // The buffer was verified when it was created, and is not modified
+        unsafe { Model::from_buffer_unchecked(self.as_bytes()) }
+    }
+}
+
+impl AsRef<Model> for OwnedModel {
+    fn as_ref(&self) -> &Model {
+        self
+    }
+}
+
+impl fmt::Debug for OwnedModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OwnedModel")
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// Copy `bytes` to `offset` bytes past a
+/// [`Model::ALIGNMENT`](crate::Model::ALIGNMENT) boundary in a new buffer.
+/// Returns the buffer and the range of the copy in it
+#[cfg(test)]
+pub(crate) fn copy_at_offset(
+    bytes: &[u8],
+    offset: usize,
+) -> (alloc::vec::Vec<u8>, core::ops::Range<usize>) {
+    let mut buffer = vec![0; bytes.len() + offset + Model::ALIGNMENT];
+    let start = buffer.as_ptr().align_offset(Model::ALIGNMENT) + offset;
+    let range = start..start + bytes.len();
+    buffer[range.clone()].copy_from_slice(bytes);
+
+    (buffer, range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes() {
+        let bytes = include_bytes!("../examples/models/hello_world.tflite");
+        let model = OwnedModel::from_bytes(&bytes[..]).unwrap();
+
+        assert_eq!(model.as_bytes(), &bytes[..]);
+        assert_eq!(
+            model.as_bytes().as_ptr() as usize & (Model::ALIGNMENT - 1),
+            0
+        );
+        assert_eq!(model.version(), 3);
+        assert_eq!(model.operators_len(), 5);
+
+        // Clones own a separate buffer
+        let clone = model.clone();
+        assert_ne!(clone.as_bytes().as_ptr(), model.as_bytes().as_ptr());
+        assert_eq!(clone.operators_len(), 5);
+    }
+
+    #[test]
+    fn from_misaligned_bytes() {
+        let bytes = include_bytes!("../examples/models/hello_world.tflite");
+        let (buffer, range) = copy_at_offset(&bytes[..], 1);
+
+        let model = OwnedModel::from_bytes(&buffer[range]).unwrap();
+        assert_eq!(model.operators_len(), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "ed25519")]
+    fn from_verified_bytes() {
+        use crate::container;
+        use ed25519_dalek::{PublicKey, SecretKey};
+
+        let secret_key = [7; 32];
+        let key = PublicKey::from(&SecretKey::from_bytes(&secret_key).unwrap())
+            .to_bytes();
+
+        let bytes = include_bytes!("../examples/models/hello_world.tflite");
+        let signed = container::wrap_signed(&bytes[..], &secret_key).unwrap();
+
+        let model = OwnedModel::from_verified_bytes(&signed, &key).unwrap();
+        assert_eq!(model.as_bytes(), &bytes[..]);
+
+        let unsigned = container::wrap(&bytes[..]);
+        assert_eq!(
+            OwnedModel::from_verified_bytes(&unsigned, &key).unwrap_err(),
+            Error::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            OwnedModel::from_bytes(&[0; 64]).unwrap_err(),
+            Error::InvalidModel
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! Posterior smoothing for keyword spotting
+//!
+//! The output of a keyword spotting model is noisy from one inference to
+//! the next. A [`CommandRecognizer`](crate::CommandRecognizer) averages the
+//! scores over a time window, applies a detection threshold, and suppresses
+//! repeated detections of the same command.
+//!
+//! This is a port of `RecognizeCommands` from the micro_speech example. See
+//! tensorflow/lite/micro/examples/micro_speech/recognize_commands.cc
+//!
+//! ```
+//! # use tfmicro::CommandRecognizer;
+//! let mut recognizer = CommandRecognizer::<4, 50>::new()
+//!     .detection_threshold(51);
+//!
+//! // Scores from `interpreter.output(0).as_data::<u8>()`
+//! let scores = [0, 0, 255, 0];
+//!
+//! for time_ms in (0..1000).step_by(100) {
+//!     if let Some(command) = recognizer.process(&scores, time_ms).unwrap() {
+//!         assert_eq!(command.label, 2);
+//!     }
+//! }
+//! ```
+
+use crate::Error;
+
+/// A command that has been newly recognised
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Command {
+    /// Index of the recognised label in the model output
+    pub label: usize,
+    /// Score for this label, averaged over the time window
+    pub score: u8,
+    /// Time of the results that triggered recognition, in milliseconds
+    pub time_ms: u32,
+}
+
+/// Scores from one inference
+#[derive(Clone, Copy)]
+struct Results<const LABELS: usize> {
+    time_ms: u32,
+    scores: [u8; LABELS],
+}
+
+/// Averages the scores of a model with `LABELS` outputs over time, and
+/// reports newly recognised commands
+///
+/// Up to `HISTORY` results are retained for averaging. This should be at
+/// least the number of inferences made in the averaging window, and must be
+/// at least one. A recognizer without history does not compile:
+///
+/// ```compile_fail
+/// # use tfmicro::CommandRecognizer;
+/// let recognizer = CommandRecognizer::<4, 0>::new();
+/// ```
+pub struct CommandRecognizer<const LABELS: usize, const HISTORY: usize> {
+    average_window_duration_ms: u32,
+    detection_threshold: u8,
+    suppression_ms: u32,
+    minimum_count: usize,
+    silence_label: Option<usize>,
+
+    // Ring buffer of previous results
+    results: [Results<LABELS>; HISTORY],
+    head: usize,
+    len: usize,
+
+    previous_top_label: Option<usize>,
+    previous_top_label_time_ms: Option<u32>,
+}
+
+impl<const LABELS: usize, const HISTORY: usize> Default
+    for CommandRecognizer<LABELS, HISTORY>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const LABELS: usize, const HISTORY: usize>
+    CommandRecognizer<LABELS, HISTORY>
+{
+    // Evaluated when `new` is compiled for a given `HISTORY`, so that an
+    // empty ring buffer is rejected at compile time
+    const NONEMPTY_HISTORY: () =
+        assert!(HISTORY > 0, "CommandRecognizer HISTORY must be at least 1");
+
+    /// Create a new CommandRecognizer, with the parameters used in the
+    /// micro_speech example. Label 0 is treated as silence.
+    pub fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::NONEMPTY_HISTORY;
+
+        Self {
+            average_window_duration_ms: 1000,
+            detection_threshold: 200,
+            suppression_ms: 1500,
+            minimum_count: 3,
+            silence_label: Some(0),
+
+            results: [Results {
+                time_ms: 0,
+                scores: [0; LABELS],
+            }; HISTORY],
+            head: 0,
+            len: 0,
+
+            previous_top_label: None,
+            previous_top_label_time_ms: None,
+        }
+    }
+
+    /// Duration of the window over which scores are averaged
+    pub fn average_window_duration_ms(mut self, duration_ms: u32) -> Self {
+        self.average_window_duration_ms = duration_ms;
+        self
+    }
+
+    /// Minimum averaged score for a command to be recognised
+    pub fn detection_threshold(mut self, threshold: u8) -> Self {
+        self.detection_threshold = threshold;
+        self
+    }
+
+    /// Time after a command is recognised during which the same command
+    /// will not be recognised again
+    pub fn suppression_ms(mut self, suppression_ms: u32) -> Self {
+        self.suppression_ms = suppression_ms;
+        self
+    }
+
+    /// Minimum number of results in the averaging window before any
+    /// command is recognised
+    pub fn minimum_count(mut self, minimum_count: usize) -> Self {
+        self.minimum_count = minimum_count;
+        self
+    }
+
+    /// Label that represents silence, if any. Following silence a command
+    /// is never suppressed
+    pub fn silence_label(mut self, label: Option<usize>) -> Self {
+        self.silence_label = label;
+        self
+    }
+
+    /// Process the scores from the latest inference, made at time
+    /// `current_time_ms`
+    ///
+    /// Returns `Some(command)` if a command was newly recognised
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InputDataLenMismatch` if the length of `scores` is
+    /// not `LABELS`.
+    ///
+    /// Returns `Error::TimestampOrder` if `current_time_ms` is earlier than
+    /// the time of the previous results.
+    pub fn process(
+        &mut self,
+        scores: &[u8],
+        current_time_ms: u32,
+    ) -> Result<Option<Command>, Error> {
+        if scores.len() != LABELS {
+            return Err(Error::InputDataLenMismatch);
+        }
+        if let Some(newest) = self.newest() {
+            if current_time_ms < newest.time_ms {
+                return Err(Error::TimestampOrder);
+            }
+        }
+
+        self.push(current_time_ms, scores);
+
+        // Prune any earlier results that are too old for the averaging
+        // window
+        while let Some(oldest) = self.oldest() {
+            if current_time_ms - oldest.time_ms
+                > self.average_window_duration_ms
+            {
+                self.pop();
+            } else {
+                break;
+            }
+        }
+
+        // If there are too few results, assume the result will be
+        // unreliable
+        if self.len < self.minimum_count {
+            return Ok(None);
+        }
+
+        // Calculate the average score across all the results in the window
+        let mut average_scores = [0u32; LABELS];
+        for results in self.iter() {
+            for (average, &score) in
+                average_scores.iter_mut().zip(results.scores.iter())
+            {
+                *average += score as u32;
+            }
+        }
+        for average in average_scores.iter_mut() {
+            *average /= self.len as u32;
+        }
+
+        // Find the current highest scoring category
+        let (current_top_label, current_top_score) = average_scores
+            .iter()
+            .enumerate()
+            .fold((0, 0), |(top_label, top_score), (label, &score)| {
+                if score > top_score {
+                    (label, score)
+                } else {
+                    (top_label, top_score)
+                }
+            });
+        let current_top_score = current_top_score as u8;
+
+        // If we've recently had another label trigger, assume one that
+        // occurs too soon afterwards is a bad result
+        let time_since_last_top = match self.previous_top_label_time_ms {
+            Some(time_ms) if self.previous_top_label != self.silence_label => {
+                current_time_ms - time_ms
+            }
+            _ => u32::MAX,
+        };
+
+        if current_top_score > self.detection_threshold
+            && (Some(current_top_label) != self.previous_top_label
+                || time_since_last_top > self.suppression_ms)
+        {
+            self.previous_top_label = Some(current_top_label);
+            self.previous_top_label_time_ms = Some(current_time_ms);
+
+            Ok(Some(Command {
+                label: current_top_label,
+                score: current_top_score,
+                time_ms: current_time_ms,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Forget all previous results and commands
+    pub fn reset(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.previous_top_label = None;
+        self.previous_top_label_time_ms = None;
+    }
+
+    fn push(&mut self, time_ms: u32, scores: &[u8]) {
+        if self.len == HISTORY {
+            warn!(
+                "CommandRecognizer history of {} results is full, \
+                 discarding the oldest",
+                HISTORY
+            );
+            self.pop();
+        }
+
+        let index = (self.head + self.len) % HISTORY;
+        self.results[index].time_ms = time_ms;
+        self.results[index].scores.copy_from_slice(scores);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) {
+        self.head = (self.head + 1) % HISTORY;
+        self.len -= 1;
+    }
+
+    fn oldest(&self) -> Option<&Results<LABELS>> {
+        self.iter().next()
+    }
+
+    fn newest(&self) -> Option<&Results<LABELS>> {
+        self.iter().last()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Results<LABELS>> {
+        (0..self.len).map(move |i| &self.results[(self.head + i) % HISTORY])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YES: [u8; 4] = [0, 0, 255, 0];
+    const NO: [u8; 4] = [0, 0, 0, 255];
+
+    #[test]
+    fn find_commands() {
+        let mut recognizer =
+            CommandRecognizer::<4, 50>::new().detection_threshold(51);
+
+        let mut commands =
+            (0..10).filter_map(|i| recognizer.process(&YES, i * 100).unwrap());
+        assert_eq!(
+            commands.next(),
+            Some(Command {
+                label: 2,
+                score: 255,
+                time_ms: 200
+            })
+        );
+        assert_eq!(commands.next(), None);
+
+        let mut commands = (0..10)
+            .filter_map(|i| recognizer.process(&NO, 900 + i * 100).unwrap());
+        assert_eq!(commands.next().map(|c| c.label), Some(3));
+        assert_eq!(commands.next(), None);
+    }
+
+    #[test]
+    fn suppression() {
+        let mut recognizer = CommandRecognizer::<4, 50>::new()
+            .detection_threshold(51)
+            .suppression_ms(500);
+
+        // The same command is recognised again after the suppression time
+        let commands = (0..10)
+            .filter_map(|i| recognizer.process(&YES, i * 100).unwrap())
+            .map(|c| c.time_ms);
+        assert!(commands.eq([200, 800].iter().cloned()));
+    }
+
+    #[test]
+    fn too_few_inputs() {
+        let mut recognizer =
+            CommandRecognizer::<4, 50>::new().detection_threshold(51);
+
+        assert_eq!(recognizer.process(&YES, 100), Ok(None));
+        assert_eq!(recognizer.process(&YES, 200), Ok(None));
+    }
+
+    #[test]
+    fn bad_input_length() {
+        let mut recognizer = CommandRecognizer::<4, 50>::new();
+
+        assert_eq!(
+            recognizer.process(&[0, 0, 255], 0),
+            Err(Error::InputDataLenMismatch)
+        );
+    }
+
+    #[test]
+    fn bad_input_times() {
+        let mut recognizer = CommandRecognizer::<4, 50>::new();
+
+        recognizer.process(&YES, 100).unwrap();
+        assert_eq!(recognizer.process(&YES, 0), Err(Error::TimestampOrder));
+    }
+
+    #[test]
+    fn history_full() {
+        let mut recognizer = CommandRecognizer::<4, 3>::new()
+            .detection_threshold(51)
+            .minimum_count(3);
+
+        // The oldest results are discarded, so the window only ever
+        // contains three results
+        for i in 0..5 {
+            recognizer.process(&NO, i * 10).unwrap();
+        }
+        assert_eq!(recognizer.len, 3);
+        assert_eq!(recognizer.oldest().unwrap().time_ms, 20);
+    }
+
+    #[test]
+    fn reset() {
+        let mut recognizer =
+            CommandRecognizer::<4, 50>::new().detection_threshold(51);
+
+        for i in 0..3 {
+            recognizer.process(&YES, i * 100).unwrap();
+        }
+        recognizer.reset();
+
+        // Time may restart after a reset
+        assert_eq!(recognizer.process(&YES, 0), Ok(None));
+    }
+}
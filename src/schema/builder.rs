@@ -0,0 +1,159 @@
+//! Writing small flatbuffers for test models
+//!
+//! Objects are written front to back, so every offset points forward to an
+//! object that is written later. Offsets name the object they point to,
+//! and are filled in when an object of that name is written.
+
+/// A field of a table, or an element of a vector
+#[derive(Clone, Copy)]
+pub(crate) enum Field {
+    /// A field that is not present, so that it reads as its default
+    Absent,
+    /// A one byte scalar, such as an enum
+    U8(u8),
+    /// A four byte scalar
+    I32(i32),
+    /// An offset to the object of this name
+    Offset(&'static str),
+}
+
+/// Offsets to objects that have not been written yet
+const FIXUPS: usize = 32;
+
+/// A flatbuffer with the `TFL3` identifier, built in a buffer aligned to
+/// 16 bytes as `Model::from_buffer` requires
+#[repr(C, align(16))]
+pub(crate) struct Builder {
+    buf: [u8; 512],
+    len: usize,
+    fixups: [Option<(usize, &'static str)>; FIXUPS],
+}
+
+impl Builder {
+    /// Starts a flatbuffer whose root table is named `root`
+    pub fn new(root: &'static str) -> Self {
+        let mut builder = Self {
+            buf: [0; 512],
+            len: 0,
+            fixups: [None; FIXUPS],
+        };
+        builder.field(Field::Offset(root));
+        builder.push(b"TFL3");
+        builder
+    }
+
+    /// Writes a table named `name`, with the fields in order of their
+    /// field number
+    pub fn table(&mut self, name: &'static str, fields: &[Field]) {
+        // The vtable has the size of the vtable and the table, and then
+        // the position of each field in the table
+        let vtable = self.len;
+        self.len += 4 + 2 * fields.len();
+        self.align(4);
+        self.define(name);
+        let table = self.len;
+        self.push(&((table - vtable) as i32).to_le_bytes());
+
+        for (n, &field) in fields.iter().enumerate() {
+            if let Field::Absent = field {
+                continue;
+            }
+            let offset = (self.field(field) - table) as u16;
+            self.write(vtable + 4 + 2 * n, &offset.to_le_bytes());
+        }
+        self.align(4);
+
+        let vtable_len = (4 + 2 * fields.len()) as u16;
+        let table_len = (self.len - table) as u16;
+        self.write(vtable, &vtable_len.to_le_bytes());
+        self.write(vtable + 2, &table_len.to_le_bytes());
+    }
+
+    /// Writes a vector named `name`
+    pub fn vector(&mut self, name: &'static str, elements: &[Field]) {
+        self.align(4);
+        self.define(name);
+        self.push(&(elements.len() as u32).to_le_bytes());
+        for &element in elements {
+            self.field(element);
+        }
+        self.align(4);
+    }
+
+    /// Writes a string named `name`
+    // Only used by the interpreter tests
+    #[cfg_attr(not(feature = "tensorflow"), allow(dead_code))]
+    pub fn string(&mut self, name: &'static str, s: &str) {
+        self.align(4);
+        self.define(name);
+        self.push(&(s.len() as u32).to_le_bytes());
+        self.push(s.as_bytes());
+        self.push(&[0]);
+        self.align(4);
+    }
+
+    /// The flatbuffer
+    ///
+    /// # Panics
+    ///
+    /// Panics if an offset names an object that has not been written.
+    pub fn as_bytes(&self) -> &[u8] {
+        if let Some((_, name)) = self.fixups.iter().flatten().next() {
+            panic!("{} is not written", name);
+        }
+        &self.buf[..self.len]
+    }
+
+    /// Writes `field` at its alignment, and returns its position
+    fn field(&mut self, field: Field) -> usize {
+        match field {
+            Field::Absent => panic!("absent vector element"),
+            Field::U8(value) => self.push(&[value]),
+            Field::I32(value) => {
+                self.align(4);
+                self.push(&value.to_le_bytes())
+            }
+            Field::Offset(name) => {
+                self.align(4);
+                let fixup = self
+                    .fixups
+                    .iter_mut()
+                    .find(|fixup| fixup.is_none())
+                    .expect("too many offsets");
+                *fixup = Some((self.len, name));
+                self.push(&[0; 4])
+            }
+        }
+    }
+
+    /// Points the offsets to `name` at the current position
+    fn define(&mut self, name: &'static str) {
+        for fixup in self.fixups.iter_mut() {
+            match *fixup {
+                Some((pos, to)) if to == name => {
+                    let offset = (self.len - pos) as u32;
+                    self.buf[pos..pos + 4]
+                        .copy_from_slice(&offset.to_le_bytes());
+                    *fixup = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn align(&mut self, align: usize) {
+        self.len = (self.len + align - 1) & !(align - 1);
+    }
+
+    /// Appends `bytes`, and returns their position
+    fn push(&mut self, bytes: &[u8]) -> usize {
+        let pos = self.len;
+        self.write(pos, bytes);
+        self.len += bytes.len();
+        pos
+    }
+
+    fn write(&mut self, pos: usize, bytes: &[u8]) {
+        self.buf[pos..pos + bytes.len()].copy_from_slice(bytes);
+    }
+}
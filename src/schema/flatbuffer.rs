@@ -0,0 +1,365 @@
+//! Reading and verifying flatbuffers
+//!
+//! Every access is bounds checked, and returns `None` if the buffer is
+//! malformed. The `verify_*` methods check the layout of a table and its
+//! fields up front, so that reads from a verified table always succeed.
+
+use core::convert::TryInto;
+use core::marker::PhantomData;
+use core::str;
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(pos..pos.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(pos..pos.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(buf: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        buf.get(pos..pos.checked_add(8)?)?.try_into().ok()?,
+    ))
+}
+
+/// Follow the offset stored at `pos`
+fn indirect(buf: &[u8], pos: usize) -> Option<usize> {
+    pos.checked_add(read_u32(buf, pos)? as usize)
+}
+
+/// A table within a flatbuffer
+#[derive(Clone, Copy)]
+pub(crate) struct Table<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Table<'a> {
+    /// The root table of the flatbuffer `buf`
+    pub fn root(buf: &'a [u8]) -> Option<Self> {
+        Some(Self {
+            buf,
+            pos: indirect(buf, 0)?,
+        })
+    }
+
+    /// Position of the vtable
+    fn vtable(&self) -> Option<usize> {
+        let soffset = read_u32(self.buf, self.pos)? as i32;
+
+        (self.pos as i64 - soffset as i64).try_into().ok()
+    }
+
+    /// Position of field `n`, if it is present
+    fn field(&self, n: usize) -> Option<usize> {
+        let vtable = self.vtable()?;
+        let vtable_len = read_u16(self.buf, vtable)? as usize;
+
+        let entry = 4 + 2 * n;
+        if entry + 2 > vtable_len {
+            return None;
+        }
+        match read_u16(self.buf, vtable + entry)? {
+            0 => None,
+            offset => self.pos.checked_add(offset as usize),
+        }
+    }
+
+    /// Scalar field `n`, or `default` if it is not present
+    pub fn u8(&self, n: usize, default: u8) -> u8 {
+        self.field(n)
+            .and_then(|pos| self.buf.get(pos).copied())
+            .unwrap_or(default)
+    }
+
+    /// Scalar field `n`, or `default` if it is not present
+    pub fn u32(&self, n: usize, default: u32) -> u32 {
+        self.field(n)
+            .and_then(|pos| read_u32(self.buf, pos))
+            .unwrap_or(default)
+    }
+
+    /// Scalar field `n`, or `default` if it is not present
+    pub fn i32(&self, n: usize, default: i32) -> i32 {
+        self.u32(n, default as u32) as i32
+    }
+
+    /// Table field `n`
+    pub fn table(&self, n: usize) -> Option<Table<'a>> {
+        Some(Table {
+            buf: self.buf,
+            pos: indirect(self.buf, self.field(n)?)?,
+        })
+    }
+
+    /// Vector field `n`. Missing vectors are empty
+    pub fn vector<T: Element<'a>>(&self, n: usize) -> Vector<'a, T> {
+        let (start, len) = self
+            .field(n)
+            .and_then(|pos| {
+                let pos = indirect(self.buf, pos)?;
+                Some((pos + 4, read_u32(self.buf, pos)? as usize))
+            })
+            .unwrap_or((0, 0));
+
+        Vector {
+            buf: self.buf,
+            start,
+            len,
+            element: PhantomData,
+        }
+    }
+
+    /// Vector of bytes field `n`. Missing vectors are empty
+    pub fn bytes(&self, n: usize) -> &'a [u8] {
+        let vector = self.vector::<u8>(n);
+
+        vector
+            .start
+            .checked_add(vector.len)
+            .and_then(|end| self.buf.get(vector.start..end))
+            .unwrap_or(&[])
+    }
+
+    /// String field `n`
+    pub fn str(&self, n: usize) -> Option<&'a str> {
+        self.field(n)?;
+
+        str::from_utf8(self.bytes(n)).ok()
+    }
+
+    /// Check the vtable of the table, and that every field lies within the
+    /// table
+    pub fn verify(&self) -> Option<()> {
+        // Tables are 4-byte aligned, as they start with the vtable offset
+        if self.pos & 3 != 0 {
+            return None;
+        }
+        let vtable = self.vtable()?;
+        let vtable_len = read_u16(self.buf, vtable)? as usize;
+        let table_len = read_u16(self.buf, vtable + 2)? as usize;
+        if vtable_len < 4 || vtable_len & 1 != 0 || table_len < 4 {
+            return None;
+        }
+        self.buf.get(vtable..vtable.checked_add(vtable_len)?)?;
+        self.buf.get(self.pos..self.pos.checked_add(table_len)?)?;
+
+        for entry in (4..vtable_len).step_by(2) {
+            if read_u16(self.buf, vtable + entry)? as usize >= table_len {
+                return None;
+            }
+        }
+
+        Some(())
+    }
+
+    /// Check that scalar field `n` of `size` bytes lies within the buffer
+    pub fn verify_scalar(&self, n: usize, size: usize) -> Option<()> {
+        match self.field(n) {
+            Some(pos) => self.buf.get(pos..pos.checked_add(size)?).map(|_| ()),
+            None => Some(()),
+        }
+    }
+
+    /// Check that vector field `n` lies within the buffer
+    pub fn verify_vector<T: Element<'a>>(&self, n: usize) -> Option<()> {
+        let pos = match self.field(n) {
+            Some(pos) => indirect(self.buf, pos)?,
+            None => return Some(()),
+        };
+        if pos & 3 != 0 {
+            return None;
+        }
+        let len = read_u32(self.buf, pos)? as usize;
+        let end = (pos + 4).checked_add(len.checked_mul(T::SIZE)?)?;
+
+        self.buf.get(pos..end).map(|_| ())
+    }
+
+    /// Check that string field `n` lies within the buffer, is terminated
+    /// and is valid UTF-8
+    pub fn verify_str(&self, n: usize) -> Option<()> {
+        self.verify_vector::<u8>(n)?;
+        if self.field(n).is_none() {
+            return Some(());
+        }
+
+        let vector = self.vector::<u8>(n);
+        if self.buf.get(vector.start + vector.len) != Some(&0) {
+            return None;
+        }
+        self.str(n).map(|_| ())
+    }
+
+    /// Check that table field `n` lies within the buffer, and return it
+    pub fn verify_table(&self, n: usize) -> Option<Option<Table<'a>>> {
+        if self.field(n).is_none() {
+            return Some(None);
+        }
+        let table = self.table(n)?;
+        table.verify()?;
+
+        Some(Some(table))
+    }
+
+    /// Check that vector of tables field `n` lies within the buffer, and
+    /// verify each table with `f`
+    pub fn verify_tables<F>(&self, n: usize, mut f: F) -> Option<()>
+    where
+        F: FnMut(Table<'a>) -> Option<()>,
+    {
+        self.verify_vector::<Table>(n)?;
+
+        let tables = self.vector::<Table>(n);
+        for i in 0..tables.len() {
+            let table = Table {
+                buf: self.buf,
+                pos: indirect(self.buf, tables.start + 4 * i)?,
+            };
+            table.verify()?;
+            f(table)?;
+        }
+
+        Some(())
+    }
+}
+
+/// Types that can be read from a flatbuffer vector
+pub trait Element<'a>: Sized + private::Sealed {
+    #[doc(hidden)]
+    const SIZE: usize;
+
+    #[doc(hidden)]
+    fn read(buf: &'a [u8], pos: usize) -> Option<Self>;
+}
+
+pub(super) mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! scalar_element {
+    ($($t:ty, $read:expr;)*) => {
+        $(
+            impl private::Sealed for $t {}
+
+            impl<'a> Element<'a> for $t {
+                const SIZE: usize = core::mem::size_of::<$t>();
+
+                fn read(buf: &'a [u8], pos: usize) -> Option<Self> {
+                    #[allow(clippy::redundant_closure_call)]
+                    $read(buf, pos)
+                }
+            }
+        )*
+    };
+}
+
+scalar_element! {
+    u8, |buf: &[u8], pos| buf.get(pos).copied();
+    i32, |buf, pos| read_u32(buf, pos).map(|v| v as i32);
+    u32, read_u32;
+    i64, |buf, pos| read_u64(buf, pos).map(|v| v as i64);
+    f32, |buf, pos| read_u32(buf, pos).map(f32::from_bits);
+}
+
+impl private::Sealed for Table<'_> {}
+
+/// Vectors of tables hold offsets to each table
+impl<'a> Element<'a> for Table<'a> {
+    const SIZE: usize = 4;
+
+    fn read(buf: &'a [u8], pos: usize) -> Option<Self> {
+        Some(Table {
+            buf,
+            pos: indirect(buf, pos)?,
+        })
+    }
+}
+
+/// A vector within a flatbuffer
+pub struct Vector<'a, T> {
+    buf: &'a [u8],
+    start: usize,
+    len: usize,
+    element: PhantomData<T>,
+}
+
+// Not derived, as that would require `T: Copy`
+impl<T> Clone for Vector<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Vector<'_, T> {}
+
+impl<'a, T: Element<'a>> Vector<'a, T> {
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Element `n`, or `None` if it is out of bounds
+    pub fn get(&self, n: usize) -> Option<T> {
+        if n >= self.len {
+            return None;
+        }
+
+        T::read(self.buf, self.start.checked_add(n * T::SIZE)?)
+    }
+
+    /// Iterate over the elements
+    pub fn iter(&self) -> VectorIter<'a, T> {
+        VectorIter {
+            vector: *self,
+            n: 0,
+        }
+    }
+}
+
+impl<'a, T: Element<'a>> IntoIterator for Vector<'a, T> {
+    type Item = T;
+    type IntoIter = VectorIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T: Element<'a> + core::fmt::Debug> core::fmt::Debug for Vector<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the elements of a [`Vector`]
+pub struct VectorIter<'a, T> {
+    vector: Vector<'a, T>,
+    n: usize,
+}
+
+impl<'a, T: Element<'a>> Iterator for VectorIter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let element = self.vector.get(self.n)?;
+        self.n += 1;
+
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.vector.len.saturating_sub(self.n);
+
+        (len, Some(len))
+    }
+}
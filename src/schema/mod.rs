@@ -0,0 +1,761 @@
+//! Reading `.tflite` models in pure Rust
+//!
+//! A reader for the TensorFlow Lite flatbuffer schema, that does not need
+//! the C++ TensorFlow build. Host tools and build scripts can use it to
+//! inspect the operators, tensors and buffers of a model, with
+//! `default-features = false` so that TensorFlow is not built.
+//!
+//! [`Model::from_buffer`] verifies the model once, and then each accessor
+//! is a zero-copy view into the buffer. Works without `std` or `alloc`.
+//!
+//! ```
+//! use tfmicro::schema::Model;
+//!
+//! let model = include_bytes!("../../examples/models/micro_speech.tflite");
+//! let model = Model::from_buffer(&model[..]).unwrap();
+//!
+//! for opcode in model.operator_codes() {
+//!     println!("{}", opcode.name());
+//! }
+//!
+//! let subgraph = model.subgraphs().get(0).unwrap();
+//! for tensor in subgraph.tensors() {
+//!     println!("{}: {:?}", tensor.name(), tensor.shape());
+//! }
+//! ```
+
+use crate::Error;
+
+#[cfg(test)]
+pub(crate) mod builder;
+mod flatbuffer;
+
+use flatbuffer::{private, Table};
+pub use flatbuffer::{Element, Vector, VectorIter};
+
+/// Builtin operator names, indexed by builtin code
+const BUILTIN_OPERATORS: [&str; 127] = [
+    "ADD",
+    "AVERAGE_POOL_2D",
+    "CONCATENATION",
+    "CONV_2D",
+    "DEPTHWISE_CONV_2D",
+    "DEPTH_TO_SPACE",
+    "DEQUANTIZE",
+    "EMBEDDING_LOOKUP",
+    "FLOOR",
+    "FULLY_CONNECTED",
+    "HASHTABLE_LOOKUP",
+    "L2_NORMALIZATION",
+    "L2_POOL_2D",
+    "LOCAL_RESPONSE_NORMALIZATION",
+    "LOGISTIC",
+    "LSH_PROJECTION",
+    "LSTM",
+    "MAX_POOL_2D",
+    "MUL",
+    "RELU",
+    "RELU_N1_TO_1",
+    "RELU6",
+    "RESHAPE",
+    "RESIZE_BILINEAR",
+    "RNN",
+    "SOFTMAX",
+    "SPACE_TO_DEPTH",
+    "SVDF",
+    "TANH",
+    "CONCAT_EMBEDDINGS",
+    "SKIP_GRAM",
+    "CALL",
+    "CUSTOM",
+    "EMBEDDING_LOOKUP_SPARSE",
+    "PAD",
+    "UNIDIRECTIONAL_SEQUENCE_RNN",
+    "GATHER",
+    "BATCH_TO_SPACE_ND",
+    "SPACE_TO_BATCH_ND",
+    "TRANSPOSE",
+    "MEAN",
+    "SUB",
+    "DIV",
+    "SQUEEZE",
+    "UNIDIRECTIONAL_SEQUENCE_LSTM",
+    "STRIDED_SLICE",
+    "BIDIRECTIONAL_SEQUENCE_RNN",
+    "EXP",
+    "TOPK_V2",
+    "SPLIT",
+    "LOG_SOFTMAX",
+    "DELEGATE",
+    "BIDIRECTIONAL_SEQUENCE_LSTM",
+    "CAST",
+    "PRELU",
+    "MAXIMUM",
+    "ARG_MAX",
+    "MINIMUM",
+    "LESS",
+    "NEG",
+    "PADV2",
+    "GREATER",
+    "GREATER_EQUAL",
+    "LESS_EQUAL",
+    "SELECT",
+    "SLICE",
+    "SIN",
+    "TRANSPOSE_CONV",
+    "SPARSE_TO_DENSE",
+    "TILE",
+    "EXPAND_DIMS",
+    "EQUAL",
+    "NOT_EQUAL",
+    "LOG",
+    "SUM",
+    "SQRT",
+    "RSQRT",
+    "SHAPE",
+    "POW",
+    "ARG_MIN",
+    "FAKE_QUANT",
+    "REDUCE_PROD",
+    "REDUCE_MAX",
+    "PACK",
+    "LOGICAL_OR",
+    "ONE_HOT",
+    "LOGICAL_AND",
+    "LOGICAL_NOT",
+    "UNPACK",
+    "REDUCE_MIN",
+    "FLOOR_DIV",
+    "REDUCE_ANY",
+    "SQUARE",
+    "ZEROS_LIKE",
+    "FILL",
+    "FLOOR_MOD",
+    "RANGE",
+    "RESIZE_NEAREST_NEIGHBOR",
+    "LEAKY_RELU",
+    "SQUARED_DIFFERENCE",
+    "MIRROR_PAD",
+    "ABS",
+    "SPLIT_V",
+    "UNIQUE",
+    "CEIL",
+    "REVERSE_V2",
+    "ADD_N",
+    "GATHER_ND",
+    "COS",
+    "WHERE",
+    "RANK",
+    "ELU",
+    "REVERSE_SEQUENCE",
+    "MATRIX_DIAG",
+    "QUANTIZE",
+    "MATRIX_SET_DIAG",
+    "ROUND",
+    "HARD_SWISH",
+    "IF",
+    "WHILE",
+    "NON_MAX_SUPPRESSION_V4",
+    "NON_MAX_SUPPRESSION_V5",
+    "SCATTER_ND",
+    "SELECT_V2",
+    "DENSIFY",
+    "SEGMENT_SUM",
+    "BATCH_MATMUL",
+];
+
+/// Builtin code of custom operators
+pub const CUSTOM: i32 = 32;
+
+/// Name of the builtin operator with code `code`, for example
+/// `FULLY_CONNECTED`
+pub fn builtin_operator_name(code: i32) -> Option<&'static str> {
+    BUILTIN_OPERATORS.get(code as usize).copied()
+}
+
+/// Element type of a tensor
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TensorType {
+    Float32,
+    Float16,
+    Int32,
+    UInt8,
+    Int64,
+    String,
+    Bool,
+    Int16,
+    Complex64,
+    Int8,
+    Float64,
+}
+
+impl TensorType {
+    pub(crate) fn from_u8(t: u8) -> Option<Self> {
+        use TensorType::*;
+
+        [
+            Float32, Float16, Int32, UInt8, Int64, String, Bool, Int16,
+            Complex64, Int8, Float64,
+        ]
+        .get(t as usize)
+        .copied()
+    }
+
+    /// Size in bytes of each element, or `None` for strings
+    pub fn size(self) -> Option<usize> {
+        use TensorType::*;
+
+        match self {
+            UInt8 | Bool | Int8 => Some(1),
+            Float16 | Int16 => Some(2),
+            Float32 | Int32 => Some(4),
+            Int64 | Complex64 | Float64 => Some(8),
+            String => None,
+        }
+    }
+}
+
+/// Defines a view of a table in the schema, that can be read from a vector
+macro_rules! schema_table {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy)]
+        pub struct $name<'a>(Table<'a>);
+
+        impl private::Sealed for $name<'_> {}
+
+        impl<'a> Element<'a> for $name<'a> {
+            const SIZE: usize = 4;
+
+            fn read(buf: &'a [u8], pos: usize) -> Option<Self> {
+                Table::read(buf, pos).map($name)
+            }
+        }
+    };
+}
+
+schema_table! {
+    /// A `.tflite` model
+    Model
+}
+
+schema_table! {
+    /// An operator used by the model
+    OperatorCode
+}
+
+schema_table! {
+    /// A graph of operators
+    SubGraph
+}
+
+schema_table! {
+    /// A tensor in a subgraph
+    Tensor
+}
+
+schema_table! {
+    /// Quantization parameters of a tensor
+    Quantization
+}
+
+schema_table! {
+    /// An operator in a subgraph
+    Operator
+}
+
+schema_table! {
+    /// Constant data, such as weights
+    Buffer
+}
+
+schema_table! {
+    /// Named metadata, stored in a buffer
+    Metadata
+}
+
+/// The schema version that this reader supports
+pub const SCHEMA_VERSION: u32 = 3;
+
+impl<'a> Model<'a> {
+    /// Verify the `.tflite` model in `buffer`
+    ///
+    /// Checks that every table, vector and string that the accessors read
+    /// lies within the buffer, and that every index into the operator
+    /// codes, tensors and buffers is in range. Fields that the reader does
+    /// not expose, such as operator options, are not verified.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidModel` if the buffer failed verification
+    pub fn from_buffer(buffer: &'a [u8]) -> Result<Self, Error> {
+        if buffer.get(4..8) != Some(b"TFL3") {
+            return Err(Error::InvalidModel);
+        }
+
+        let model = Table::root(buffer)
+            .filter(|table| table.verify().is_some())
+            .map(Model)
+            .ok_or(Error::InvalidModel)?;
+        model.verify().ok_or(Error::InvalidModel)?;
+
+        Ok(model)
+    }
+
+    fn verify(&self) -> Option<()> {
+        let t = self.0;
+        t.verify_scalar(0, 4)?;
+        t.verify_str(3)?;
+        t.verify_vector::<i32>(5)?;
+        t.verify_tables(1, |t| {
+            t.verify_scalar(0, 1)?;
+            t.verify_str(1)?;
+            t.verify_scalar(2, 4)?;
+            t.verify_scalar(3, 4)
+        })?;
+        t.verify_tables(4, |t| t.verify_vector::<u8>(0))?;
+        t.verify_tables(2, |t| SubGraph(t).verify(self))?;
+        t.verify_tables(6, |t| {
+            t.verify_str(0)?;
+            t.verify_scalar(1, 4)?;
+            in_range(Metadata(t).buffer() as i64, self.buffers().len())
+        })?;
+
+        for n in self.0.vector::<i32>(5) {
+            in_range(n as i64, self.buffers().len())?;
+        }
+
+        Some(())
+    }
+
+    /// Schema version of the model
+    pub fn version(&self) -> u32 {
+        self.0.u32(0, 0)
+    }
+
+    /// Operators used by the model. Each operator refers to one of these by
+    /// index
+    pub fn operator_codes(&self) -> Vector<'a, OperatorCode<'a>> {
+        self.0.vector(1)
+    }
+
+    /// Subgraphs of the model. The first is the main subgraph
+    pub fn subgraphs(&self) -> Vector<'a, SubGraph<'a>> {
+        self.0.vector(2)
+    }
+
+    /// Description of the model, if any
+    pub fn description(&self) -> Option<&'a str> {
+        self.0.str(3)
+    }
+
+    /// Buffers of the model. Buffer 0 is always empty, and is used by
+    /// tensors without constant data
+    pub fn buffers(&self) -> Vector<'a, Buffer<'a>> {
+        self.0.vector(4)
+    }
+
+    /// Named metadata of the model
+    pub fn metadata(&self) -> Vector<'a, Metadata<'a>> {
+        self.0.vector(6)
+    }
+
+    /// Data of the metadata named `name`
+    pub fn metadata_buffer(&self, name: &str) -> Option<&'a [u8]> {
+        let metadata = self.metadata().iter().find(|m| m.name() == name)?;
+
+        Some(self.buffers().get(metadata.buffer() as usize)?.data())
+    }
+}
+
+impl<'a> OperatorCode<'a> {
+    /// Builtin operator code, as in the TensorFlow Lite schema
+    ///
+    /// Older models only have the 8-bit `deprecated_builtin_code` field,
+    /// and newer models also have the 32-bit `builtin_code` field, which
+    /// is needed for codes above 127. As in TensorFlow's `GetBuiltinCode`,
+    /// the larger of the two is returned.
+    pub fn builtin_code(&self) -> i32 {
+        let deprecated = self.0.u8(0, 0) as i8 as i32;
+
+        deprecated.max(self.0.i32(3, 0))
+    }
+
+    /// Custom code of a custom operator
+    pub fn custom_code(&self) -> Option<&'a str> {
+        self.0.str(1)
+    }
+
+    /// Returns true if this is a custom operator
+    pub fn is_custom(&self) -> bool {
+        self.builtin_code() == CUSTOM
+    }
+
+    /// Name of the builtin operator, for example `FULLY_CONNECTED`, or the
+    /// custom code of a custom operator
+    pub fn name(&self) -> &'a str {
+        match self.custom_code() {
+            Some(code) if self.is_custom() => code,
+            _ => {
+                builtin_operator_name(self.builtin_code()).unwrap_or("UNKNOWN")
+            }
+        }
+    }
+
+    /// Version of the operator
+    pub fn version(&self) -> i32 {
+        self.0.i32(2, 1)
+    }
+}
+
+/// Check that `n` is an index into a vector of length `len`
+fn in_range(n: i64, len: usize) -> Option<()> {
+    if n >= 0 && (n as usize) < len {
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Check that each tensor index is in range. Optional tensors are -1
+fn tensors_in_range(tensors: Vector<i32>, len: usize) -> Option<()> {
+    for n in tensors {
+        if n != -1 {
+            in_range(n as i64, len)?;
+        }
+    }
+
+    Some(())
+}
+
+impl<'a> SubGraph<'a> {
+    fn verify(&self, model: &Model) -> Option<()> {
+        let t = self.0;
+        t.verify_vector::<i32>(1)?;
+        t.verify_vector::<i32>(2)?;
+        t.verify_str(4)?;
+        t.verify_tables(0, |t| Tensor(t).verify(model))?;
+
+        let tensors = self.tensors().len();
+        tensors_in_range(self.inputs(), tensors)?;
+        tensors_in_range(self.outputs(), tensors)?;
+
+        t.verify_tables(3, |t| {
+            t.verify_scalar(0, 4)?;
+            t.verify_vector::<i32>(1)?;
+            t.verify_vector::<i32>(2)?;
+            t.verify_vector::<u8>(5)?;
+            t.verify_vector::<i32>(8)?;
+
+            let operator = Operator(t);
+            in_range(
+                operator.opcode_index() as i64,
+                model.operator_codes().len(),
+            )?;
+            tensors_in_range(operator.inputs(), tensors)?;
+            tensors_in_range(operator.outputs(), tensors)?;
+            tensors_in_range(operator.intermediates(), tensors)
+        })
+    }
+
+    /// Tensors of the subgraph
+    pub fn tensors(&self) -> Vector<'a, Tensor<'a>> {
+        self.0.vector(0)
+    }
+
+    /// Indices of the input tensors
+    pub fn inputs(&self) -> Vector<'a, i32> {
+        self.0.vector(1)
+    }
+
+    /// Indices of the output tensors
+    pub fn outputs(&self) -> Vector<'a, i32> {
+        self.0.vector(2)
+    }
+
+    /// Operators of the subgraph, in execution order
+    pub fn operators(&self) -> Vector<'a, Operator<'a>> {
+        self.0.vector(3)
+    }
+
+    /// Name of the subgraph, if any
+    pub fn name(&self) -> Option<&'a str> {
+        self.0.str(4)
+    }
+}
+
+impl<'a> Tensor<'a> {
+    fn verify(&self, model: &Model) -> Option<()> {
+        let t = self.0;
+        t.verify_vector::<i32>(0)?;
+        t.verify_scalar(1, 1)?;
+        t.verify_scalar(2, 4)?;
+        t.verify_str(3)?;
+        t.verify_scalar(5, 1)?;
+        t.verify_vector::<i32>(7)?;
+        if let Some(t) = t.verify_table(4)? {
+            t.verify_vector::<f32>(0)?;
+            t.verify_vector::<f32>(1)?;
+            t.verify_vector::<f32>(2)?;
+            t.verify_vector::<i64>(3)?;
+            t.verify_scalar(6, 4)?;
+        }
+
+        in_range(self.buffer() as i64, model.buffers().len())
+    }
+
+    /// Dimensions of the tensor
+    pub fn shape(&self) -> Vector<'a, i32> {
+        self.0.vector(0)
+    }
+
+    /// Dimensions of the tensor, where unknown dimensions are -1. Empty if
+    /// the shape is fully known
+    pub fn shape_signature(&self) -> Vector<'a, i32> {
+        self.0.vector(7)
+    }
+
+    /// Element type, or `None` if it is not known to this reader
+    pub fn tensor_type(&self) -> Option<TensorType> {
+        TensorType::from_u8(self.0.u8(1, 0))
+    }
+
+    /// Index of the buffer that holds the constant data of the tensor.
+    /// Buffer 0 is empty
+    pub fn buffer(&self) -> u32 {
+        self.0.u32(2, 0)
+    }
+
+    /// Name of the tensor
+    pub fn name(&self) -> &'a str {
+        self.0.str(3).unwrap_or("")
+    }
+
+    /// Quantization parameters, if any
+    pub fn quantization(&self) -> Option<Quantization<'a>> {
+        self.0.table(4).map(Quantization)
+    }
+
+    /// Returns true if the tensor is a variable, that persists between
+    /// invocations
+    pub fn is_variable(&self) -> bool {
+        self.0.u8(5, 0) != 0
+    }
+
+    /// Number of elements, or `None` if the shape is not known
+    pub fn elements(&self) -> Option<usize> {
+        self.shape().iter().try_fold(1usize, |n, d| {
+            if d < 0 {
+                None
+            } else {
+                n.checked_mul(d as usize)
+            }
+        })
+    }
+}
+
+impl<'a> Quantization<'a> {
+    /// Minimum real values, per channel
+    pub fn min(&self) -> Vector<'a, f32> {
+        self.0.vector(0)
+    }
+
+    /// Maximum real values, per channel
+    pub fn max(&self) -> Vector<'a, f32> {
+        self.0.vector(1)
+    }
+
+    /// Scales, per channel
+    pub fn scale(&self) -> Vector<'a, f32> {
+        self.0.vector(2)
+    }
+
+    /// Zero points, per channel
+    pub fn zero_point(&self) -> Vector<'a, i64> {
+        self.0.vector(3)
+    }
+
+    /// Dimension of the tensor that the channels are along
+    pub fn quantized_dimension(&self) -> i32 {
+        self.0.i32(6, 0)
+    }
+}
+
+impl<'a> Operator<'a> {
+    /// Index into the operator codes of the model
+    pub fn opcode_index(&self) -> u32 {
+        self.0.u32(0, 0)
+    }
+
+    /// Indices of the input tensors. Optional inputs that are not present
+    /// are -1
+    pub fn inputs(&self) -> Vector<'a, i32> {
+        self.0.vector(1)
+    }
+
+    /// Indices of the output tensors
+    pub fn outputs(&self) -> Vector<'a, i32> {
+        self.0.vector(2)
+    }
+
+    /// Indices of the intermediate tensors
+    pub fn intermediates(&self) -> Vector<'a, i32> {
+        self.0.vector(8)
+    }
+
+    /// Options of a custom operator
+    pub fn custom_options(&self) -> &'a [u8] {
+        self.0.bytes(5)
+    }
+}
+
+impl<'a> Buffer<'a> {
+    /// Contents of the buffer
+    pub fn data(&self) -> &'a [u8] {
+        self.0.bytes(0)
+    }
+}
+
+impl<'a> Metadata<'a> {
+    /// Name of the metadata
+    pub fn name(&self) -> &'a str {
+        self.0.str(0).unwrap_or("")
+    }
+
+    /// Index of the buffer that holds the metadata
+    pub fn buffer(&self) -> u32 {
+        self.0.u32(1, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::builder::{Builder, Field::*};
+    use super::*;
+
+    const MICRO_SPEECH: &[u8] =
+        include_bytes!("../../examples/models/micro_speech.tflite");
+    const HELLO_WORLD: &[u8] =
+        include_bytes!("../../examples/models/hello_world.tflite");
+
+    #[test]
+    fn micro_speech() {
+        let model = Model::from_buffer(MICRO_SPEECH).unwrap();
+
+        assert_eq!(model.version(), SCHEMA_VERSION);
+        assert_eq!(model.subgraphs().len(), 1);
+
+        let names: [&str; 3] = {
+            let mut codes = model.operator_codes().iter();
+            let mut name = || codes.next().unwrap().name();
+            [name(), name(), name()]
+        };
+        assert_eq!(names, ["DEPTHWISE_CONV_2D", "FULLY_CONNECTED", "SOFTMAX"]);
+
+        let subgraph = model.subgraphs().get(0).unwrap();
+        assert_eq!(subgraph.tensors().len(), 8);
+        assert_eq!(subgraph.operators().len(), 3);
+
+        let input = subgraph.inputs().get(0).unwrap();
+        let input = subgraph.tensors().get(input as usize).unwrap();
+        assert_eq!(input.tensor_type(), Some(TensorType::UInt8));
+        assert_eq!(input.elements(), Some(49 * 40));
+        assert!(input.quantization().is_some());
+    }
+
+    #[test]
+    fn hello_world() {
+        let model = Model::from_buffer(HELLO_WORLD).unwrap();
+        let subgraph = model.subgraphs().get(0).unwrap();
+
+        assert_eq!(subgraph.tensors().len(), 12);
+        assert_eq!(subgraph.operators().len(), 5);
+
+        // Weights are stored in buffers, activations are not
+        for operator in subgraph.operators() {
+            let output = operator.outputs().get(0).unwrap();
+            let output = subgraph.tensors().get(output as usize).unwrap();
+            let buffer = model.buffers().get(output.buffer() as usize);
+
+            assert!(buffer.unwrap().data().is_empty());
+        }
+    }
+
+    #[test]
+    fn builtin_names() {
+        assert_eq!(builtin_operator_name(9), Some("FULLY_CONNECTED"));
+        assert_eq!(builtin_operator_name(CUSTOM), Some("CUSTOM"));
+        assert_eq!(builtin_operator_name(114), Some("QUANTIZE"));
+        assert_eq!(builtin_operator_name(-1), None);
+        assert_eq!(builtin_operator_name(127), None);
+    }
+
+    /// A model with a single operator code, that has both the
+    /// `deprecated_builtin_code` and `builtin_code` fields
+    fn operator_code_model(deprecated: i8, builtin_code: i32) -> Builder {
+        let mut model = Builder::new("model");
+        model.table("model", &[Absent, Offset("codes")]);
+        model.vector("codes", &[Offset("code")]);
+        model.table(
+            "code",
+            &[U8(deprecated as u8), Absent, Absent, I32(builtin_code)],
+        );
+
+        model
+    }
+
+    #[test]
+    fn builtin_code_fields() {
+        let code = |deprecated, builtin_code| {
+            let model = operator_code_model(deprecated, builtin_code);
+            let model = Model::from_buffer(model.as_bytes()).unwrap();
+
+            model.operator_codes().get(0).unwrap().builtin_code()
+        };
+
+        // Older converters only write the deprecated field
+        assert_eq!(code(9, 0), 9);
+        // Codes above 127 only fit in the new field
+        assert_eq!(code(127, 130), 130);
+        assert_eq!(code(9, 9), 9);
+    }
+
+    #[test]
+    fn rejects_invalid_models() {
+        assert_eq!(
+            Model::from_buffer(b"not a model").err(),
+            Some(Error::InvalidModel)
+        );
+
+        // Truncated models are rejected, or read without panicking
+        for len in 0..HELLO_WORLD.len() {
+            if let Ok(model) = Model::from_buffer(&HELLO_WORLD[..len]) {
+                for subgraph in model.subgraphs() {
+                    subgraph.tensors().iter().for_each(|t| {
+                        t.name();
+                    });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn corrupted_models_do_not_panic() {
+        let mut model = HELLO_WORLD.to_vec();
+
+        for i in 0..model.len() {
+            let original = model[i];
+            for &value in &[0x00, 0x7f, 0x80, 0xff] {
+                model[i] = value;
+                if let Ok(model) = Model::from_buffer(&model) {
+                    for subgraph in model.subgraphs() {
+                        for operator in subgraph.operators() {
+                            operator.inputs().iter().count();
+                        }
+                    }
+                }
+            }
+            model[i] = original;
+        }
+    }
+}
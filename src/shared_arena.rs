@@ -0,0 +1,585 @@
+//! One tensor arena shared by models that run one after another
+//!
+//! A product might run a wake word model, and then a command model, but
+//! never both at once. Rather than give each its own arena, a
+//! [`SharedArena`] builds an interpreter for one model at a time. Each
+//! interpreter borrows the arena mutably, so the previous interpreter must
+//! be dropped before the next is built.
+//!
+//! Building an interpreter resets the state held in a model's variable
+//! tensors, such as the state of a recurrent layer. To keep it from one run
+//! to the next, give each model its own [`TensorState`].
+//!
+//! ```
+//! # use tfmicro::{include_model, AllOpResolver, Model, SharedArena};
+//! let wake = include_model!("../examples/models/hello_world.tflite");
+//! let wake = Model::from_buffer(wake).unwrap();
+//! let command = include_model!("../examples/models/micro_speech.tflite");
+//! let command = Model::from_buffer(command).unwrap();
+//! let resolver = AllOpResolver::new();
+//!
+//! let mut buffer = [0; 10 * 1024];
+//! let mut arena = SharedArena::new(&mut buffer[..]);
+//!
+//! let mut interpreter = arena.interpreter(&wake, &resolver).unwrap();
+//! interpreter.input(0, &[0.0f32]).unwrap();
+//! interpreter.invoke().unwrap();
+//! drop(interpreter);
+//!
+//! let mut interpreter = arena.interpreter(&command, &resolver).unwrap();
+//! interpreter.invoke().unwrap();
+//! drop(interpreter);
+//!
+//! // The arena size needed by the largest model
+//! let size = arena.max_used_bytes();
+//! ```
+
+use core::ops::Deref;
+
+use managed::ManagedSlice;
+
+use crate::micro_interpreter::MicroInterpreter;
+use crate::micro_op_resolver::OpResolverRepr;
+use crate::model::Model;
+use crate::tensor::{ElemTypeOf, Tensor};
+use crate::{Error, Status};
+
+/// A tensor arena that is shared by interpreters for several models, one
+/// at a time
+pub struct SharedArena<'a> {
+    arena: ManagedSlice<'a, u8>,
+    max_used_bytes: usize,
+}
+
+impl<'a> SharedArena<'a> {
+    /// Share `arena`, which is either a mutable slice or, with the `std` or
+    /// `alloc` features, a `Vec`
+    pub fn new<TArena: Into<ManagedSlice<'a, u8>>>(arena: TArena) -> Self {
+        Self {
+            arena: arena.into(),
+            max_used_bytes: 0,
+        }
+    }
+
+    /// Size of the arena in bytes
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns `true` if the arena is empty
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    /// The largest number of bytes used by any interpreter built in this
+    /// arena so far. Once an interpreter has been built for each model, this
+    /// is the arena size needed for the set
+    pub fn max_used_bytes(&self) -> usize {
+        self.max_used_bytes
+    }
+
+    /// Build an interpreter for `model` in the arena
+    ///
+    /// # Errors
+    ///
+    /// As for [`MicroInterpreter::new`](crate::MicroInterpreter::new)
+    pub fn interpreter<'s, OpResolver: OpResolverRepr>(
+        &'s mut self,
+        model: &'s Model,
+        resolver: &'s OpResolver,
+    ) -> Result<ArenaInterpreter<'s>, Error> {
+        let interpreter =
+            MicroInterpreter::new(model, resolver, &mut self.arena[..])?;
+        self.max_used_bytes =
+            self.max_used_bytes.max(interpreter.arena_used_bytes());
+
+        Ok(ArenaInterpreter {
+            interpreter,
+            state: None,
+        })
+    }
+
+    /// Build an interpreter for `model` in the arena, restoring its
+    /// variable tensors from `state` if they were saved for the same model.
+    /// They are saved to `state` again when the interpreter is dropped
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::TensorStateTooSmall` if the variable tensors of the
+    /// model do not fit in `state`.
+    ///
+    /// Otherwise, as for
+    /// [`MicroInterpreter::new`](crate::MicroInterpreter::new)
+    pub fn interpreter_with_state<'s, OpResolver: OpResolverRepr>(
+        &'s mut self,
+        model: &'s Model,
+        resolver: &'s OpResolver,
+        state: &'s mut TensorState<'_>,
+    ) -> Result<ArenaInterpreter<'s>, Error> {
+        let mut interpreter = self.interpreter(model, resolver)?;
+
+        let layout =
+            Layout::new(model.as_bytes(), &mut interpreter.interpreter);
+        if layout.len > state.buffer.len() {
+            return Err(Error::TensorStateTooSmall);
+        }
+
+        // State saved for a different model, or for tensors of different
+        // sizes, is discarded
+        if state.saved == Some(layout) {
+            restore(&state.buffer[..layout.len], &mut interpreter.interpreter);
+        }
+        state.saved = None;
+
+        let TensorState { buffer, saved } = state;
+        interpreter.state = Some(StateRef {
+            buffer: &mut buffer[..],
+            saved,
+            layout,
+        });
+        Ok(interpreter)
+    }
+}
+
+/// The variable tensors of an interpreter
+trait VariableTensors {
+    /// Call `f` with the index and bytes of each variable tensor, in order
+    fn for_each(&mut self, f: impl FnMut(usize, &mut [u8]));
+}
+
+impl VariableTensors for MicroInterpreter<'_> {
+    fn for_each(&mut self, mut f: impl FnMut(usize, &mut [u8])) {
+        for n in 0..self.tensors_len() {
+            let tensor = self.tensor_mut(n);
+            if tensor.is_variable() {
+                f(n, tensor.as_bytes_mut());
+            }
+        }
+    }
+}
+
+/// Copy the variable tensors into `buffer`, one after another
+fn save(buffer: &mut [u8], tensors: &mut impl VariableTensors) {
+    let mut len = 0;
+    tensors.for_each(|_, bytes| {
+        buffer[len..len + bytes.len()].copy_from_slice(bytes);
+        len += bytes.len();
+    });
+}
+
+/// Copy the variable tensors back from `saved`, as written by [`save`]
+fn restore(mut saved: &[u8], tensors: &mut impl VariableTensors) {
+    tensors.for_each(|_, bytes| {
+        bytes.copy_from_slice(&saved[..bytes.len()]);
+        saved = &saved[bytes.len()..];
+    });
+}
+
+/// Identifies the model and variable tensors that state was saved for
+///
+/// The model is identified by a hash of its bytes, so state is restored
+/// for the same model even if it is loaded again at another address.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Layout {
+    // FNV-1a hash of the model
+    model: u64,
+    // Total length of the variable tensors
+    len: usize,
+    // FNV-1a hash of the index and length of each variable tensor
+    hash: u64,
+}
+
+impl Layout {
+    fn new(model: &[u8], tensors: &mut impl VariableTensors) -> Self {
+        let mut len = 0;
+        let mut hash = FNV_OFFSET_BASIS;
+        tensors.for_each(|n, bytes| {
+            len += bytes.len();
+            hash = fnv1a(hash, &(n as u64).to_le_bytes());
+            hash = fnv1a(hash, &(bytes.len() as u64).to_le_bytes());
+        });
+
+        Self {
+            model: fnv1a(FNV_OFFSET_BASIS, model),
+            len,
+            hash,
+        }
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Continue the 64-bit FNV-1a hash `hash` over `bytes`
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// A buffer holding the variable tensors of a model between runs in a
+/// [`SharedArena`]
+pub struct TensorState<'a> {
+    buffer: ManagedSlice<'a, u8>,
+    // Layout of the saved state, if any
+    saved: Option<Layout>,
+}
+
+impl<'a> TensorState<'a> {
+    /// Hold state in `buffer`, which is either a mutable slice or, with the
+    /// `std` or `alloc` features, a `Vec`. It must be at least as large as
+    /// the variable tensors of the model
+    pub fn new<TBuffer: Into<ManagedSlice<'a, u8>>>(buffer: TBuffer) -> Self {
+        Self {
+            buffer: buffer.into(),
+            saved: None,
+        }
+    }
+
+    /// Returns `true` if state has been saved, and will be restored when
+    /// the next interpreter is built
+    pub fn is_saved(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// Discard the saved state, so that the variable tensors start from
+    /// zero when the next interpreter is built
+    pub fn clear(&mut self) {
+        self.saved = None;
+    }
+}
+
+/// Where an [`ArenaInterpreter`] saves its variable tensors
+struct StateRef<'s> {
+    buffer: &'s mut [u8],
+    saved: &'s mut Option<Layout>,
+    layout: Layout,
+}
+
+/// An interpreter built in a [`SharedArena`]
+///
+/// Dereferences to a [`MicroInterpreter`](crate::MicroInterpreter).
+pub struct ArenaInterpreter<'s> {
+    interpreter: MicroInterpreter<'s>,
+    state: Option<StateRef<'s>>,
+}
+
+impl<'s> ArenaInterpreter<'s> {
+    /// See [`MicroInterpreter::input`](crate::MicroInterpreter::input)
+    pub fn input<T: ElemTypeOf + Clone>(
+        &mut self,
+        n: usize,
+        data: &[T],
+    ) -> Result<(), Error> {
+        self.interpreter.input(n, data)
+    }
+
+    /// See [`MicroInterpreter::input_tensor`](crate::MicroInterpreter::input_tensor)
+    pub fn input_tensor(&mut self, n: usize) -> &mut Tensor {
+        self.interpreter.input_tensor(n)
+    }
+
+    /// See [`MicroInterpreter::invoke`](crate::MicroInterpreter::invoke)
+    pub fn invoke(&mut self) -> Result<(), Status> {
+        self.interpreter.invoke()
+    }
+}
+
+impl<'s> Deref for ArenaInterpreter<'s> {
+    type Target = MicroInterpreter<'s>;
+
+    fn deref(&self) -> &MicroInterpreter<'s> {
+        &self.interpreter
+    }
+}
+
+impl Drop for ArenaInterpreter<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = &mut self.state {
+            // The buffer was checked to be large enough when the interpreter
+            // was built
+            save(state.buffer, &mut self.interpreter);
+            *state.saved = Some(state.layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::include_model;
+    use crate::micro_op_resolver::AllOpResolver;
+    use crate::schema::builder::{Builder, Field::*};
+
+    #[test]
+    fn sequential_models() {
+        let hello_world =
+            include_model!("../examples/models/hello_world.tflite");
+        let hello_world = Model::from_buffer(hello_world).unwrap();
+        let micro_speech =
+            include_model!("../examples/models/micro_speech.tflite");
+        let micro_speech = Model::from_buffer(micro_speech).unwrap();
+        let resolver = AllOpResolver::new();
+
+        let mut buffer = [0; 10 * 1024];
+        let mut arena = SharedArena::new(&mut buffer[..]);
+
+        let mut used = [0; 2];
+        for _ in 0..2 {
+            let mut interpreter =
+                arena.interpreter(&hello_world, &resolver).unwrap();
+            interpreter.input(0, &[1.57f32]).unwrap();
+            interpreter.invoke().unwrap();
+            assert!(interpreter.output(0).as_data::<f32>()[0] > 0.9);
+            used[0] = interpreter.arena_used_bytes();
+            drop(interpreter);
+
+            let mut interpreter =
+                arena.interpreter(&micro_speech, &resolver).unwrap();
+            interpreter.invoke().unwrap();
+            assert_eq!(interpreter.outputs_len(), 1);
+            used[1] = interpreter.arena_used_bytes();
+        }
+
+        assert!(used[1] > used[0]);
+        assert_eq!(arena.max_used_bytes(), used[1]);
+    }
+
+    #[test]
+    fn arena_too_small() {
+        let hello_world =
+            include_model!("../examples/models/hello_world.tflite");
+        let hello_world = Model::from_buffer(hello_world).unwrap();
+        let micro_speech =
+            include_model!("../examples/models/micro_speech.tflite");
+        let micro_speech = Model::from_buffer(micro_speech).unwrap();
+        let resolver = AllOpResolver::new();
+
+        let mut buffer = [0; 4 * 1024];
+        let mut arena = SharedArena::new(&mut buffer[..]);
+
+        // A failed model does not affect the next one
+        assert!(arena.interpreter(&micro_speech, &resolver).is_err());
+        arena.interpreter(&hello_world, &resolver).unwrap();
+        assert!(arena.max_used_bytes() > 0);
+    }
+
+    #[test]
+    fn tensor_state() {
+        let hello_world =
+            include_model!("../examples/models/hello_world.tflite");
+        let hello_world = Model::from_buffer(hello_world).unwrap();
+        let resolver = AllOpResolver::new();
+
+        let mut buffer = [0; 4 * 1024];
+        let mut arena = SharedArena::new(&mut buffer[..]);
+        let mut state_buffer = [0u8; 0];
+        let mut state = TensorState::new(&mut state_buffer[..]);
+        assert!(!state.is_saved());
+
+        // The model has no variable tensors, so needs no state
+        let mut interpreter = arena
+            .interpreter_with_state(&hello_world, &resolver, &mut state)
+            .unwrap();
+        interpreter.input(0, &[0.0f32]).unwrap();
+        interpreter.invoke().unwrap();
+        drop(interpreter);
+        assert!(state.is_saved());
+
+        state.clear();
+        assert!(!state.is_saved());
+    }
+
+    /// A model with one ADD operator, that adds its input tensor 0 to the
+    /// variable tensor 1, giving the output tensor 2. Each is float32 with
+    /// shape `[1, 2]`
+    fn stateful_model() -> Builder {
+        let mut model = Builder::new("model");
+        model.table(
+            "model",
+            &[
+                I32(3),
+                Offset("codes"),
+                Offset("subgraphs"),
+                Absent,
+                Offset("buffers"),
+            ],
+        );
+        model.vector("codes", &[Offset("add")]);
+        model.vector("subgraphs", &[Offset("subgraph")]);
+        model.vector("buffers", &[Offset("buffer")]);
+        model.table("buffer", &[]);
+        model.table("add", &[U8(0), Absent, Absent, I32(0)]);
+
+        model.table(
+            "subgraph",
+            &[
+                Offset("tensors"),
+                Offset("inputs"),
+                Offset("outputs"),
+                Offset("operators"),
+            ],
+        );
+        model.vector("tensors", &[Offset("x"), Offset("state"), Offset("y")]);
+        model.vector("operators", &[Offset("operator")]);
+        model.table(
+            "operator",
+            &[Absent, Offset("operator_inputs"), Offset("outputs")],
+        );
+        model.vector("inputs", &[I32(0)]);
+        model.vector("operator_inputs", &[I32(0), I32(1)]);
+        model.vector("outputs", &[I32(2)]);
+
+        // Float32 tensors with the shape, name and whether they are
+        // variable
+        model.table("x", &[Offset("shape"), Absent, Absent, Offset("x_name")]);
+        model.table("y", &[Offset("shape"), Absent, Absent, Offset("y_name")]);
+        model.table(
+            "state",
+            &[
+                Offset("shape"),
+                Absent,
+                Absent,
+                Offset("state_name"),
+                Absent,
+                U8(1),
+            ],
+        );
+        model.vector("shape", &[I32(1), I32(2)]);
+        model.string("x_name", "x");
+        model.string("y_name", "y");
+        model.string("state_name", "state");
+
+        model
+    }
+
+    #[test]
+    fn stateful_model_state() {
+        let buffer = stateful_model();
+        let model = Model::from_buffer(buffer.as_bytes()).unwrap();
+        let hello_world =
+            include_model!("../examples/models/hello_world.tflite");
+        let hello_world = Model::from_buffer(hello_world).unwrap();
+        let resolver = AllOpResolver::new();
+
+        let mut arena_buffer = [0; 4 * 1024];
+        let mut arena = SharedArena::new(&mut arena_buffer[..]);
+
+        // The variable tensor does not fit
+        let mut state_buffer = [0u8; 4];
+        let mut state = TensorState::new(&mut state_buffer[..]);
+        assert_eq!(
+            arena
+                .interpreter_with_state(&model, &resolver, &mut state)
+                .err(),
+            Some(Error::TensorStateTooSmall)
+        );
+
+        let mut state_buffer = [0u8; 8];
+        let mut state = TensorState::new(&mut state_buffer[..]);
+        let run = |arena: &mut SharedArena, state: Option<&mut TensorState>| {
+            let mut interpreter = match state {
+                Some(state) => arena
+                    .interpreter_with_state(&model, &resolver, state)
+                    .unwrap(),
+                None => arena.interpreter(&model, &resolver).unwrap(),
+            };
+            interpreter.input(0, &[1.0f32, 2.0]).unwrap();
+            interpreter.invoke().unwrap();
+            let y = interpreter.output(0).as_data::<f32>();
+            let y = [y[0], y[1]];
+
+            // Change the state for the next run
+            let variable = interpreter.interpreter.tensor_mut(1);
+            assert!(variable.is_variable());
+            variable.as_data_mut::<f32>().copy_from_slice(&[10.0, 20.0]);
+            y
+        };
+
+        // The variable tensor starts from zero
+        assert_eq!(run(&mut arena, Some(&mut state)), [1.0, 2.0]);
+        assert!(state.is_saved());
+
+        // It is restored after another model has used the arena
+        let mut interpreter =
+            arena.interpreter(&hello_world, &resolver).unwrap();
+        interpreter.input(0, &[1.57f32]).unwrap();
+        interpreter.invoke().unwrap();
+        drop(interpreter);
+        assert_eq!(run(&mut arena, Some(&mut state)), [11.0, 22.0]);
+
+        // It is not restored without the state, or once it is cleared
+        assert_eq!(run(&mut arena, None), [1.0, 2.0]);
+        state.clear();
+        assert_eq!(run(&mut arena, Some(&mut state)), [1.0, 2.0]);
+        assert_eq!(run(&mut arena, Some(&mut state)), [11.0, 22.0]);
+    }
+
+    impl VariableTensors for [(usize, &mut [u8]); 2] {
+        fn for_each(&mut self, mut f: impl FnMut(usize, &mut [u8])) {
+            for (n, bytes) in self.iter_mut() {
+                f(*n, bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn save_restore() {
+        let model = [0u8; 16];
+        let (mut a, mut b) = ([1u8, 2, 3], [4u8; 5]);
+        let mut tensors = [(1, &mut a[..]), (4, &mut b[..])];
+        let layout = Layout::new(&model, &mut tensors);
+        assert_eq!(layout.len, 8);
+
+        let mut buffer = [0; 8];
+        save(&mut buffer, &mut tensors);
+        assert_eq!(buffer, [1, 2, 3, 4, 4, 4, 4, 4]);
+
+        for (_, bytes) in tensors.iter_mut() {
+            for byte in bytes.iter_mut() {
+                *byte = 0;
+            }
+        }
+        restore(&buffer, &mut tensors);
+        assert_eq!(tensors[0].1, [1, 2, 3]);
+        assert_eq!(tensors[1].1, [4; 5]);
+        assert_eq!(Layout::new(&model, &mut tensors), layout);
+
+        // The same model at another address, and other models
+        let copy = model;
+        assert_eq!(Layout::new(&copy, &mut tensors), layout);
+        assert_ne!(Layout::new(&model[..8], &mut tensors), layout);
+        let mut other = model;
+        other[15] = 1;
+        assert_ne!(Layout::new(&other, &mut tensors), layout);
+
+        // Tensors of the same total length, but different sizes
+        let (mut a, mut b) = ([0u8; 4], [0u8; 4]);
+        let mut resized = [(1, &mut a[..]), (4, &mut b[..])];
+        assert_ne!(Layout::new(&model, &mut resized), layout);
+
+        // Other tensors of the same sizes
+        let (mut a, mut b) = ([0u8; 3], [0u8; 5]);
+        let mut other = [(2, &mut a[..]), (4, &mut b[..])];
+        assert_ne!(Layout::new(&model, &mut other), layout);
+    }
+
+    #[test]
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    fn owned_arena() {
+        extern crate alloc;
+
+        let hello_world =
+            include_model!("../examples/models/hello_world.tflite");
+        let hello_world = Model::from_buffer(hello_world).unwrap();
+        let resolver = AllOpResolver::new();
+
+        let mut arena = SharedArena::new(alloc::vec![0; 4 * 1024]);
+        assert_eq!(arena.len(), 4 * 1024);
+
+        let mut interpreter =
+            arena.interpreter(&hello_world, &resolver).unwrap();
+        interpreter.input(0, &[1.57f32]).unwrap();
+        interpreter.invoke().unwrap();
+        assert!(interpreter.output(0).as_data::<f32>()[0] > 0.9);
+    }
+}
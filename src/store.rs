@@ -0,0 +1,1071 @@
+//! A/B model slots for over the air updates
+//!
+//! A [`ModelStore`] keeps two models in a flash-like [`Storage`]: the one
+//! that is running, and either the previous model or an update. An update
+//! is written to the other slot as a [`container`](crate::container), and
+//! becomes active on trial. Once it has run successfully it is committed.
+//! If it fails, or the device restarts before it is committed, the store
+//! rolls back to the previous model.
+//!
+//! The storage is laid out in erase blocks
+//!
+//! | Blocks    | Contents                                        |
+//! |-----------|-------------------------------------------------|
+//! | 0 and 1   | Metadata records, written to each in turn       |
+//! | 2 onwards | Slot A, then slot B, each half of the remainder |
+//!
+//! Each metadata record has a sequence number and a CRC-32, and the valid
+//! record with the highest sequence number is current. A record is written
+//! by erasing the block that does not hold the current record, so an
+//! interrupted write leaves the current record in place.
+//!
+//! ```
+//! use tfmicro::container;
+//! use tfmicro::store::{MemoryStorage, ModelStore, Slot, SlotState};
+//!
+//! #[repr(C, align(16))]
+//! struct Flash([u8; 16 * 1024]);
+//!
+//! let mut flash = Flash([0xff; 16 * 1024]);
+//! let storage = MemoryStorage::new(&mut flash.0[..], 1024).unwrap();
+//! let mut store = ModelStore::new(storage).unwrap();
+//! assert_eq!(store.active(), None);
+//!
+//! # #[cfg(any(feature = "std", feature = "alloc"))]
+//! # {
+//! let model = include_bytes!("../examples/models/hello_world.tflite");
+//! store.stage(&container::wrap(&model[..])).unwrap();
+//! assert_eq!(store.active(), Some(Slot::A));
+//! assert_eq!(store.state(), Some(SlotState::Trial));
+//!
+//! // ... build an interpreter for `store.model()` and invoke it
+//!
+//! store.commit().unwrap();
+//! assert_eq!(store.state(), Some(SlotState::Committed));
+//! # }
+//! ```
+
+use core::convert::TryInto;
+
+use crate::container::{self, Container};
+use crate::model::Model;
+use crate::Error;
+
+/// Flash-like storage for a [`ModelStore`]
+///
+/// Like NOR flash, storage is erased in blocks, and only erased bytes may
+/// be written. The contents must be readable in place, as TensorFlow reads
+/// the model from the storage without copying it.
+pub trait Storage {
+    /// The contents of the storage. Must be aligned to
+    /// [`Model::ALIGNMENT`](crate::Model::ALIGNMENT)
+    fn as_bytes(&self) -> &[u8];
+
+    /// Size of an erase block in bytes. Must be a power of two, and at least
+    /// [`Model::ALIGNMENT`](crate::Model::ALIGNMENT)
+    fn erase_size(&self) -> usize;
+
+    /// Erase `len` bytes from `offset`. Both are multiples of
+    /// [`erase_size`](Storage::erase_size). Erased bytes read as `0xff`
+    fn erase(&mut self, offset: usize, len: usize) -> Result<(), Error>;
+
+    /// Write `data` to erased bytes from `offset`
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), Error>;
+}
+
+/// Storage in a buffer in memory, for testing on the host
+///
+/// Writes to bytes that have not been erased fail, to catch writes that
+/// would corrupt real flash.
+#[derive(Debug)]
+pub struct MemoryStorage<'a> {
+    bytes: &'a mut [u8],
+    erase_size: usize,
+    write_limit: Option<usize>,
+}
+
+impl<'a> MemoryStorage<'a> {
+    /// Storage in `bytes`, with erase blocks of `erase_size` bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MisalignedBuffer` if `bytes` is not aligned to
+    /// [`Model::ALIGNMENT`](crate::Model::ALIGNMENT).
+    ///
+    /// Returns `Error::StorageError` if `erase_size` is not a power of two
+    /// of at least the alignment, or does not divide the length of `bytes`
+    pub fn new(bytes: &'a mut [u8], erase_size: usize) -> Result<Self, Error> {
+        if bytes.as_ptr() as usize & (Model::ALIGNMENT - 1) != 0 {
+            return Err(Error::MisalignedBuffer);
+        }
+        if !erase_size.is_power_of_two()
+            || erase_size < Model::ALIGNMENT
+            || bytes.len() & (erase_size - 1) != 0
+        {
+            return Err(Error::StorageError);
+        }
+
+        Ok(Self {
+            bytes,
+            erase_size,
+            write_limit: None,
+        })
+    }
+
+    /// Simulate losing power after `limit` more bytes have been written.
+    /// Writes stop at that point and fail, until the limit is cleared with
+    /// `None`
+    pub fn set_write_limit(&mut self, limit: Option<usize>) {
+        self.write_limit = limit;
+    }
+}
+
+impl Storage for MemoryStorage<'_> {
+    fn as_bytes(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn erase_size(&self) -> usize {
+        self.erase_size
+    }
+
+    fn erase(&mut self, offset: usize, len: usize) -> Result<(), Error> {
+        if (offset | len) & (self.erase_size - 1) != 0 {
+            return Err(Error::StorageError);
+        }
+        let end = offset.checked_add(len).ok_or(Error::StorageError)?;
+        for b in self.bytes.get_mut(offset..end).ok_or(Error::StorageError)? {
+            *b = 0xff;
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), Error> {
+        let end = offset.checked_add(data.len()).ok_or(Error::StorageError)?;
+        let bytes =
+            self.bytes.get_mut(offset..end).ok_or(Error::StorageError)?;
+        if bytes.iter().any(|&b| b != 0xff) {
+            return Err(Error::StorageError);
+        }
+
+        let len = match self.write_limit {
+            Some(limit) => data.len().min(limit),
+            None => data.len(),
+        };
+        bytes[..len].copy_from_slice(&data[..len]);
+
+        match &mut self.write_limit {
+            Some(limit) if len < data.len() => {
+                *limit = 0;
+                Err(Error::StorageError)
+            }
+            Some(limit) => {
+                *limit -= len;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// One of the two model slots
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Slot {
+    A,
+    B,
+}
+
+impl Slot {
+    /// The other slot
+    pub fn other(self) -> Self {
+        match self {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A,
+        }
+    }
+
+    fn to_u8(slot: Option<Self>) -> u8 {
+        match slot {
+            None => 0,
+            Some(Slot::A) => 1,
+            Some(Slot::B) => 2,
+        }
+    }
+
+    fn from_u8(n: u8) -> Option<Option<Self>> {
+        match n {
+            0 => Some(None),
+            1 => Some(Some(Slot::A)),
+            2 => Some(Some(Slot::B)),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the active slot has been committed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SlotState {
+    /// The model has run successfully
+    Committed,
+    /// The model has not yet run successfully. The store rolls back to the
+    /// previous model if it is not committed before the next restart but
+    /// one
+    Trial,
+}
+
+/// State of the active slot, as stored
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Committed,
+    /// Staged, and not yet opened after a restart
+    Pending,
+    /// Opened after a restart, and not yet committed
+    Trying,
+}
+
+/// A metadata record
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Record {
+    sequence: u32,
+    generation: u32,
+    active: Option<Slot>,
+    previous: Option<Slot>,
+    state: State,
+}
+
+const RECORD_MAGIC: [u8; 4] = *b"TFMS";
+const RECORD_LEN: usize = 32;
+
+impl Record {
+    fn encode(&self) -> [u8; RECORD_LEN] {
+        let mut bytes = [0; RECORD_LEN];
+        bytes[0..4].copy_from_slice(&RECORD_MAGIC);
+        bytes[4..8].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.generation.to_le_bytes());
+        bytes[12] = Slot::to_u8(self.active);
+        bytes[13] = Slot::to_u8(self.previous);
+        bytes[14] = self.state as u8;
+        let crc = container::crc32(&bytes[..RECORD_LEN - 4]);
+        bytes[RECORD_LEN - 4..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..RECORD_LEN)?;
+        let u32_at = |pos: usize| {
+            u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+        };
+        if bytes[0..4] != RECORD_MAGIC
+            || container::crc32(&bytes[..RECORD_LEN - 4])
+                != u32_at(RECORD_LEN - 4)
+        {
+            return None;
+        }
+
+        Some(Self {
+            sequence: u32_at(4),
+            generation: u32_at(8),
+            active: Slot::from_u8(bytes[12])?,
+            previous: Slot::from_u8(bytes[13])?,
+            state: match bytes[14] {
+                0 => State::Committed,
+                1 => State::Pending,
+                2 => State::Trying,
+                _ => return None,
+            },
+        })
+    }
+}
+
+/// Two model slots in a [`Storage`], with an atomic commit and rollback
+///
+/// See the [module documentation](crate::store).
+pub struct ModelStore<S: Storage> {
+    storage: S,
+    slot_len: usize,
+    record: Record,
+    #[cfg(feature = "ed25519")]
+    key: Option<[u8; 32]>,
+}
+
+impl<S: Storage> ModelStore<S> {
+    /// Open the model store in `storage`
+    ///
+    /// Opening the store counts as a restart. A staged model is put on
+    /// trial, and a model that was already on trial is rolled back. A slot
+    /// that fails its checksums is also rolled back.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MisalignedBuffer` if the storage is not aligned.
+    ///
+    /// Returns `Error::StorageError` if the erase size is not a power of two
+    /// of at least [`Model::ALIGNMENT`](crate::Model::ALIGNMENT), the
+    /// storage is smaller than four erase blocks, or writing the metadata
+    /// fails
+    pub fn new(storage: S) -> Result<Self, Error> {
+        Self::open(storage, None)
+    }
+
+    /// Open the model store in `storage`, as for
+    /// [`new`](ModelStore::new), only accepting models signed with the
+    /// Ed25519 public key `key` (`ed25519` feature)
+    #[cfg(feature = "ed25519")]
+    pub fn with_key(storage: S, key: &[u8; 32]) -> Result<Self, Error> {
+        Self::open(storage, Some(key))
+    }
+
+    fn open(storage: S, _key: Option<&[u8; 32]>) -> Result<Self, Error> {
+        let bytes = storage.as_bytes();
+        let erase_size = storage.erase_size();
+        if bytes.as_ptr() as usize & (Model::ALIGNMENT - 1) != 0 {
+            return Err(Error::MisalignedBuffer);
+        }
+        if !erase_size.is_power_of_two()
+            || erase_size < Model::ALIGNMENT
+            || bytes.len() / erase_size < 4
+        {
+            return Err(Error::StorageError);
+        }
+        let slot_len = (bytes.len() / erase_size - 2) / 2 * erase_size;
+
+        let record = (0..2)
+            .filter_map(|n| Record::decode(&bytes[n * erase_size..]))
+            .max_by_key(|record| record.sequence)
+            .unwrap_or(Record {
+                sequence: 0,
+                generation: 0,
+                active: None,
+                previous: None,
+                state: State::Committed,
+            });
+
+        let mut store = Self {
+            storage,
+            slot_len,
+            record,
+            #[cfg(feature = "ed25519")]
+            key: _key.copied(),
+        };
+
+        match store.record.state {
+            State::Committed => {}
+            State::Pending => store.write_record(Record {
+                state: State::Trying,
+                ..store.record
+            })?,
+            State::Trying => store.rollback()?,
+        }
+        if let Some(active) = store.record.active {
+            if store.check_slot(active).is_err() {
+                store.rollback()?;
+            }
+        }
+
+        Ok(store)
+    }
+
+    /// The storage
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Close the store, and return the storage
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /// Size of each slot in bytes, including the container header
+    pub fn slot_len(&self) -> usize {
+        self.slot_len
+    }
+
+    /// The active slot, if a model has been staged
+    pub fn active(&self) -> Option<Slot> {
+        self.record.active
+    }
+
+    /// Whether the active slot has been committed
+    pub fn state(&self) -> Option<SlotState> {
+        self.record.active?;
+
+        match self.record.state {
+            State::Committed => Some(SlotState::Committed),
+            State::Pending | State::Trying => Some(SlotState::Trial),
+        }
+    }
+
+    /// The slot that the store rolls back to
+    pub fn previous(&self) -> Option<Slot> {
+        self.record.previous
+    }
+
+    /// A number that changes whenever the active slot changes. An
+    /// interpreter built for the active model must be rebuilt when this
+    /// changes
+    pub fn generation(&self) -> u32 {
+        self.record.generation
+    }
+
+    /// The bytes of `slot`
+    fn slot(&self, slot: Slot) -> &[u8] {
+        let erase_size = self.storage.erase_size();
+        let start = match slot {
+            Slot::A => 2 * erase_size,
+            Slot::B => 2 * erase_size + self.slot_len,
+        };
+
+        &self.storage.as_bytes()[start..start + self.slot_len]
+    }
+
+    /// Check the container in `slot`
+    fn check_slot(&self, slot: Slot) -> Result<Container<'_>, Error> {
+        let container = Container::parse(self.slot(slot))?;
+        self.check(&container)?;
+
+        Ok(container)
+    }
+
+    /// Check `container`, and its signature if there is a key
+    fn check(&self, container: &Container) -> Result<(), Error> {
+        #[cfg(feature = "ed25519")]
+        {
+            if let Some(key) = &self.key {
+                return container.verify(key);
+            }
+        }
+
+        container.check()
+    }
+
+    /// The `.tflite` model in the active slot
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoActiveModel` if no model has been staged
+    pub fn model_bytes(&self) -> Result<&[u8], Error> {
+        let active = self.record.active.ok_or(Error::NoActiveModel)?;
+
+        Ok(Container::parse(self.slot(active))?.model())
+    }
+
+    /// The model in the active slot
+    ///
+    /// The model is read in place from the storage, so the store cannot be
+    /// changed while an interpreter for the model exists. Drop the
+    /// interpreter before staging, committing or rolling back, and build it
+    /// again afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoActiveModel` if no model has been staged.
+    ///
+    /// Otherwise, returns the same errors as
+    /// [`Model::from_buffer`](crate::Model::from_buffer)
+    pub fn model(&self) -> Result<&Model, Error> {
+        Model::from_buffer(self.model_bytes()?)
+    }
+
+    /// Write the [`container`](crate::container) `bytes` to the inactive
+    /// slot, and make it the active slot on trial
+    ///
+    /// If the active slot is itself on trial, it is replaced, so that the
+    /// last committed model is kept to roll back to.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UpdateTooLarge` if the container is larger than a
+    /// slot.
+    ///
+    /// Returns `Error::StorageError` if the storage fails.
+    ///
+    /// Otherwise, returns the same errors as
+    /// [`Container::check`](crate::container::Container::check), or
+    /// [`Container::verify`](crate::container::Container::verify) if the
+    /// store has a key. The container is checked before the slot is
+    /// erased, and again after it has been written.
+    pub fn stage(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let container = Container::parse(bytes)?;
+        self.check(&container)?;
+
+        let len = container::HEADER_LEN + container.model_len();
+        if len > self.slot_len {
+            return Err(Error::UpdateTooLarge);
+        }
+
+        let (target, previous) = match (self.record.active, self.record.state) {
+            (Some(active), State::Committed) => (active.other(), Some(active)),
+            (Some(active), _) => (active, self.record.previous),
+            (None, _) => (Slot::A, None),
+        };
+
+        // Write the slot
+        let erase_size = self.storage.erase_size();
+        let offset = self.slot(target).as_ptr() as usize
+            - self.storage.as_bytes().as_ptr() as usize;
+        let erase_len = (len + erase_size - 1) & !(erase_size - 1);
+        self.storage.erase(offset, erase_len)?;
+        self.storage.write(offset, &bytes[..len])?;
+        self.check_slot(target)?;
+
+        self.write_record(Record {
+            generation: self.record.generation.wrapping_add(1),
+            active: Some(target),
+            previous,
+            state: State::Pending,
+            ..self.record
+        })
+    }
+
+    /// Commit the active slot, after the model has run successfully
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::StorageError` if the storage fails
+    pub fn commit(&mut self) -> Result<(), Error> {
+        if self.record.state == State::Committed {
+            return Ok(());
+        }
+
+        self.write_record(Record {
+            state: State::Committed,
+            ..self.record
+        })
+    }
+
+    /// Make the previous slot active, if it holds a valid model
+    ///
+    /// If not, no slot is active.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::StorageError` if the storage fails
+    pub fn rollback(&mut self) -> Result<(), Error> {
+        let active = self
+            .record
+            .previous
+            .filter(|&previous| self.check_slot(previous).is_ok());
+
+        self.write_record(Record {
+            generation: self.record.generation.wrapping_add(1),
+            active,
+            previous: None,
+            state: State::Committed,
+            ..self.record
+        })
+    }
+
+    /// Write a new metadata record, replacing the current one
+    fn write_record(&mut self, record: Record) -> Result<(), Error> {
+        let record = Record {
+            sequence: self.record.sequence.wrapping_add(1),
+            ..record
+        };
+
+        let erase_size = self.storage.erase_size();
+        let offset = (record.sequence as usize & 1) * erase_size;
+        self.storage.erase(offset, erase_size)?;
+        self.storage.write(offset, &record.encode())?;
+
+        self.record = record;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+mod slot_interpreter {
+    use super::*;
+    use crate::micro_op_resolver::{AllOpResolver, OpResolverRepr};
+    use crate::{OwnedInterpreter, OwnedModel};
+
+    /// An interpreter for the active model in a [`ModelStore`], which is
+    /// rebuilt whenever the active slot changes (`std` feature)
+    ///
+    /// A model on trial is committed after its first successful invoke, and
+    /// rolled back if invoking it fails. If no slot is active after a
+    /// rollback, the interpreter has no model until another is staged.
+    ///
+    /// The active model is copied from the store into an
+    /// [`OwnedModel`](crate::OwnedModel) on the heap, rather than read in
+    /// place as by [`ModelStore::model`](crate::store::ModelStore::model).
+    /// That would borrow the store for as long as the interpreter exists,
+    /// so the store could not be committed or rolled back by the
+    /// interpreter. This needs heap memory for a second copy of the model.
+    pub struct SlotInterpreter<S: Storage, R = AllOpResolver>
+    where
+        R: OpResolverRepr + 'static,
+    {
+        store: ModelStore<S>,
+        interpreter: OwnedInterpreter<R>,
+        generation: u32,
+    }
+
+    impl<S: Storage, R: OpResolverRepr + 'static> SlotInterpreter<S, R> {
+        /// Create an interpreter for the active model in `store`, with a
+        /// tensor arena of `arena_size` bytes
+        ///
+        /// # Errors
+        ///
+        /// Returns `Error::NoActiveModel` if no model has been staged.
+        ///
+        /// Otherwise, returns the same errors as
+        /// [`OwnedInterpreter::new`](crate::OwnedInterpreter::new)
+        pub fn new(
+            store: ModelStore<S>,
+            resolver: R,
+            arena_size: usize,
+        ) -> Result<Self, Error> {
+            let model = OwnedModel::from_bytes(store.model_bytes()?)?;
+
+            Ok(Self {
+                generation: store.generation(),
+                interpreter: OwnedInterpreter::new(
+                    model, resolver, arena_size,
+                )?,
+                store,
+            })
+        }
+
+        /// The model store
+        pub fn store(&self) -> &ModelStore<S> {
+            &self.store
+        }
+
+        /// The interpreter for the active model, which has no model if no
+        /// slot is active
+        pub fn interpreter(&self) -> &OwnedInterpreter<R> {
+            &self.interpreter
+        }
+
+        /// The interpreter for the active model, to set its inputs
+        pub fn interpreter_mut(&mut self) -> &mut OwnedInterpreter<R> {
+            &mut self.interpreter
+        }
+
+        /// Stage an update as for
+        /// [`ModelStore::stage`](crate::store::ModelStore::stage), and
+        /// rebuild the interpreter for it
+        ///
+        /// If an interpreter cannot be built for the update, the store is
+        /// rolled back.
+        ///
+        /// # Errors
+        ///
+        /// As for [`ModelStore::stage`](crate::store::ModelStore::stage),
+        /// and [`OwnedInterpreter::new`](crate::OwnedInterpreter::new)
+        pub fn stage(&mut self, bytes: &[u8]) -> Result<(), Error> {
+            self.store.stage(bytes)?;
+
+            if let Err(e) = self.reload() {
+                self.store.rollback()?;
+                self.reload()?;
+                return Err(e);
+            }
+            Ok(())
+        }
+
+        /// Invoke the interpreter. If the model is on trial, it is
+        /// committed if it runs successfully, and rolled back otherwise
+        ///
+        /// # Errors
+        ///
+        /// Returns `Error::NoActiveModel` if no slot is active.
+        ///
+        /// Returns `Error::InvokeFailed` if the model failed to run.
+        ///
+        /// Returns `Error::StorageError` if committing or rolling back
+        /// failed
+        pub fn invoke(&mut self) -> Result<(), Error> {
+            if !self.interpreter.has_model() {
+                return Err(Error::NoActiveModel);
+            }
+            let trial = self.store.state() == Some(SlotState::Trial);
+
+            match self.interpreter.invoke() {
+                Ok(()) if trial => self.store.commit(),
+                Ok(()) => Ok(()),
+                Err(_) => {
+                    // If there is no previous model, the interpreter is left
+                    // without one
+                    if trial {
+                        self.store.rollback()?;
+                        self.reload()?;
+                    }
+                    Err(Error::InvokeFailed)
+                }
+            }
+        }
+
+        /// Roll the store back as for
+        /// [`ModelStore::rollback`](crate::store::ModelStore::rollback),
+        /// and rebuild the interpreter for the previous model
+        ///
+        /// # Errors
+        ///
+        /// Returns `Error::NoActiveModel` if there is no previous model
+        pub fn rollback(&mut self) -> Result<(), Error> {
+            self.store.rollback()?;
+            self.reload()?;
+
+            if self.interpreter.has_model() {
+                Ok(())
+            } else {
+                Err(Error::NoActiveModel)
+            }
+        }
+
+        /// Close the store, and return it
+        pub fn into_store(self) -> ModelStore<S> {
+            self.store
+        }
+
+        /// Rebuild the interpreter if the active slot has changed, or
+        /// unload its model if no slot is active
+        fn reload(&mut self) -> Result<(), Error> {
+            if self.store.generation() == self.generation {
+                return Ok(());
+            }
+
+            match self.store.model_bytes() {
+                Ok(bytes) => {
+                    let model = OwnedModel::from_bytes(bytes)?;
+                    self.interpreter.replace_model(model)?;
+                }
+                Err(Error::NoActiveModel) => self.interpreter.unload_model(),
+                Err(e) => return Err(e),
+            }
+            self.generation = self.store.generation();
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub use slot_interpreter::SlotInterpreter;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLASH_LEN: usize = 16 * 1024;
+    const ERASE_SIZE: usize = 1024;
+
+    #[repr(C, align(16))]
+    struct Flash([u8; FLASH_LEN]);
+
+    #[test]
+    fn record_round_trip() {
+        let record = Record {
+            sequence: 7,
+            generation: 3,
+            active: Some(Slot::B),
+            previous: Some(Slot::A),
+            state: State::Trying,
+        };
+        let mut bytes = record.encode();
+        assert_eq!(Record::decode(&bytes), Some(record));
+
+        bytes[12] = 1;
+        assert_eq!(Record::decode(&bytes), None);
+    }
+
+    #[test]
+    fn memory_storage() {
+        let mut flash = Flash([0; FLASH_LEN]);
+        let mut storage = MemoryStorage::new(&mut flash.0[..], 1024).unwrap();
+
+        // Bytes must be erased before they are written
+        assert_eq!(storage.write(0, &[1, 2]), Err(Error::StorageError));
+        storage.erase(0, 1024).unwrap();
+        storage.write(0, &[1, 2]).unwrap();
+        assert_eq!(&storage.as_bytes()[..3], &[1, 2, 0xff]);
+        assert_eq!(storage.erase(1, 1024), Err(Error::StorageError));
+
+        // Power is lost part way through a write
+        storage.set_write_limit(Some(3));
+        assert_eq!(storage.write(2, &[3, 4, 5, 6]), Err(Error::StorageError));
+        assert_eq!(&storage.as_bytes()[..7], &[1, 2, 3, 4, 5, 0xff, 0xff]);
+
+        assert_eq!(
+            MemoryStorage::new(&mut flash.0[1..1025], 1024).unwrap_err(),
+            Error::MisalignedBuffer
+        );
+    }
+
+    #[test]
+    fn too_small() {
+        let mut flash = Flash([0; FLASH_LEN]);
+        let storage = MemoryStorage::new(&mut flash.0[..3072], 1024).unwrap();
+
+        assert_eq!(ModelStore::new(storage).err(), Some(Error::StorageError));
+    }
+
+    #[test]
+    fn empty() {
+        let mut flash = Flash([0; FLASH_LEN]);
+        let storage = MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+        let mut store = ModelStore::new(storage).unwrap();
+
+        assert_eq!(store.slot_len(), 7 * ERASE_SIZE);
+        assert_eq!(store.active(), None);
+        assert_eq!(store.state(), None);
+        assert_eq!(store.model_bytes(), Err(Error::NoActiveModel));
+
+        assert_eq!(
+            store.stage(&[0; 256]).unwrap_err(),
+            Error::InvalidContainer
+        );
+        assert_eq!(store.active(), None);
+    }
+
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    mod update {
+        extern crate alloc;
+
+        use super::*;
+        use alloc::vec::Vec;
+
+        const MODEL: &[u8] =
+            include_bytes!("../examples/models/hello_world.tflite");
+
+        /// A container for a different model, with the same header
+        fn update() -> Vec<u8> {
+            let mut model = MODEL.to_vec();
+            model.extend_from_slice(&[0; 16]);
+            container::wrap(&model)
+        }
+
+        /// Simulate a restart
+        fn restart<S: Storage>(store: ModelStore<S>) -> ModelStore<S> {
+            ModelStore::new(store.into_storage()).unwrap()
+        }
+
+        #[test]
+        fn commit() {
+            let mut flash = Flash([0; FLASH_LEN]);
+            let storage =
+                MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+            let mut store = ModelStore::new(storage).unwrap();
+
+            store.stage(&container::wrap(MODEL)).unwrap();
+            assert_eq!(store.active(), Some(Slot::A));
+            assert_eq!(store.previous(), None);
+            assert_eq!(store.state(), Some(SlotState::Trial));
+            assert_eq!(store.model_bytes(), Ok(MODEL));
+            let generation = store.generation();
+
+            store.commit().unwrap();
+            assert_eq!(store.state(), Some(SlotState::Committed));
+            assert_eq!(store.generation(), generation);
+
+            let mut store = restart(store);
+            assert_eq!(store.active(), Some(Slot::A));
+            assert_eq!(store.state(), Some(SlotState::Committed));
+
+            // The update goes to the other slot
+            store.stage(&update()).unwrap();
+            assert_eq!(store.active(), Some(Slot::B));
+            assert_eq!(store.previous(), Some(Slot::A));
+            assert_eq!(store.model_bytes().unwrap().len(), MODEL.len() + 16);
+            assert_ne!(store.generation(), generation);
+        }
+
+        #[test]
+        fn rollback_after_restarts() {
+            let mut flash = Flash([0; FLASH_LEN]);
+            let storage =
+                MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+            let mut store = ModelStore::new(storage).unwrap();
+            store.stage(&container::wrap(MODEL)).unwrap();
+            store.commit().unwrap();
+            store.stage(&update()).unwrap();
+
+            // The update is tried once after a restart
+            let store = restart(store);
+            assert_eq!(store.active(), Some(Slot::B));
+            assert_eq!(store.state(), Some(SlotState::Trial));
+
+            // It was not committed, so it is rolled back
+            let store = restart(store);
+            assert_eq!(store.active(), Some(Slot::A));
+            assert_eq!(store.state(), Some(SlotState::Committed));
+            assert_eq!(store.model_bytes(), Ok(MODEL));
+        }
+
+        #[test]
+        fn replace_trial() {
+            let mut flash = Flash([0; FLASH_LEN]);
+            let storage =
+                MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+            let mut store = ModelStore::new(storage).unwrap();
+            store.stage(&container::wrap(MODEL)).unwrap();
+            store.commit().unwrap();
+
+            // A second update replaces the first, keeping the committed
+            // model to roll back to
+            store.stage(&update()).unwrap();
+            store.stage(&update()).unwrap();
+            assert_eq!(store.active(), Some(Slot::B));
+            assert_eq!(store.previous(), Some(Slot::A));
+
+            store.rollback().unwrap();
+            assert_eq!(store.active(), Some(Slot::A));
+            assert_eq!(store.model_bytes(), Ok(MODEL));
+        }
+
+        #[test]
+        fn power_loss() {
+            let mut flash = Flash([0; FLASH_LEN]);
+            let storage =
+                MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+            let mut store = ModelStore::new(storage).unwrap();
+            store.stage(&container::wrap(MODEL)).unwrap();
+            store.commit().unwrap();
+
+            // Power is lost at every point while an update is staged. The
+            // store is always left with either the old or the new model
+            let update = update();
+            for limit in (0..update.len() + 64).step_by(97) {
+                let mut storage = store.into_storage();
+                storage.set_write_limit(Some(limit));
+                let mut staging = ModelStore::new(storage).unwrap();
+                let staged = staging.stage(&update).is_ok();
+
+                let mut storage = staging.into_storage();
+                storage.set_write_limit(None);
+                store = ModelStore::new(storage).unwrap();
+                if staged {
+                    assert_eq!(store.active(), Some(Slot::B));
+                    store.rollback().unwrap();
+                }
+                assert_eq!(store.active(), Some(Slot::A));
+                assert_eq!(store.state(), Some(SlotState::Committed));
+                assert_eq!(store.model_bytes(), Ok(MODEL));
+            }
+        }
+
+        #[test]
+        fn corrupted_slot() {
+            let mut flash = Flash([0; FLASH_LEN]);
+            let storage =
+                MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+            {
+                let mut store = ModelStore::new(storage).unwrap();
+                store.stage(&container::wrap(MODEL)).unwrap();
+                store.commit().unwrap();
+            }
+
+            // Flip a bit of the model in slot A
+            flash.0[2 * ERASE_SIZE + container::HEADER_LEN + 100] ^= 1;
+
+            let storage =
+                MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+            let store = ModelStore::new(storage).unwrap();
+            assert_eq!(store.active(), None);
+        }
+
+        #[test]
+        fn too_large() {
+            let mut flash = Flash([0; FLASH_LEN]);
+            let storage =
+                MemoryStorage::new(&mut flash.0[..6 * ERASE_SIZE], ERASE_SIZE)
+                    .unwrap();
+            let mut store = ModelStore::new(storage).unwrap();
+
+            assert_eq!(
+                store.stage(&container::wrap(MODEL)).unwrap_err(),
+                Error::UpdateTooLarge
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    mod slot_interpreter {
+        use super::*;
+        use crate::AllOpResolver;
+
+        const MODEL: &[u8] =
+            include_bytes!("../examples/models/hello_world.tflite");
+
+        #[test]
+        fn commit_after_invoke() {
+            let mut flash = Flash([0; FLASH_LEN]);
+            let storage =
+                MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+            let mut store = ModelStore::new(storage).unwrap();
+            store.stage(&container::wrap(MODEL)).unwrap();
+
+            let mut interpreter =
+                SlotInterpreter::new(store, AllOpResolver::new(), 4 * 1024)
+                    .unwrap();
+            assert_eq!(interpreter.store().state(), Some(SlotState::Trial));
+
+            interpreter.interpreter_mut().input(0, &[1.57f32]).unwrap();
+            interpreter.invoke().unwrap();
+            assert!(
+                interpreter.interpreter().output(0).as_data::<f32>()[0] > 0.9
+            );
+            assert_eq!(interpreter.store().state(), Some(SlotState::Committed));
+        }
+
+        #[test]
+        fn rollback_unusable_update() {
+            let mut flash = Flash([0; FLASH_LEN]);
+            let storage =
+                MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+            let mut store = ModelStore::new(storage).unwrap();
+            store.stage(&container::wrap(MODEL)).unwrap();
+            store.commit().unwrap();
+
+            let mut interpreter =
+                SlotInterpreter::new(store, AllOpResolver::new(), 4 * 1024)
+                    .unwrap();
+            let generation = interpreter.store().generation();
+
+            // The update is a valid container, but not a valid model
+            let update = container::wrap(&[0; 256]);
+            assert_eq!(interpreter.stage(&update), Err(Error::InvalidModel));
+            assert_eq!(interpreter.store().active(), Some(Slot::A));
+            assert_ne!(interpreter.store().generation(), generation);
+
+            // The interpreter was rebuilt for the previous model
+            interpreter.interpreter_mut().input(0, &[1.57f32]).unwrap();
+            interpreter.invoke().unwrap();
+            assert!(
+                interpreter.interpreter().output(0).as_data::<f32>()[0] > 0.9
+            );
+        }
+
+        #[test]
+        fn no_previous_model() {
+            let mut flash = Flash([0; FLASH_LEN]);
+            let storage =
+                MemoryStorage::new(&mut flash.0[..], ERASE_SIZE).unwrap();
+            let mut store = ModelStore::new(storage).unwrap();
+            store.stage(&container::wrap(MODEL)).unwrap();
+
+            let mut interpreter =
+                SlotInterpreter::new(store, AllOpResolver::new(), 4 * 1024)
+                    .unwrap();
+
+            // Rolling back the first model leaves the interpreter without
+            // one, and invoking it fails
+            assert_eq!(interpreter.rollback(), Err(Error::NoActiveModel));
+            assert!(!interpreter.interpreter().has_model());
+            assert_eq!(interpreter.invoke(), Err(Error::NoActiveModel));
+
+            // The error from an unusable update is not masked by there
+            // being no model to roll back to
+            let update = container::wrap(&[0; 256]);
+            assert_eq!(interpreter.stage(&update), Err(Error::InvalidModel));
+            assert_eq!(interpreter.store().active(), None);
+
+            interpreter.stage(&container::wrap(MODEL)).unwrap();
+            interpreter.interpreter_mut().input(0, &[1.57f32]).unwrap();
+            interpreter.invoke().unwrap();
+            assert_eq!(interpreter.store().state(), Some(SlotState::Committed));
+        }
+    }
+}
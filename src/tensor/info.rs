@@ -16,6 +16,16 @@ pub struct TensorInfo<'a> {
     pub name: &'a str,
     pub element_type: ElementType,
     pub dims: &'a [i32],
+    /// Affine quantization of the tensor, if any
+    pub quantization: Option<QuantizationParams>,
+}
+
+/// Parameters that map quantized values `q` to real values, where
+/// `real = scale * (q - zero_point)`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct QuantizationParams {
+    pub scale: f32,
+    pub zero_point: i32,
 }
 
 impl fmt::Debug for TensorInfo<'_> {
@@ -24,6 +34,7 @@ impl fmt::Debug for TensorInfo<'_> {
             .field("name", &self.name)
             .field("element_type", &self.element_type)
             .field("dims", &self.dims)
+            .field("quantization", &self.quantization)
             .finish()
     }
 }
@@ -47,6 +58,16 @@ impl<'a> TryFrom<&'a bindings::TfLiteTensor> for TensorInfo<'a> {
             .try_into()
             .or(Err(Error::ElementTypeUnimplemented))?;
 
+        // A scale of zero indicates that the tensor is not quantized
+        let quantization = if t.params.scale != 0. {
+            Some(QuantizationParams {
+                scale: t.params.scale,
+                zero_point: t.params.zero_point,
+            })
+        } else {
+            None
+        };
+
         Ok(Self {
             name,
             element_type,
@@ -54,6 +75,7 @@ impl<'a> TryFrom<&'a bindings::TfLiteTensor> for TensorInfo<'a> {
                 let dims = &*t.dims;
                 dims.data.as_slice(dims.size as usize)
             },
+            quantization,
         })
     }
 }
@@ -8,7 +8,7 @@ use ordered_float::NotNan;
 use crate::bindings;
 
 mod info;
-pub use info::TensorInfo;
+pub use info::{QuantizationParams, TensorInfo};
 
 /// A TensorFlow Tensor
 #[repr(transparent)]
@@ -20,6 +20,7 @@ pub struct Tensor(bindings::TfLiteTensor);
 pub enum ElementType {
     Float32,
     UInt8,
+    Int8,
     Int32,
 }
 impl TryFrom<bindings::TfLiteType> for ElementType {
@@ -31,6 +32,7 @@ impl TryFrom<bindings::TfLiteType> for ElementType {
         match status {
             bindings::TfLiteType::kTfLiteFloat32 => Ok(Float32),
             bindings::TfLiteType::kTfLiteUInt8 => Ok(UInt8),
+            bindings::TfLiteType::kTfLiteInt8 => Ok(Int8),
             bindings::TfLiteType::kTfLiteInt32 => Ok(Int32),
             t => Err(t),
         }
@@ -57,6 +59,11 @@ impl ElemTypeOf for u8 {
         ElementType::UInt8
     }
 }
+impl ElemTypeOf for i8 {
+    fn elem_type_of() -> ElementType {
+        ElementType::Int8
+    }
+}
 impl ElemTypeOf for i32 {
     fn elem_type_of() -> ElementType {
         ElementType::Int32
@@ -99,6 +106,11 @@ impl Tensor {
         &self.0
     }
 
+    /// Whether this tensor holds state that persists between invocations
+    pub(crate) fn is_variable(&self) -> bool {
+        self.0.is_variable
+    }
+
     /// Extracts the tensor's data as a flat slice.
     ///
     /// Call the [info](#method.info) method to check the dimensionality of
@@ -156,4 +168,21 @@ impl Tensor {
             )
         }
     }
+    /// Returns the tensor's data as bytes, whatever its element type
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(
+                self.0.data.raw_const as *const u8,
+                self.0.bytes,
+            )
+        }
+    }
+
+    /// Returns the tensor's data as mutable bytes, whatever its element
+    /// type
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            slice::from_raw_parts_mut(self.0.data.raw as *mut u8, self.0.bytes)
+        }
+    }
 }
@@ -0,0 +1,290 @@
+//! Loading 16-bit PCM audio for the audio frontend
+//!
+//! Reads WAV files, and the raw little-endian PCM `.data` files used in
+//! `examples/models`. Audio is mixed down to a single channel, and can be
+//! resampled to the sample rate expected by the frontend.
+//!
+//! Requires the `std` feature.
+//!
+//! ```no_run
+//! # use tfmicro::wav::Wav;
+//! let wav = Wav::open("yes.wav").unwrap();
+//!
+//! // The default frontend configuration expects 16kHz audio
+//! let samples = wav.resample(16_000);
+//! ```
+
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::vec::Vec;
+
+use crate::Error;
+
+/// Single channel 16-bit PCM audio
+#[derive(Clone, PartialEq, Debug)]
+pub struct Wav {
+    /// Sample rate, in Hz
+    pub sample_rate: u32,
+    /// Samples, mixed down to a single channel
+    pub samples: Vec<i16>,
+}
+
+impl Wav {
+    /// Read a WAV file from `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file cannot be read. If the file is
+    /// not a 16-bit PCM WAV file, the error kind is `InvalidData`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+
+        Self::from_bytes(&bytes).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e))
+        })
+    }
+
+    /// Parse a WAV file from `bytes`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidWav` if `bytes` is not a valid 16-bit PCM WAV
+    /// file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 12
+            || &bytes[0..4] != b"RIFF"
+            || &bytes[8..12] != b"WAVE"
+        {
+            return Err(Error::InvalidWav);
+        }
+
+        let mut format = None;
+        let mut data = None;
+
+        // Iterate over chunks, each of which is padded to an even length
+        let mut chunks = &bytes[12..];
+        while chunks.len() >= 8 {
+            let id = &chunks[0..4];
+            let len = u32_le(&chunks[4..8]) as usize;
+            // The length may be up to 4GiB, which overflows on 32-bit
+            // targets
+            let end = len.checked_add(8).ok_or(Error::InvalidWav)?;
+            let body = chunks.get(8..end).ok_or(Error::InvalidWav)?;
+
+            match id {
+                b"fmt " => format = Some(Format::parse(body)?),
+                b"data" => data = Some(body),
+                _ => {}
+            }
+
+            chunks = chunks.get(end + len % 2..).unwrap_or(&[]);
+        }
+
+        let (format, data) = match (format, data) {
+            (Some(format), Some(data)) => (format, data),
+            _ => return Err(Error::InvalidWav),
+        };
+
+        // Mix down each frame to a single channel
+        let channels = format.channels as usize;
+        let samples = data
+            .chunks_exact(2 * channels)
+            .map(|frame| {
+                let sum: i32 = frame
+                    .chunks_exact(2)
+                    .map(|s| i16::from_le_bytes([s[0], s[1]]) as i32)
+                    .sum();
+                (sum / channels as i32) as i16
+            })
+            .collect();
+
+        Ok(Self {
+            sample_rate: format.sample_rate,
+            samples,
+        })
+    }
+
+    /// Interpret `bytes` as raw single channel 16-bit little-endian PCM
+    /// sampled at `sample_rate`, like the `.data` files in
+    /// `examples/models`. Any trailing odd byte is ignored
+    pub fn from_raw_pcm(bytes: &[u8], sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            samples: bytes
+                .chunks_exact(2)
+                .map(|c| i16::from_le_bytes([c[0], c[1]]))
+                .collect(),
+        }
+    }
+
+    /// Returns the samples resampled to `sample_rate`, by linear
+    /// interpolation
+    ///
+    /// No anti-aliasing filter is applied when downsampling, so the audio
+    /// should not contain significant energy above the new Nyquist
+    /// frequency.
+    pub fn resample(&self, sample_rate: u32) -> Vec<i16> {
+        if sample_rate == self.sample_rate || self.samples.is_empty() {
+            return self.samples.clone();
+        }
+
+        let len = (self.samples.len() as u64 * sample_rate as u64
+            / self.sample_rate as u64) as usize;
+        let step = self.sample_rate as f64 / sample_rate as f64;
+        let last = self.samples.len() - 1;
+
+        (0..len)
+            .map(|i| {
+                let position = i as f64 * step;
+                let index = (position as usize).min(last);
+                let next = (index + 1).min(last);
+                let fraction = position - index as f64;
+
+                let a = self.samples[index] as f64;
+                let b = self.samples[next] as f64;
+                (a + (b - a) * fraction).round() as i16
+            })
+            .collect()
+    }
+}
+
+/// The fields of a `fmt ` chunk that we need
+struct Format {
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl Format {
+    fn parse(chunk: &[u8]) -> Result<Self, Error> {
+        if chunk.len() < 16 {
+            return Err(Error::InvalidWav);
+        }
+
+        let audio_format = u16_le(&chunk[0..2]);
+        let channels = u16_le(&chunk[2..4]);
+        let sample_rate = u32_le(&chunk[4..8]);
+        let bits_per_sample = u16_le(&chunk[14..16]);
+
+        // PCM, or WAVE_FORMAT_EXTENSIBLE with a PCM subformat
+        let pcm = match audio_format {
+            1 => true,
+            0xfffe => chunk.len() >= 26 && u16_le(&chunk[24..26]) == 1,
+            _ => false,
+        };
+
+        if pcm && bits_per_sample == 16 && channels > 0 && sample_rate > 0 {
+            Ok(Self {
+                channels,
+                sample_rate,
+            })
+        } else {
+            Err(Error::InvalidWav)
+        }
+    }
+}
+
+fn u16_le(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes(bytes.try_into().unwrap())
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes.try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a 16-bit PCM WAV file
+    fn wav_bytes(channels: u16, sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+        let data_len = samples.len() as u32 * 2;
+        let block_align = channels * 2;
+
+        let mut bytes = Vec::new();
+        bytes.extend(b"RIFF");
+        bytes.extend(&(36 + data_len).to_le_bytes());
+        bytes.extend(b"WAVE");
+
+        bytes.extend(b"fmt ");
+        bytes.extend(&16u32.to_le_bytes());
+        bytes.extend(&1u16.to_le_bytes());
+        bytes.extend(&channels.to_le_bytes());
+        bytes.extend(&sample_rate.to_le_bytes());
+        bytes.extend(&(sample_rate * block_align as u32).to_le_bytes());
+        bytes.extend(&block_align.to_le_bytes());
+        bytes.extend(&16u16.to_le_bytes());
+
+        // An unknown chunk of odd length, which must be skipped
+        bytes.extend(b"LIST");
+        bytes.extend(&3u32.to_le_bytes());
+        bytes.extend(&[0, 0, 0, 0]);
+
+        bytes.extend(b"data");
+        bytes.extend(&data_len.to_le_bytes());
+        for s in samples {
+            bytes.extend(&s.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn mono() {
+        let bytes = wav_bytes(1, 16_000, &[0, 1, -1, i16::MAX, i16::MIN]);
+        let wav = Wav::from_bytes(&bytes).unwrap();
+
+        assert_eq!(wav.sample_rate, 16_000);
+        assert_eq!(wav.samples, [0, 1, -1, i16::MAX, i16::MIN]);
+    }
+
+    #[test]
+    fn stereo_is_mixed_down() {
+        let bytes = wav_bytes(2, 8_000, &[100, 300, -100, -300]);
+        let wav = Wav::from_bytes(&bytes).unwrap();
+
+        assert_eq!(wav.samples, [200, -200]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(Wav::from_bytes(b"RIFF"), Err(Error::InvalidWav));
+
+        // 8-bit audio is not supported
+        let mut bytes = wav_bytes(1, 16_000, &[0]);
+        bytes[34] = 8;
+        assert_eq!(Wav::from_bytes(&bytes), Err(Error::InvalidWav));
+
+        // Truncated data chunk
+        let bytes = wav_bytes(1, 16_000, &[0, 1, 2]);
+        assert_eq!(
+            Wav::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::InvalidWav)
+        );
+
+        // Chunk length at the limit of a u32
+        let mut bytes = wav_bytes(1, 16_000, &[0]);
+        bytes[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(Wav::from_bytes(&bytes), Err(Error::InvalidWav));
+    }
+
+    #[test]
+    fn raw_pcm() {
+        let wav = Wav::from_raw_pcm(&[1, 0, 0xff, 0xff, 7], 16_000);
+
+        assert_eq!(wav.samples, [1, -1]);
+    }
+
+    #[test]
+    fn resample() {
+        let wav = Wav {
+            sample_rate: 8_000,
+            samples: vec![0, 100, 200, 300],
+        };
+
+        assert_eq!(wav.resample(8_000), wav.samples);
+        assert_eq!(wav.resample(16_000), [0, 50, 100, 150, 200, 250, 300, 300]);
+        assert_eq!(wav.resample(4_000), [0, 200]);
+    }
+}
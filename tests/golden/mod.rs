@@ -0,0 +1,461 @@
+//! Golden output regression testing
+//!
+//! A `Golden` records the output tensors of an interpreter for a set of
+//! named cases to `tests/golden/<name>.golden`, and compares later runs
+//! against that file. Use it to check that updating TensorFlow does not
+//! silently change the numerics of the example models.
+//!
+//! A missing golden file is a test failure. When the `TFMICRO_BLESS`
+//! environment variable is set, the outputs are recorded instead of
+//! compared
+//!
+//! ```text
+//! TFMICRO_BLESS=1 cargo test
+//! ```
+//!
+//! Recording intermediate tensors is out of scope: only the output tensors
+//! are recorded. TensorFlow Micro reuses the arena memory of intermediate
+//! tensors once they have been consumed, so their values are no longer
+//! available after `invoke`.
+#![allow(dead_code)]
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use tfmicro::{ElementType, MicroInterpreter};
+
+/// Values of one output tensor
+#[derive(Clone, PartialEq, Debug)]
+struct Output {
+    index: usize,
+    name: String,
+    element_type: ElementType,
+    dims: Vec<i32>,
+    values: Vec<f64>,
+}
+
+/// Outputs of one case
+#[derive(Clone, PartialEq, Debug)]
+struct Case {
+    name: String,
+    outputs: Vec<Output>,
+}
+
+/// The first difference between a run and the golden outputs
+#[derive(Clone, PartialEq, Debug)]
+pub struct Divergence {
+    case: String,
+    output: usize,
+    name: String,
+    kind: DivergenceKind,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum DivergenceKind {
+    MissingCase,
+    OutputCount {
+        expected: usize,
+        actual: usize,
+    },
+    ElementType {
+        expected: ElementType,
+        actual: ElementType,
+    },
+    Dims {
+        expected: Vec<i32>,
+        actual: Vec<i32>,
+    },
+    Value {
+        element: usize,
+        expected: f64,
+        actual: f64,
+        tolerance: f64,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use DivergenceKind::*;
+
+        write!(f, "case `{}`: ", self.case)?;
+        match &self.kind {
+            MissingCase => write!(
+                f,
+                "no golden outputs recorded, re-record with TFMICRO_BLESS=1"
+            ),
+            OutputCount { expected, actual } => {
+                write!(f, "{} outputs, expected {}", actual, expected)
+            }
+            ElementType { expected, actual } => write!(
+                f,
+                "output {} (`{}`) has type {:?}, expected {:?}",
+                self.output, self.name, actual, expected
+            ),
+            Dims { expected, actual } => write!(
+                f,
+                "output {} (`{}`) has dims {:?}, expected {:?}",
+                self.output, self.name, actual, expected
+            ),
+            Value {
+                element,
+                expected,
+                actual,
+                tolerance,
+            } => write!(
+                f,
+                "output {} (`{}`) element {} is {}, expected {} \
+                 (tolerance {})",
+                self.output, self.name, element, actual, expected, tolerance
+            ),
+        }
+    }
+}
+
+/// Records or compares the outputs of a model against a golden file
+pub struct Golden {
+    path: PathBuf,
+    recording: bool,
+    tolerances: [(ElementType, f64); 4],
+    expected: Vec<Case>,
+    recorded: Vec<Case>,
+}
+
+impl Golden {
+    /// Golden outputs stored in `tests/golden/<name>.golden`
+    ///
+    /// Panics if the file does not exist or cannot be parsed, unless
+    /// recording.
+    pub fn new(name: &str) -> Self {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}.golden", name));
+        let recording = env::var_os("TFMICRO_BLESS").is_some();
+
+        let expected = if recording {
+            Vec::new()
+        } else {
+            let contents = fs::read_to_string(&path).unwrap_or_else(|e| {
+                panic!(
+                    "{}: {}, record it with TFMICRO_BLESS=1",
+                    path.display(),
+                    e
+                );
+            });
+            parse(&contents).unwrap_or_else(|e| {
+                panic!("{}: {}", path.display(), e);
+            })
+        };
+
+        Self {
+            path,
+            recording,
+            tolerances: [
+                (ElementType::Float32, 1e-5),
+                (ElementType::UInt8, 0.),
+                (ElementType::Int8, 0.),
+                (ElementType::Int32, 0.),
+            ],
+            expected,
+            recorded: Vec::new(),
+        }
+    }
+
+    /// Set the largest absolute difference allowed between elements of
+    /// type `element_type`. By default float32 values may differ by 1e-5,
+    /// and integer values must be equal
+    pub fn tolerance(
+        mut self,
+        element_type: ElementType,
+        tolerance: f64,
+    ) -> Self {
+        for entry in self.tolerances.iter_mut() {
+            if entry.0 == element_type {
+                entry.1 = tolerance;
+            }
+        }
+        self
+    }
+
+    fn tolerance_of(&self, element_type: ElementType) -> f64 {
+        self.tolerances
+            .iter()
+            .find(|entry| entry.0 == element_type)
+            .map_or(0., |entry| entry.1)
+    }
+
+    /// Returns true if outputs are being recorded rather than compared
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Record the outputs of `interpreter` as `case`, or compare them with
+    /// the golden outputs of `case`
+    pub fn compare(
+        &mut self,
+        case: &str,
+        interpreter: &MicroInterpreter,
+    ) -> Result<(), Divergence> {
+        let actual = Case {
+            name: case.to_string(),
+            outputs: outputs(interpreter),
+        };
+
+        if self.recording {
+            self.recorded.push(actual);
+            return Ok(());
+        }
+
+        let expected = self.expected.iter().find(|c| c.name == case).ok_or(
+            Divergence {
+                case: case.to_string(),
+                output: 0,
+                name: String::new(),
+                kind: DivergenceKind::MissingCase,
+            },
+        )?;
+        self.first_divergence(expected, &actual).map_or(Ok(()), Err)
+    }
+
+    /// Like `compare`, but panics at the first divergence
+    pub fn check(&mut self, case: &str, interpreter: &MicroInterpreter) {
+        if let Err(divergence) = self.compare(case, interpreter) {
+            panic!("{}: {}", self.path.display(), divergence);
+        }
+    }
+
+    /// Write the golden file, if recording
+    pub fn finish(self) {
+        if !self.recording {
+            return;
+        }
+
+        fs::create_dir_all(self.path.parent().unwrap()).unwrap();
+        fs::write(&self.path, format(&self.recorded)).unwrap();
+        eprintln!("Recorded golden outputs to {}", self.path.display());
+    }
+
+    fn first_divergence(
+        &self,
+        expected: &Case,
+        actual: &Case,
+    ) -> Option<Divergence> {
+        let divergence = |output: &Output, kind| Divergence {
+            case: actual.name.clone(),
+            output: output.index,
+            name: output.name.clone(),
+            kind,
+        };
+
+        if expected.outputs.len() != actual.outputs.len() {
+            return Some(Divergence {
+                case: actual.name.clone(),
+                output: 0,
+                name: String::new(),
+                kind: DivergenceKind::OutputCount {
+                    expected: expected.outputs.len(),
+                    actual: actual.outputs.len(),
+                },
+            });
+        }
+
+        for (e, a) in expected.outputs.iter().zip(&actual.outputs) {
+            if e.element_type != a.element_type {
+                return Some(divergence(
+                    a,
+                    DivergenceKind::ElementType {
+                        expected: e.element_type,
+                        actual: a.element_type,
+                    },
+                ));
+            }
+            if e.dims != a.dims || e.values.len() != a.values.len() {
+                return Some(divergence(
+                    a,
+                    DivergenceKind::Dims {
+                        expected: e.dims.clone(),
+                        actual: a.dims.clone(),
+                    },
+                ));
+            }
+
+            let tolerance = self.tolerance_of(a.element_type);
+            let diverging =
+                e.values.iter().zip(&a.values).position(|(x, y)| {
+                    !((x - y).abs() <= tolerance || (x.is_nan() && y.is_nan()))
+                });
+            if let Some(element) = diverging {
+                return Some(divergence(
+                    a,
+                    DivergenceKind::Value {
+                        element,
+                        expected: e.values[element],
+                        actual: a.values[element],
+                        tolerance,
+                    },
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+/// Copy the values of every output tensor of `interpreter`
+fn outputs(interpreter: &MicroInterpreter) -> Vec<Output> {
+    (0..interpreter.outputs_len())
+        .map(|index| {
+            let tensor = interpreter.output(index);
+            let info = tensor.info();
+            let values = match info.element_type {
+                ElementType::Float32 => {
+                    tensor.as_data::<f32>().iter().map(|&v| v.into()).collect()
+                }
+                ElementType::UInt8 => {
+                    tensor.as_data::<u8>().iter().map(|&v| v.into()).collect()
+                }
+                ElementType::Int8 => {
+                    tensor.as_data::<i8>().iter().map(|&v| v.into()).collect()
+                }
+                ElementType::Int32 => {
+                    tensor.as_data::<i32>().iter().map(|&v| v.into()).collect()
+                }
+            };
+
+            Output {
+                index,
+                name: info.name.to_string(),
+                element_type: info.element_type,
+                dims: info.dims.to_vec(),
+                values,
+            }
+        })
+        .collect()
+}
+
+const VALUES_PER_LINE: usize = 16;
+
+fn element_type_name(element_type: ElementType) -> &'static str {
+    match element_type {
+        ElementType::Float32 => "float32",
+        ElementType::UInt8 => "uint8",
+        ElementType::Int8 => "int8",
+        ElementType::Int32 => "int32",
+    }
+}
+
+/// The golden file format
+///
+/// ```text
+/// case yes
+/// output 0 uint8 [1,4] Identity
+/// 0 1 250 4
+/// ```
+///
+/// Float32 values are written with enough digits to be read back exactly.
+fn format(cases: &[Case]) -> String {
+    let mut s = String::from(
+        "# Golden outputs for the tfmicro tests. \
+         Re-record with TFMICRO_BLESS=1\n",
+    );
+
+    for case in cases {
+        s += &format!("case {}\n", case.name);
+        for output in &case.outputs {
+            let dims: Vec<String> =
+                output.dims.iter().map(|d| d.to_string()).collect();
+            let header = format!(
+                "output {} {} [{}] {}",
+                output.index,
+                element_type_name(output.element_type),
+                dims.join(","),
+                output.name
+            );
+            s += header.trim_end();
+            s += "\n";
+
+            for line in output.values.chunks(VALUES_PER_LINE) {
+                let values: Vec<String> = line
+                    .iter()
+                    .map(|&v| match output.element_type {
+                        ElementType::Float32 => format!("{:?}", v as f32),
+                        _ => (v as i64).to_string(),
+                    })
+                    .collect();
+                s += &values.join(" ");
+                s += "\n";
+            }
+        }
+    }
+
+    s
+}
+
+fn parse(contents: &str) -> Result<Vec<Case>, String> {
+    let mut cases: Vec<Case> = Vec::new();
+
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: &str| format!("line {}: {}", n + 1, message);
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("case ") {
+            cases.push(Case {
+                name: name.trim().to_string(),
+                outputs: Vec::new(),
+            });
+        } else if let Some(header) = line.strip_prefix("output ") {
+            let case = cases
+                .last_mut()
+                .ok_or_else(|| error("output before the first case"))?;
+
+            let mut fields = header.splitn(4, ' ');
+            let mut field = || fields.next().ok_or_else(|| error("bad output"));
+            let index = field()?.parse().map_err(|_| error("bad index"))?;
+            let element_type = match field()? {
+                "float32" => ElementType::Float32,
+                "uint8" => ElementType::UInt8,
+                "int8" => ElementType::Int8,
+                "int32" => ElementType::Int32,
+                _ => return Err(error("unknown element type")),
+            };
+            let dims = field()?
+                .strip_prefix('[')
+                .and_then(|dims| dims.strip_suffix(']'))
+                .ok_or_else(|| error("bad dims"))?
+                .split(',')
+                .filter(|d| !d.is_empty())
+                .map(|d| d.parse().map_err(|_| error("bad dims")))
+                .collect::<Result<_, _>>()?;
+            let name = fields.next().unwrap_or("").to_string();
+
+            case.outputs.push(Output {
+                index,
+                name,
+                element_type,
+                dims,
+                values: Vec::new(),
+            });
+        } else {
+            let output = cases
+                .last_mut()
+                .and_then(|case| case.outputs.last_mut())
+                .ok_or_else(|| error("values before the first output"))?;
+
+            for value in line.split_whitespace() {
+                // Read float32 values at the precision they were written
+                let value = match output.element_type {
+                    ElementType::Float32 => value.parse::<f32>().map(f64::from),
+                    _ => value.parse(),
+                };
+                output.values.push(value.map_err(|_| error("bad value"))?);
+            }
+        }
+    }
+
+    Ok(cases)
+}
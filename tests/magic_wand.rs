@@ -1,6 +1,11 @@
 //! magic_wand example
 //!
-use tfmicro::{MicroInterpreter, Model, MutableOpResolver};
+#![cfg(feature = "tensorflow")]
+
+use tfmicro::{
+    include_model, AccelerometerBuffer, MicroInterpreter, Model,
+    MutableOpResolver,
+};
 
 extern crate itertools;
 
@@ -8,12 +13,15 @@ use itertools::Itertools;
 use log::info;
 use ordered_float::NotNan;
 
+mod golden;
+use golden::Golden;
+
 #[test]
 fn magic_wand() {
     env_logger::init();
     info!("---- Starting tensorflow micro example: magic_wand");
 
-    let model = include_bytes!("../examples/models/magic_wand.tflite");
+    let model = include_model!("../examples/models/magic_wand.tflite");
     let ring =
         &include_bytes!("../examples/models/ring_micro_f9643d42_nohash_4.data")
             .chunks_exact(4)
@@ -27,37 +35,52 @@ fn magic_wand() {
     .collect_vec();
 
     // Instantiate the model from the file
-    let model = Model::from_buffer(&model[..]).unwrap();
+    let model = Model::from_buffer(model).unwrap();
 
     const TENSOR_ARENA_SIZE: usize = 60 * 1024;
     let mut tensor_arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
 
-    let micro_op_resolver = MutableOpResolver::empty()
+    let micro_op_resolver = MutableOpResolver::<5>::empty()
         .depthwise_conv_2d()
         .max_pool_2d()
         .conv_2d()
         .fully_connected()
         .softmax();
 
-    let mut interpreter =
-        MicroInterpreter::new(&model, micro_op_resolver, &mut tensor_arena[..])
-            .unwrap();
+    let mut interpreter = MicroInterpreter::new(
+        &model,
+        &micro_op_resolver,
+        &mut tensor_arena[..],
+    )
+    .unwrap();
+
+    // Outputs are compared with tests/golden/magic_wand.golden
+    let mut golden = Golden::new("magic_wand");
 
     // Four indices:
     // WingScore
     // RingScore
     // SlopeScore
     // NegativeScore
-    test_gesture(&mut interpreter, slope, 2);
-    test_gesture(&mut interpreter, ring, 1);
+    test_gesture(&mut interpreter, &mut golden, "slope", slope, 2);
+    test_gesture(&mut interpreter, &mut golden, "ring", ring, 1);
+
+    golden.finish();
 }
 
 fn test_gesture(
     interpreter: &mut MicroInterpreter,
+    golden: &mut Golden,
+    case: &str,
     data: &[f32],
     expected_idx: usize,
 ) {
-    interpreter.input(0, data).unwrap();
+    // Fill the input tensor in place from a ring buffer of samples
+    let mut buffer = AccelerometerBuffer::<128>::new();
+    buffer.push_flat(data);
+    assert!(buffer.is_full());
+
+    buffer.write_to(interpreter.input_tensor(0)).unwrap();
     assert_eq!(
         [1, 128, 3, 1],
         interpreter.input_info(0).dims,
@@ -78,7 +101,7 @@ fn test_gesture(
     // RingScore
     // SlopeScore
     // NegativeScore
-    dbg!(output_tensor.as_data::<f32>());
+    golden.check(case, interpreter);
     assert_eq!(
         output_tensor
             .as_data::<NotNan<f32>>()
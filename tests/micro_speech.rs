@@ -1,15 +1,20 @@
 //! micro_speech example
 
-use tfmicro::{MicroInterpreter, Model, MutableOpResolver};
+#![cfg(feature = "tensorflow")]
+
+use tfmicro::{include_model, MicroInterpreter, Model, MutableOpResolver};
 
 use log::info;
 
+mod golden;
+use golden::Golden;
+
 #[test]
 fn micro_speech() {
     env_logger::init();
     info!("---- Starting tensorflow micro example: micro_speech");
 
-    let model = include_bytes!("../examples/models/micro_speech.tflite");
+    let model = include_model!("../examples/models/micro_speech.tflite");
     let no =
         include_bytes!("../examples/models/no_micro_f9643d42_nohash_4.data");
     let yes =
@@ -17,7 +22,7 @@ fn micro_speech() {
 
     // Map the model into a usable data structure. This doesn't involve
     // any copying or parsing, it's a very lightweight operation.
-    let model = Model::from_buffer(&model[..]).unwrap();
+    let model = Model::from_buffer(model).unwrap();
 
     // Create an area of memory to use for input, output, and
     // intermediate arrays.
@@ -25,7 +30,7 @@ fn micro_speech() {
     let mut tensor_arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
 
     // Pull in all needed operation implementations
-    let micro_op_resolver = MutableOpResolver::empty()
+    let micro_op_resolver = MutableOpResolver::<3>::empty()
         .depthwise_conv_2d()
         .fully_connected()
         .softmax();
@@ -33,9 +38,15 @@ fn micro_speech() {
     info!("Resolver: {:?}", micro_op_resolver);
 
     // Build an interpreter to run the model with
-    let mut interpreter =
-        MicroInterpreter::new(&model, micro_op_resolver, &mut tensor_arena[..])
-            .unwrap();
+    let mut interpreter = MicroInterpreter::new(
+        &model,
+        &micro_op_resolver,
+        &mut tensor_arena[..],
+    )
+    .unwrap();
+
+    // Outputs are compared with tests/golden/micro_speech.golden
+    let mut golden = Golden::new("micro_speech");
 
     // Check properties of the input sensor
     assert_eq!([1, 49, 40, 1], interpreter.input_info(0).dims);
@@ -48,7 +59,7 @@ fn micro_speech() {
     let output_tensor = interpreter.output(0);
     assert_eq!([1, 4], output_tensor.info().dims);
 
-    dbg!(output_tensor.as_data::<u8>());
+    golden.check("yes", &interpreter);
     let silence_score: u8 = output_tensor.as_data()[0];
     let unknown_score: u8 = output_tensor.as_data()[1];
     let yes_score: u8 = output_tensor.as_data()[2];
@@ -67,7 +78,7 @@ fn micro_speech() {
     let output_tensor = interpreter.output(0);
     assert_eq!([1, 4], output_tensor.info().dims);
 
-    dbg!(output_tensor.as_data::<u8>());
+    golden.check("no", &interpreter);
     let silence_score: u8 = output_tensor.as_data()[0];
     let unknown_score: u8 = output_tensor.as_data()[1];
     let yes_score: u8 = output_tensor.as_data()[2];
@@ -80,5 +91,7 @@ fn micro_speech() {
     interpreter.arena_used_bytes();
     info!("Output Info: {:?}", output_tensor.info());
 
+    golden.finish();
+
     info!("---- Done");
 }
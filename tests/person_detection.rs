@@ -1,10 +1,16 @@
 //! person_detection example
 //!
-use tfmicro::{MicroInterpreter, Model, MutableOpResolver};
+#![cfg(feature = "tensorflow")]
+
+use tfmicro::image::{Image, Normalization, PixelFormat, Resize};
+use tfmicro::{include_model, MicroInterpreter, Model, MutableOpResolver};
 
 use itertools::Itertools;
 use log::info;
 
+mod golden;
+use golden::Golden;
+
 #[test]
 fn person_detection() {
     env_logger::init();
@@ -13,7 +19,7 @@ fn person_detection() {
 
     // Include trained model and test datasets
     let model =
-        include_bytes!("../examples/models/person_detection_grayscale.tflite");
+        include_model!("../examples/models/person_detection_grayscale.tflite");
     let no_person = include_bytes!(
         "../examples/models/no_person_image_data_grayscale.data"
     );
@@ -22,26 +28,42 @@ fn person_detection() {
 
     // Map the model into a usable data structure. This doesn't involve
     // any copying or parsing, it's a very lightweight operation.
-    let model = Model::from_buffer(&model[..]).unwrap();
+    let model = Model::from_buffer(model).unwrap();
 
     // Create memory area for input, output and intermediate arrays
     const TENSOR_ARENA_SIZE: usize = 93 * 1024;
     let mut tensor_arena: [u8; TENSOR_ARENA_SIZE] = [0; TENSOR_ARENA_SIZE];
 
-    let micro_op_resolver = MutableOpResolver::empty()
+    let micro_op_resolver = MutableOpResolver::<3>::empty()
         .depthwise_conv_2d()
         .conv_2d()
         .average_pool_2d();
 
     // Build an interpreter to run the model with
-    let mut interpreter =
-        MicroInterpreter::new(&model, micro_op_resolver, &mut tensor_arena[..])
-            .unwrap();
+    let mut interpreter = MicroInterpreter::new(
+        &model,
+        &micro_op_resolver,
+        &mut tensor_arena[..],
+    )
+    .unwrap();
+
+    // Outputs are compared with tests/golden/person_detection.golden
+    let mut golden = Golden::new("person_detection");
 
     // Check properties of the input sensor
-    interpreter.input(0, person).unwrap();
     assert_eq!([1, 96, 96, 1], interpreter.input_info(0).dims);
 
+    // The test images are already 96x96 grayscale, so are written into the
+    // input tensor unchanged
+    let person = Image::new(person, 96, 96, PixelFormat::Grayscale).unwrap();
+    person
+        .write_to(
+            interpreter.input_tensor(0),
+            Resize::Nearest,
+            Normalization::None,
+        )
+        .unwrap();
+
     info!("Created setup");
 
     // -------- 'person' example ------------
@@ -55,6 +77,7 @@ fn person_detection() {
         "Dimensions of output tensor"
     );
 
+    golden.check("person", &interpreter);
     assert_eq!(
         1,
         output_tensor.as_data::<u8>().iter().position_max().unwrap()
@@ -62,7 +85,15 @@ fn person_detection() {
     info!("---- Person output correct!");
 
     // ------- 'no person' example ----------
-    interpreter.input(0, no_person).unwrap();
+    let no_person =
+        Image::new(no_person, 96, 96, PixelFormat::Grayscale).unwrap();
+    no_person
+        .write_to(
+            interpreter.input_tensor(0),
+            Resize::Nearest,
+            Normalization::None,
+        )
+        .unwrap();
 
     interpreter.invoke().unwrap();
 
@@ -74,11 +105,14 @@ fn person_detection() {
         "Dimensions of output tensor"
     );
 
+    golden.check("no_person", &interpreter);
     assert_eq!(
         2,
         output_tensor.as_data::<u8>().iter().position_max().unwrap()
     );
     info!("---- No-person output correct!");
 
+    golden.finish();
+
     info!("---- Done");
 }